@@ -2,12 +2,44 @@ import postgres from "postgres";
 
 // ── Types ────────────────────────────────────────────────────────
 
+export type InheritanceMode = "override" | "intersect" | "union" | "isolate";
+
 export interface Resource {
   id: string;
   parent_id: string | null;
   name: string | null;
   capacity: number;
   buffer_after: number | null;
+  inheritance: InheritanceMode;
+  buffer_before: number | null;
+  /** Booking policy; unset fields are inherited from the nearest ancestor. */
+  min_duration: number | null;
+  max_duration: number | null;
+  min_lead_time: number | null;
+  max_horizon: number | null;
+  granularity_ms: number | null;
+  granularity_offset_ms: number;
+  /** Per-owner limit on allocations within any rolling window. */
+  quota_window_ms: number | null;
+  quota_max_count: number | null;
+  quota_max_duration_ms: number | null;
+  /** Set on composites: the resources each booking here also occupies. */
+  components: string[] | null;
+  /** Order among siblings; children one apart are adjacent. */
+  position: number | null;
+}
+
+export interface OwnerQuota {
+  windowMs: number;
+  maxCount?: number;
+  maxDurationMs?: number;
+}
+
+export interface BookingPolicy {
+  minDuration?: number | null;
+  maxDuration?: number | null;
+  minLeadTime?: number | null;
+  maxHorizon?: number | null;
 }
 
 export interface Rule {
@@ -16,14 +48,26 @@ export interface Rule {
   start: number;
   end: number;
   blocking: boolean;
+  /** Set for capacity rules, which override the resource's capacity within the span. */
+  capacity: number | null;
 }
 
+export type BookingStatus = "confirmed" | "cancelled" | "no_show" | "completed";
+
 export interface Booking {
   id: string;
   resource_id: string;
   start: number;
   end: number;
   label: string | null;
+  /** Per-booking overrides of the resource's buffers. */
+  buffer_before: number | null;
+  buffer_after: number | null;
+  owner: string | null;
+  metadata: Record<string, unknown> | null;
+  status: BookingStatus;
+  cancel_reason: string | null;
+  cancelled_at: number | null;
 }
 
 export interface Hold {
@@ -32,6 +76,10 @@ export interface Hold {
   start: number;
   end: number;
   expires_at: number;
+  buffer_before: number | null;
+  buffer_after: number | null;
+  owner: string | null;
+  metadata: Record<string, unknown> | null;
 }
 
 export interface Slot {
@@ -68,15 +116,25 @@ export class DeltaT {
 
   // ── Resources ────────────────────────────────────────────────
 
+  /** Create a resource. Without an `id` the server mints one. */
   async createResource(opts: {
-    id: string;
+    id?: string;
     parentId?: string | null;
     name?: string | null;
     capacity?: number;
     bufferAfter?: number | null;
+    bufferBefore?: number | null;
+    inheritance?: InheritanceMode;
+    policy?: BookingPolicy;
+    granularityMs?: number | null;
+    granularityOffsetMs?: number;
+    quota?: OwnerQuota;
+    /** Makes this a composite over these resources; fixed at creation. */
+    components?: string[];
+    position?: number | null;
   }): Promise<string> {
     const cols = ["id"];
-    const vals = [opts.id];
+    const vals = [opts.id ?? null!];
 
     if (opts.parentId !== undefined) {
       cols.push("parent_id");
@@ -94,17 +152,59 @@ export class DeltaT {
       cols.push("buffer_after");
       vals.push(opts.bufferAfter === null ? null! : String(opts.bufferAfter));
     }
+    if (opts.bufferBefore !== undefined) {
+      cols.push("buffer_before");
+      vals.push(opts.bufferBefore === null ? null! : String(opts.bufferBefore));
+    }
+    for (const [col, v] of policyColumns(opts.policy)) {
+      cols.push(col);
+      vals.push(v === null ? null! : String(v));
+    }
+    if (opts.granularityMs !== undefined) {
+      cols.push("granularity_ms");
+      vals.push(opts.granularityMs === null ? null! : String(opts.granularityMs));
+    }
+    if (opts.granularityOffsetMs !== undefined) {
+      cols.push("granularity_offset_ms");
+      vals.push(String(opts.granularityOffsetMs));
+    }
+    for (const [col, v] of quotaColumns(opts.quota)) {
+      cols.push(col);
+      vals.push(String(v));
+    }
+    if (opts.inheritance !== undefined) {
+      cols.push("inheritance");
+      vals.push(opts.inheritance);
+    }
+    if (opts.components !== undefined) {
+      cols.push("components");
+      vals.push(JSON.stringify(opts.components));
+    }
+    if (opts.position !== undefined) {
+      cols.push("position");
+      vals.push(opts.position === null ? null! : String(opts.position));
+    }
 
-    await this.sql.unsafe(
-      `INSERT INTO resources (${cols.join(", ")}) VALUES (${cols.map((_, i) => `$${i + 1}`).join(", ")})`,
+    const rows = await this.sql.unsafe(
+      `INSERT INTO resources (${cols.join(", ")}) VALUES (${cols.map((_, i) => `$${i + 1}`).join(", ")}) RETURNING id`,
       vals,
     );
-    return opts.id;
+    return rows[0].id;
   }
 
   async updateResource(
     id: string,
-    opts: { name?: string; capacity?: number; bufferAfter?: number | null },
+    opts: {
+      name?: string;
+      capacity?: number;
+      bufferAfter?: number | null;
+      bufferBefore?: number | null;
+      inheritance?: InheritanceMode;
+      policy?: BookingPolicy;
+      granularityMs?: number | null;
+      granularityOffsetMs?: number;
+      quota?: OwnerQuota;
+    },
   ): Promise<void> {
     const sets: string[] = [];
     const vals: (string | number | null)[] = [];
@@ -122,6 +222,30 @@ export class DeltaT {
       sets.push(`buffer_after = $${idx++}`);
       vals.push(opts.bufferAfter);
     }
+    if (opts.bufferBefore !== undefined) {
+      sets.push(`buffer_before = $${idx++}`);
+      vals.push(opts.bufferBefore);
+    }
+    for (const [col, v] of policyColumns(opts.policy)) {
+      sets.push(`${col} = $${idx++}`);
+      vals.push(v);
+    }
+    if (opts.granularityMs !== undefined) {
+      sets.push(`granularity_ms = $${idx++}`);
+      vals.push(opts.granularityMs);
+    }
+    if (opts.granularityOffsetMs !== undefined) {
+      sets.push(`granularity_offset_ms = $${idx++}`);
+      vals.push(opts.granularityOffsetMs);
+    }
+    for (const [col, v] of quotaColumns(opts.quota)) {
+      sets.push(`${col} = $${idx++}`);
+      vals.push(v);
+    }
+    if (opts.inheritance !== undefined) {
+      sets.push(`inheritance = $${idx++}`);
+      vals.push(opts.inheritance);
+    }
 
     vals.push(id);
     await this.sql.unsafe(
@@ -154,26 +278,27 @@ export class DeltaT {
   // ── Rules ────────────────────────────────────────────────────
 
   async addRule(opts: {
-    id: string;
+    id?: string;
     resourceId: string;
     start: number;
     end: number;
     blocking?: boolean;
+    capacity?: number | null;
   }): Promise<string> {
-    await this.sql.unsafe(
-      `INSERT INTO rules (id, resource_id, start, "end", blocking) VALUES ($1, $2, $3, $4, $5)`,
-      [opts.id, opts.resourceId, opts.start, opts.end, opts.blocking ?? false],
+    const rows = await this.sql.unsafe(
+      `INSERT INTO rules (id, resource_id, start, "end", blocking, capacity) VALUES ($1, $2, $3, $4, $5, $6) RETURNING id`,
+      [opts.id ?? null, opts.resourceId, opts.start, opts.end, opts.blocking ?? false, opts.capacity ?? null],
     );
-    return opts.id;
+    return rows[0].id;
   }
 
   async updateRule(
     id: string,
-    opts: { start: number; end: number; blocking?: boolean },
+    opts: { start: number; end: number; blocking?: boolean; capacity?: number | null },
   ): Promise<void> {
     await this.sql.unsafe(
-      `UPDATE rules SET start = $1, "end" = $2, blocking = $3 WHERE id = $4`,
-      [opts.start, opts.end, opts.blocking ?? false, id],
+      `UPDATE rules SET start = $1, "end" = $2, blocking = $3, capacity = $4 WHERE id = $5`,
+      [opts.start, opts.end, opts.blocking ?? false, opts.capacity ?? null, id],
     );
   }
 
@@ -189,32 +314,48 @@ export class DeltaT {
     return rows.map(toRule);
   }
 
+  /** Rules by id, in the order given. Unknown ids are skipped. */
+  async getRulesById(ids: string[]): Promise<Rule[]> {
+    if (ids.length === 0) return [];
+    const inList = ids.map((_, i) => `$${i + 1}`).join(", ");
+    const rows = await this.sql.unsafe(
+      `SELECT * FROM rules WHERE id IN (${inList})`,
+      ids,
+    );
+    return rows.map(toRule);
+  }
+
   // ── Bookings ─────────────────────────────────────────────────
 
   async book(opts: {
-    id: string;
+    id?: string;
     resourceId: string;
     start: number;
     end: number;
     label?: string | null;
+    bufferBefore?: number;
+    bufferAfter?: number;
+    owner?: string;
+    metadata?: Record<string, unknown>;
   }): Promise<string> {
+    const cols = ["id", "resource_id", "start", `"end"`];
+    const vals: (string | number | null)[] = [opts.id ?? null, opts.resourceId, opts.start, opts.end];
     if (opts.label !== undefined) {
-      await this.sql.unsafe(
-        `INSERT INTO bookings (id, resource_id, start, "end", label) VALUES ($1, $2, $3, $4, $5)`,
-        [opts.id, opts.resourceId, opts.start, opts.end, opts.label],
-      );
-    } else {
-      await this.sql.unsafe(
-        `INSERT INTO bookings (id, resource_id, start, "end") VALUES ($1, $2, $3, $4)`,
-        [opts.id, opts.resourceId, opts.start, opts.end],
-      );
+      cols.push("label");
+      vals.push(opts.label);
     }
-    return opts.id;
+    pushAllocOptions(cols, vals, opts);
+
+    const rows = await this.sql.unsafe(
+      `INSERT INTO bookings (${cols.join(", ")}) VALUES (${cols.map((_, i) => `$${i + 1}`).join(", ")}) RETURNING id`,
+      vals,
+    );
+    return rows[0].id;
   }
 
   async batchBook(
     bookings: Array<{
-      id: string;
+      id?: string;
       resourceId: string;
       start: number;
       end: number;
@@ -235,28 +376,96 @@ export class DeltaT {
         placeholders.push(
           `($${idx++}, $${idx++}, $${idx++}, $${idx++}, $${idx++})`,
         );
-        vals.push(b.id, b.resourceId, b.start, b.end, b.label ?? null);
+        vals.push(b.id ?? null, b.resourceId, b.start, b.end, b.label ?? null);
       } else {
         placeholders.push(`($${idx++}, $${idx++}, $${idx++}, $${idx++})`);
-        vals.push(b.id, b.resourceId, b.start, b.end);
+        vals.push(b.id ?? null, b.resourceId, b.start, b.end);
       }
     }
 
-    await this.sql.unsafe(
-      `INSERT INTO bookings ${cols} VALUES ${placeholders.join(", ")}`,
+    const rows = await this.sql.unsafe(
+      `INSERT INTO bookings ${cols} VALUES ${placeholders.join(", ")} RETURNING id`,
       vals,
     );
-    return bookings.map((b) => b.id);
+    return rows.map((r) => r.id);
   }
 
-  async cancelBooking(id: string): Promise<void> {
-    await this.sql.unsafe(`DELETE FROM bookings WHERE id = $1`, [id]);
+  /** Cancel a booking. It frees its time but stays queryable until GC. */
+  async cancelBooking(id: string, reason?: string): Promise<void> {
+    if (reason === undefined) {
+      await this.sql.unsafe(`DELETE FROM bookings WHERE id = $1`, [id]);
+    } else {
+      await this.sql.unsafe(
+        `UPDATE bookings SET status = 'cancelled', cancel_reason = $1 WHERE id = $2`,
+        [reason, id],
+      );
+    }
   }
 
-  async getBookings(resourceId: string): Promise<Booking[]> {
+  /** Mark a booking confirmed, no-show or completed. */
+  async setBookingStatus(
+    id: string,
+    status: Exclude<BookingStatus, "cancelled">,
+  ): Promise<void> {
+    await this.sql.unsafe(`UPDATE bookings SET status = $1 WHERE id = $2`, [
+      status,
+      id,
+    ]);
+  }
+
+  /** Bookings in one status, on a resource or across the tenant. */
+  async getBookingsByStatus(
+    status: BookingStatus,
+    resourceId?: string,
+  ): Promise<Booking[]> {
+    const rows =
+      resourceId === undefined
+        ? await this.sql.unsafe(`SELECT * FROM bookings WHERE status = $1`, [
+            status,
+          ])
+        : await this.sql.unsafe(
+            `SELECT * FROM bookings WHERE resource_id = $1 AND status = $2`,
+            [resourceId, status],
+          );
+    return rows.map(toBooking);
+  }
+
+  /** Bookings on a resource, optionally only those whose metadata contains `metadata`. */
+  async getBookings(
+    resourceId: string,
+    filter?: { metadata?: Record<string, unknown> },
+  ): Promise<Booking[]> {
+    let sql = `SELECT * FROM bookings WHERE resource_id = $1`;
+    const vals: string[] = [resourceId];
+    if (filter?.metadata !== undefined) {
+      sql += ` AND metadata @> $2`;
+      vals.push(JSON.stringify(filter.metadata));
+    }
+    const rows = await this.sql.unsafe(sql, vals);
+    return rows.map(toBooking);
+  }
+
+  async getBooking(id: string): Promise<Booking | null> {
+    const [booking] = await this.getBookingsById([id]);
+    return booking ?? null;
+  }
+
+  /** Bookings by id across every resource, in the order given. Unknown ids are skipped. */
+  async getBookingsById(ids: string[]): Promise<Booking[]> {
+    if (ids.length === 0) return [];
+    const inList = ids.map((_, i) => `$${i + 1}`).join(", ");
     const rows = await this.sql.unsafe(
-      `SELECT * FROM bookings WHERE resource_id = $1`,
-      [resourceId],
+      `SELECT * FROM bookings WHERE id IN (${inList})`,
+      ids,
+    );
+    return rows.map(toBooking);
+  }
+
+  /** Bookings of one owner across every resource. */
+  async getBookingsByOwner(owner: string): Promise<Booking[]> {
+    const rows = await this.sql.unsafe(
+      `SELECT * FROM bookings WHERE owner = $1`,
+      [owner],
     );
     return rows.map(toBooking);
   }
@@ -264,17 +473,31 @@ export class DeltaT {
   // ── Holds ────────────────────────────────────────────────────
 
   async placeHold(opts: {
-    id: string;
+    id?: string;
     resourceId: string;
     start: number;
     end: number;
     expiresAt: number;
+    bufferBefore?: number;
+    bufferAfter?: number;
+    owner?: string;
+    metadata?: Record<string, unknown>;
   }): Promise<string> {
-    await this.sql.unsafe(
-      `INSERT INTO holds (id, resource_id, start, "end", expires_at) VALUES ($1, $2, $3, $4, $5)`,
-      [opts.id, opts.resourceId, opts.start, opts.end, opts.expiresAt],
+    const cols = ["id", "resource_id", "start", `"end"`, "expires_at"];
+    const vals: (string | number | null)[] = [
+      opts.id ?? null,
+      opts.resourceId,
+      opts.start,
+      opts.end,
+      opts.expiresAt,
+    ];
+    pushAllocOptions(cols, vals, opts);
+
+    const rows = await this.sql.unsafe(
+      `INSERT INTO holds (${cols.join(", ")}) VALUES (${cols.map((_, i) => `$${i + 1}`).join(", ")}) RETURNING id`,
+      vals,
     );
-    return opts.id;
+    return rows[0].id;
   }
 
   async releaseHold(id: string): Promise<void> {
@@ -289,6 +512,29 @@ export class DeltaT {
     return rows.map(toHold);
   }
 
+  async getHold(id: string): Promise<Hold | null> {
+    const [hold] = await this.getHoldsById([id]);
+    return hold ?? null;
+  }
+
+  async getHoldsById(ids: string[]): Promise<Hold[]> {
+    if (ids.length === 0) return [];
+    const inList = ids.map((_, i) => `$${i + 1}`).join(", ");
+    const rows = await this.sql.unsafe(
+      `SELECT * FROM holds WHERE id IN (${inList})`,
+      ids,
+    );
+    return rows.map(toHold);
+  }
+
+  async getHoldsByOwner(owner: string): Promise<Hold[]> {
+    const rows = await this.sql.unsafe(
+      `SELECT * FROM holds WHERE owner = $1`,
+      [owner],
+    );
+    return rows.map(toHold);
+  }
+
   // ── Availability ─────────────────────────────────────────────
 
   async getAvailability(
@@ -360,6 +606,57 @@ export class DeltaT {
 
 // ── Row mappers ──────────────────────────────────────────────────
 
+function optNumber(v: unknown): number | null {
+  return v != null ? Number(v) : null;
+}
+
+function policyColumns(policy?: BookingPolicy): Array<[string, number | null]> {
+  if (!policy) return [];
+  const cols: Array<[string, number | null | undefined]> = [
+    ["min_duration", policy.minDuration],
+    ["max_duration", policy.maxDuration],
+    ["min_lead_time", policy.minLeadTime],
+    ["max_horizon", policy.maxHorizon],
+  ];
+  return cols.filter((c): c is [string, number | null] => c[1] !== undefined);
+}
+
+function quotaColumns(quota?: OwnerQuota): Array<[string, number]> {
+  if (!quota) return [];
+  const cols: Array<[string, number]> = [["quota_window_ms", quota.windowMs]];
+  if (quota.maxCount !== undefined) cols.push(["quota_max_count", quota.maxCount]);
+  if (quota.maxDurationMs !== undefined) cols.push(["quota_max_duration_ms", quota.maxDurationMs]);
+  return cols;
+}
+
+function pushAllocOptions(
+  cols: string[],
+  vals: (string | number | null)[],
+  opts: {
+    bufferBefore?: number;
+    bufferAfter?: number;
+    owner?: string;
+    metadata?: Record<string, unknown>;
+  },
+): void {
+  if (opts.bufferBefore !== undefined) {
+    cols.push("buffer_before");
+    vals.push(opts.bufferBefore);
+  }
+  if (opts.bufferAfter !== undefined) {
+    cols.push("buffer_after");
+    vals.push(opts.bufferAfter);
+  }
+  if (opts.owner !== undefined) {
+    cols.push("owner");
+    vals.push(opts.owner);
+  }
+  if (opts.metadata !== undefined) {
+    cols.push("metadata");
+    vals.push(JSON.stringify(opts.metadata));
+  }
+}
+
 function toResource(row: postgres.Row): Resource {
   return {
     id: row.id,
@@ -367,6 +664,19 @@ function toResource(row: postgres.Row): Resource {
     name: row.name ?? null,
     capacity: Number(row.capacity),
     buffer_after: row.buffer_after != null ? Number(row.buffer_after) : null,
+    inheritance: row.inheritance ?? "override",
+    buffer_before: optNumber(row.buffer_before),
+    min_duration: optNumber(row.min_duration),
+    max_duration: optNumber(row.max_duration),
+    min_lead_time: optNumber(row.min_lead_time),
+    max_horizon: optNumber(row.max_horizon),
+    granularity_ms: optNumber(row.granularity_ms),
+    granularity_offset_ms: Number(row.granularity_offset_ms ?? 0),
+    quota_window_ms: optNumber(row.quota_window_ms),
+    quota_max_count: optNumber(row.quota_max_count),
+    quota_max_duration_ms: optNumber(row.quota_max_duration_ms),
+    components: row.components != null ? JSON.parse(row.components) : null,
+    position: optNumber(row.position),
   };
 }
 
@@ -377,6 +687,7 @@ function toRule(row: postgres.Row): Rule {
     start: Number(row.start),
     end: Number(row.end),
     blocking: row.blocking === true || row.blocking === "t",
+    capacity: row.capacity != null ? Number(row.capacity) : null,
   };
 }
 
@@ -387,6 +698,13 @@ function toBooking(row: postgres.Row): Booking {
     start: Number(row.start),
     end: Number(row.end),
     label: row.label ?? null,
+    buffer_before: optNumber(row.buffer_before),
+    buffer_after: optNumber(row.buffer_after),
+    owner: row.owner ?? null,
+    metadata: row.metadata ?? null,
+    status: row.status,
+    cancel_reason: row.cancel_reason ?? null,
+    cancelled_at: optNumber(row.cancelled_at),
   };
 }
 
@@ -397,6 +715,10 @@ function toHold(row: postgres.Row): Hold {
     start: Number(row.start),
     end: Number(row.end),
     expires_at: Number(row.expires_at),
+    buffer_before: optNumber(row.buffer_before),
+    buffer_after: optNumber(row.buffer_after),
+    owner: row.owner ?? null,
+    metadata: row.metadata ?? null,
   };
 }
 
@@ -0,0 +1,73 @@
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::conflict::{snap_to_grid, validate_span};
+use super::{Engine, EngineError};
+
+/// How far either side of a refused span to look for free time on the same
+/// resource.
+const SEARCH_RADIUS_MS: Ms = 7 * 86_400_000;
+
+impl Engine {
+    /// Up to `count` feasible placements near `span` on `resource_id`: the
+    /// same resource at the nearest free times, and siblings free for `span`
+    /// itself. Ordered by how far each start moves from `span.start`; ties
+    /// go to the same resource, then siblings in creation order.
+    pub async fn suggest_alternatives(
+        &self,
+        resource_id: Ulid,
+        span: Span,
+        count: usize,
+    ) -> Result<Vec<(Ulid, Span)>, EngineError> {
+        validate_span(&span)?;
+        if count > MAX_ALTERNATIVES {
+            return Err(EngineError::LimitExceeded("too many alternatives requested"));
+        }
+        let Some(rs) = self.get_resource(&resource_id) else {
+            return Err(EngineError::NotFound(resource_id));
+        };
+        let (parent_id, settings) = {
+            let guard = rs.read().await;
+            (guard.parent_id, guard.settings.clone())
+        };
+        let duration = span.duration_ms();
+
+        // One placement per free gap: the grid start closest to the requested
+        // one, on whichever side of it.
+        let radius = ((MAX_QUERY_WINDOW_MS - duration) / 2).clamp(0, SEARCH_RADIUS_MS);
+        let window = Span::new((span.start - radius).max(MIN_VALID_TIMESTAMP_MS), span.end + radius);
+        let free = self
+            .compute_availability(resource_id, window.start, window.end, Some(duration))
+            .await?;
+        let mut found: Vec<(Ms, Ulid, Span)> = free
+            .iter()
+            .filter_map(|gap| {
+                let start = span.start.clamp(gap.start, gap.end - duration);
+                [settings.align_down(start), settings.align_up(start)]
+                    .into_iter()
+                    .filter(|t| gap.start <= *t && *t + duration <= gap.end)
+                    .min_by_key(|t| (t - span.start).abs())
+                    .map(|t| ((t - span.start).abs(), resource_id, Span::new(t, t + duration)))
+            })
+            .collect();
+
+        if let Some(parent_id) = parent_id {
+            for sibling in self.store.get_children(&parent_id) {
+                if sibling != resource_id && self.on_grid(sibling, &span).await && self.is_free_for(sibling, &span).await? {
+                    found.push((0, sibling, span));
+                }
+            }
+        }
+
+        // Stable: same-resource placements were pushed first.
+        found.sort_by_key(|(distance, ..)| *distance);
+        Ok(found.into_iter().take(count).map(|(_, rid, span)| (rid, span)).collect())
+    }
+    /// Whether `span` lies on the grid of `resource_id`.
+    async fn on_grid(&self, resource_id: Ulid, span: &Span) -> bool {
+        let Some(rs) = self.get_resource(&resource_id) else { return false };
+        snap_to_grid(&rs.read().await.settings, vec![*span]) == [*span]
+    }
+}
@@ -1,3 +1,6 @@
+use std::collections::BTreeMap;
+
+use crate::limits::MAX_BUFFER_MS;
 use crate::model::*;
 
 // ── Availability Algorithm ────────────────────────────────────────
@@ -5,25 +8,44 @@ use crate::model::*;
 /// Compute raw free intervals for a resource using its unified interval list
 /// plus inherited rules from ancestors.
 ///
-/// Non-blocking: OVERRIDE — if resource has own non-blocking rules, use those;
-/// otherwise fall back to inherited_non_blocking.
-/// Blocking: ACCUMULATE — own blocking + inherited_blocking are all subtracted.
+/// `calendar_rules` are the rules of calendars attached to the resource; they
+/// count as the resource's own rules.
+///
+/// Own and inherited rules are combined according to the resource's
+/// `InheritanceMode` (see `inherit_rules`).
 pub fn availability(
     resource: &ResourceState,
     query: &Span,
+    calendar_rules: &[Interval],
     inherited_non_blocking: &[Span],
     inherited_blocking: &[Span],
     now: Ms,
 ) -> Vec<Span> {
-    let buffer = resource.buffer_after.unwrap_or(0);
     let capacity = resource.capacity;
 
     // Step 1: Determine base non-blocking spans (using binary search)
     let mut own_non_blocking: Vec<Span> = Vec::new();
     let mut own_blocking: Vec<Span> = Vec::new();
+    let mut capacity_rules: Vec<(Span, u32)> = Vec::new();
     let mut active_allocs: Vec<Span> = Vec::new();
 
-    for interval in resource.overlapping(query) {
+    // Allocations just outside the query can still reach into it via buffers.
+    let alloc_window = Span::new(
+        (query.start - MAX_BUFFER_MS).max(0),
+        query.end + MAX_BUFFER_MS,
+    );
+    for interval in resource.overlapping(&alloc_window).chain(calendar_rules) {
+        match &interval.kind {
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => continue,
+            IntervalKind::Hold { options, .. } | IntervalKind::Booking { options, .. } => {
+                active_allocs.push(resource.footprint(&interval.span, options));
+                continue;
+            }
+            _ => {}
+        }
+        if !interval.span.overlaps(query) {
+            continue;
+        }
         let clamped = Span::new(
             interval.span.start.max(query.start),
             interval.span.end.min(query.end),
@@ -31,38 +53,32 @@ pub fn availability(
         match &interval.kind {
             IntervalKind::NonBlocking => own_non_blocking.push(clamped),
             IntervalKind::Blocking => own_blocking.push(clamped),
-            IntervalKind::Hold { expires_at } if *expires_at > now => {
-                let effective_end = interval.span.end + buffer;
-                active_allocs.push(Span::new(interval.span.start, effective_end));
-            }
-            IntervalKind::Booking { .. } => {
-                let effective_end = interval.span.end + buffer;
-                active_allocs.push(Span::new(interval.span.start, effective_end));
-            }
-            _ => {} // expired hold
+            IntervalKind::Capacity { capacity } => capacity_rules.push((clamped, *capacity)),
+            _ => {}
         }
     }
 
-    let mut free = if own_non_blocking.is_empty() {
-        inherited_non_blocking.to_vec()
-    } else {
-        own_non_blocking
-    };
-
-    free.sort_by_key(|s| s.start);
-    free = merge_overlapping(&free);
-
-    // Step 2: Collect ALL blocking rules (own + inherited)
-    let mut blocked = own_blocking;
-    blocked.extend_from_slice(inherited_blocking);
-    blocked.sort_by_key(|s| s.start);
+    // Step 2: Combine own and inherited rules per the inheritance mode
+    let (mut free, blocked) = inherit_rules(
+        resource.settings.inheritance,
+        own_non_blocking,
+        own_blocking,
+        inherited_non_blocking,
+        inherited_blocking,
+    );
 
     if !blocked.is_empty() {
         free = subtract_intervals(&free, &blocked);
     }
 
     // Step 3: Subtract active allocations (with capacity awareness)
-    if !active_allocs.is_empty() {
+    if !capacity_rules.is_empty() {
+        active_allocs.sort_by_key(|s| s.start);
+        let saturated = compute_saturated_spans_piecewise(&active_allocs, capacity, &capacity_rules);
+        if !saturated.is_empty() {
+            free = subtract_intervals(&free, &saturated);
+        }
+    } else if !active_allocs.is_empty() {
         active_allocs.sort_by_key(|s| s.start);
         if capacity <= 1 {
             free = subtract_intervals(&free, &active_allocs);
@@ -77,6 +93,69 @@ pub fn availability(
     free
 }
 
+/// Combine a resource's own rules with the effective rules of its parent.
+/// Returns `(non_blocking, blocking)`, each sorted and merged.
+///
+/// - Override: own non-blocking rules replace inherited ones if any exist.
+/// - Intersect: open only where both own and inherited rules are open; with
+///   no own non-blocking rules the inherited ones apply unchanged.
+/// - Union: open where either is open.
+/// - Isolate: inherited rules are dropped, blocking included.
+///
+/// Blocking rules accumulate in every mode except Isolate.
+pub fn inherit_rules(
+    mode: InheritanceMode,
+    mut own_non_blocking: Vec<Span>,
+    mut own_blocking: Vec<Span>,
+    inherited_non_blocking: &[Span],
+    inherited_blocking: &[Span],
+) -> (Vec<Span>, Vec<Span>) {
+    let mut non_blocking = match mode {
+        InheritanceMode::Override if own_non_blocking.is_empty() => inherited_non_blocking.to_vec(),
+        InheritanceMode::Override | InheritanceMode::Isolate => own_non_blocking,
+        InheritanceMode::Intersect if own_non_blocking.is_empty() => inherited_non_blocking.to_vec(),
+        InheritanceMode::Intersect => {
+            own_non_blocking.sort_by_key(|s| s.start);
+            let mut inherited = inherited_non_blocking.to_vec();
+            inherited.sort_by_key(|s| s.start);
+            intersect_intervals(&merge_overlapping(&own_non_blocking), &merge_overlapping(&inherited))
+        }
+        InheritanceMode::Union => {
+            own_non_blocking.extend_from_slice(inherited_non_blocking);
+            own_non_blocking
+        }
+    };
+    non_blocking.sort_by_key(|s| s.start);
+    let non_blocking = merge_overlapping(&non_blocking);
+
+    if mode != InheritanceMode::Isolate {
+        own_blocking.extend_from_slice(inherited_blocking);
+    }
+    own_blocking.sort_by_key(|s| s.start);
+    let blocking = merge_overlapping(&own_blocking);
+
+    (non_blocking, blocking)
+}
+
+/// Intersection of two sorted, disjoint interval lists.
+pub fn intersect_intervals(a: &[Span], b: &[Span]) -> Vec<Span> {
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let start = a[i].start.max(b[j].start);
+        let end = a[i].end.min(b[j].end);
+        if start < end {
+            result.push(Span::new(start, end));
+        }
+        if a[i].end < b[j].end {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    result
+}
+
 /// Merge sorted overlapping/adjacent intervals into disjoint intervals.
 pub fn merge_overlapping(sorted: &[Span]) -> Vec<Span> {
     let mut merged: Vec<Span> = Vec::new();
@@ -162,6 +241,72 @@ pub fn compute_saturated_spans(allocs: &[Span], capacity: u32) -> Vec<Span> {
     result
 }
 
+/// Like `compute_saturated_spans`, but with capacity varying over time:
+/// each `(span, capacity)` rule overrides `base_capacity` within its span, the
+/// lowest capacity winning where rules overlap. A capacity of 0 saturates its
+/// span even with no allocations. As elsewhere, a base capacity of 0 counts as 1.
+pub fn compute_saturated_spans_piecewise(
+    allocs: &[Span],
+    base_capacity: u32,
+    capacity_rules: &[(Span, u32)],
+) -> Vec<Span> {
+    enum Change {
+        Alloc(i32),
+        CapacityStart(u32),
+        CapacityEnd(u32),
+    }
+
+    let mut events: Vec<(Ms, Change)> = Vec::with_capacity((allocs.len() + capacity_rules.len()) * 2);
+    for a in allocs {
+        events.push((a.start, Change::Alloc(1)));
+        events.push((a.end, Change::Alloc(-1)));
+    }
+    for (span, cap) in capacity_rules {
+        events.push((span.start, Change::CapacityStart(*cap)));
+        events.push((span.end, Change::CapacityEnd(*cap)));
+    }
+    events.sort_by_key(|e| e.0);
+
+    let base = base_capacity.max(1);
+    let mut active_caps: BTreeMap<u32, usize> = BTreeMap::new();
+    let mut result = Vec::new();
+    let mut count: i32 = 0;
+    let mut saturated_start: Option<Ms> = None;
+
+    let mut i = 0;
+    while i < events.len() {
+        let time = events[i].0;
+        // Apply every event at this instant before evaluating the segment after it.
+        while i < events.len() && events[i].0 == time {
+            match events[i].1 {
+                Change::Alloc(delta) => count += delta,
+                Change::CapacityStart(cap) => *active_caps.entry(cap).or_insert(0) += 1,
+                Change::CapacityEnd(cap) => {
+                    if let Some(n) = active_caps.get_mut(&cap) {
+                        *n -= 1;
+                        if *n == 0 {
+                            active_caps.remove(&cap);
+                        }
+                    }
+                }
+            }
+            i += 1;
+        }
+
+        let capacity = active_caps.keys().next().copied().unwrap_or(base);
+        let saturated = count >= capacity as i32;
+        if saturated && saturated_start.is_none() {
+            saturated_start = Some(time);
+        } else if !saturated
+            && let Some(start) = saturated_start.take()
+            && time > start {
+                result.push(Span::new(start, time));
+            }
+    }
+
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -197,7 +342,7 @@ mod tests {
         Interval {
             id: ulid::Ulid::new(),
             span: Span::new(start, end),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         }
     }
 
@@ -205,7 +350,7 @@ mod tests {
         Interval {
             id: ulid::Ulid::new(),
             span: Span::new(start, end),
-            kind: IntervalKind::Hold { expires_at },
+            kind: IntervalKind::Hold { expires_at, options: AllocOptions::default() },
         }
     }
 
@@ -305,7 +450,7 @@ mod tests {
             booking(ten, ten_thirty),
         ]);
         let query = Span::new(0, 24 * H);
-        let free = availability(&rs, &query, &[], &[], 0);
+        let free = availability(&rs, &query, &[], &[], &[], 0);
         assert_eq!(free.len(), 2);
         assert_eq!(free[0], Span::new(nine, ten));
         assert_eq!(free[1], Span::new(ten_thirty, twelve));
@@ -316,7 +461,7 @@ mod tests {
         let rs = make_resource(vec![]);
         let inherited = vec![Span::new(9 * H, 17 * H)];
         let query = Span::new(0, 24 * H);
-        let free = availability(&rs, &query, &inherited, &[], 0);
+        let free = availability(&rs, &query, &[], &inherited, &[], 0);
         assert_eq!(free, vec![Span::new(9 * H, 17 * H)]);
     }
 
@@ -325,7 +470,7 @@ mod tests {
         let rs = make_resource(vec![rule(14 * H, 16 * H, false)]);
         let inherited = vec![Span::new(9 * H, 17 * H)];
         let query = Span::new(0, 24 * H);
-        let free = availability(&rs, &query, &inherited, &[], 0);
+        let free = availability(&rs, &query, &[], &inherited, &[], 0);
         assert_eq!(free, vec![Span::new(14 * H, 16 * H)]);
     }
 
@@ -334,7 +479,7 @@ mod tests {
         let rs = make_resource(vec![rule(9 * H, 17 * H, false)]);
         let inherited_blocking = vec![Span::new(12 * H, 13 * H)];
         let query = Span::new(0, 24 * H);
-        let free = availability(&rs, &query, &[], &inherited_blocking, 0);
+        let free = availability(&rs, &query, &[], &[], &inherited_blocking, 0);
         assert_eq!(
             free,
             vec![Span::new(9 * H, 12 * H), Span::new(13 * H, 17 * H)]
@@ -352,7 +497,7 @@ mod tests {
         ]);
         let query = Span::new(0, ten + H);
         let now = 1000;
-        let free = availability(&rs, &query, &[], &[], now);
+        let free = availability(&rs, &query, &[], &[], &[], now);
         assert_eq!(free, vec![Span::new(nine, ten)]);
     }
 
@@ -368,13 +513,30 @@ mod tests {
             rule(ten, eleven, true),
         ]);
         let query = Span::new(0, twelve + H);
-        let free = availability(&rs, &query, &[], &[], 0);
+        let free = availability(&rs, &query, &[], &[], &[], 0);
         assert_eq!(
             free,
             vec![Span::new(nine, ten), Span::new(eleven, twelve)]
         );
     }
 
+    #[test]
+    fn availability_calendar_rules_count_as_own() {
+        let rs = make_resource(vec![rule(9 * H, 12 * H, false)]);
+        let calendar = vec![rule(14 * H, 17 * H, false), rule(10 * H, 11 * H, true)];
+        let inherited = vec![Span::new(0, 24 * H)];
+        let query = Span::new(0, 24 * H);
+        let free = availability(&rs, &query, &calendar, &inherited, &[], 0);
+        assert_eq!(
+            free,
+            vec![
+                Span::new(9 * H, 10 * H),
+                Span::new(11 * H, 12 * H),
+                Span::new(14 * H, 17 * H),
+            ]
+        );
+    }
+
     // ── compute_saturated_spans ────────────────────────────
 
     #[test]
@@ -414,4 +576,49 @@ mod tests {
         let sat = compute_saturated_spans(&[], 5);
         assert!(sat.is_empty());
     }
+
+    #[test]
+    fn saturated_spans_piecewise_lower_capacity_window() {
+        // Two overlapping allocations: fine at capacity 3, saturated where capacity drops to 1.
+        let allocs = vec![Span::new(0, 100), Span::new(0, 100)];
+        let sat = compute_saturated_spans_piecewise(&allocs, 3, &[(Span::new(40, 60), 1)]);
+        assert_eq!(sat, vec![Span::new(40, 60)]);
+    }
+
+    #[test]
+    fn saturated_spans_piecewise_lowest_rule_wins() {
+        let allocs = vec![Span::new(0, 100), Span::new(0, 100)];
+        let rules = [(Span::new(0, 50), 5), (Span::new(20, 30), 2)];
+        let sat = compute_saturated_spans_piecewise(&allocs, 5, &rules);
+        assert_eq!(sat, vec![Span::new(20, 30)]);
+    }
+
+    #[test]
+    fn saturated_spans_piecewise_zero_capacity_closes_span() {
+        let sat = compute_saturated_spans_piecewise(&[], 3, &[(Span::new(10, 20), 0)]);
+        assert_eq!(sat, vec![Span::new(10, 20)]);
+    }
+
+    #[test]
+    fn saturated_spans_piecewise_without_rules_matches_flat() {
+        let allocs = vec![Span::new(0, 100), Span::new(25, 75), Span::new(50, 150)];
+        assert_eq!(
+            compute_saturated_spans_piecewise(&allocs, 3, &[]),
+            compute_saturated_spans(&allocs, 3)
+        );
+    }
+
+    #[test]
+    fn availability_capacity_rule_reduces_capacity() {
+        let mut rs = make_resource_with_capacity(vec![rule(0, 10 * H, false)], 2, None);
+        rs.insert_interval(Interval {
+            id: ulid::Ulid::new(),
+            span: Span::new(4 * H, 6 * H),
+            kind: IntervalKind::Capacity { capacity: 1 },
+        });
+        rs.insert_interval(booking(3 * H, 7 * H));
+        let query = Span::new(0, 10 * H);
+        let free = availability(&rs, &query, &[], &[], &[], 0);
+        assert_eq!(free, vec![Span::new(0, 4 * H), Span::new(6 * H, 10 * H)]);
+    }
 }
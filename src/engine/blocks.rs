@@ -0,0 +1,88 @@
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::conflict::{check_no_conflict, now_ms, validate_span};
+use super::{ConflictDetail, Engine, EngineError};
+
+impl Engine {
+    /// Book `count` adjacent children of `parent_id` for `span` as one atomic
+    /// batch, choosing the first free run in position order. Children without
+    /// a position are never chosen. Returns `(booking id, resource id)` pairs
+    /// in position order. If no run is free, fails with a `Conflict` listing
+    /// what holds the positioned children over `span`, on the first such
+    /// allocation or on `parent_id` if none is in the way.
+    pub async fn book_adjacent_children(
+        &self,
+        parent_id: Ulid,
+        count: usize,
+        span: Span,
+        label: Option<String>,
+    ) -> Result<Vec<(Ulid, Ulid)>, EngineError> {
+        validate_span(&span)?;
+        if count == 0 {
+            return Err(EngineError::InvalidArgument("block size must be at least 1"));
+        }
+        if count > MAX_BATCH_SIZE {
+            return Err(EngineError::LimitExceeded("block size out of range"));
+        }
+        if !self.store.contains_resource(&parent_id) {
+            return Err(EngineError::NotFound(parent_id));
+        }
+
+        let mut seats = Vec::new();
+        for child in self.store.get_children(&parent_id) {
+            let Some(rs) = self.get_resource(&child) else { continue };
+            if let Some(position) = rs.read().await.settings.position {
+                seats.push((position, child));
+            }
+        }
+        seats.sort();
+
+        let now = now_ms();
+        let mut detail = ConflictDetail { saturated: vec![span], ..Default::default() };
+        let mut free = Vec::with_capacity(seats.len());
+        for (_, child) in &seats {
+            let is_free = self.is_free_for(*child, &span).await?;
+            if !is_free
+                && let Some(rs) = self.get_resource(child)
+                && let Err(
+                    EngineError::Conflict(_, taken) | EngineError::CapacityExceeded(_, taken),
+                ) = check_no_conflict(&*rs.read().await, &[], &span, &span, now)
+            {
+                detail.intervals.extend(taken.intervals);
+            }
+            free.push(is_free);
+        }
+
+        for start in 0..seats.len().saturating_sub(count - 1) {
+            let run = &seats[start..start + count];
+            let adjacent = run.windows(2).all(|w| w[1].0 == w[0].0 + 1);
+            if !adjacent || !free[start..start + count].iter().all(|f| *f) {
+                continue;
+            }
+            let bookings: Vec<_> = run.iter().map(|(_, rid)| (Ulid::new(), *rid, span, label.clone())).collect();
+            let chosen = bookings.iter().map(|(id, rid, ..)| (*id, *rid)).collect();
+            match self.batch_confirm_bookings(bookings).await {
+                Ok(()) => return Ok(chosen),
+                // Taken since availability was read, or the run as a whole
+                // would exceed an exclusion group's limit.
+                Err(
+                    EngineError::Conflict(_, taken)
+                    | EngineError::CapacityExceeded(_, taken)
+                    | EngineError::GroupLimitReached(_, taken),
+                ) => {
+                    for interval in taken.intervals {
+                        if !detail.intervals.iter().any(|i| i.id == interval.id) {
+                            detail.intervals.push(interval);
+                        }
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let first = detail.intervals.first().map_or(parent_id, |i| i.id);
+        Err(EngineError::Conflict(first, Box::new(detail)))
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::availability::{
+    compute_saturated_spans, compute_saturated_spans_piecewise, inherit_rules, intersect_intervals,
+    merge_overlapping, subtract_intervals,
+};
+use super::conflict::{now_ms, validate_span};
+use super::{Engine, EngineError};
+
+/// An active hold or booking: its id, kind, span and footprint.
+type Allocation = (Ulid, BusyCause, Span, Span);
+
+impl Engine {
+    /// The unavailable parts of `resource_id` within `[start, end)`, each
+    /// with its cause. Causes can overlap, so a blocked instant may appear in
+    /// several segments; time that is blocked but matches no listed cause is
+    /// reported as `Policy`. A composite reports its own rules alongside the
+    /// causes of its components, where its allocations show up.
+    pub async fn compute_busy(&self, resource_id: Ulid, start: Ms, end: Ms) -> Result<Vec<BusySegment>, EngineError> {
+        let query = Span::new(start, end);
+        validate_span(&query)?;
+        if end - start > MAX_QUERY_WINDOW_MS {
+            return Err(EngineError::LimitExceeded("query window too wide"));
+        }
+        let rs = self.get_resource(&resource_id).ok_or(EngineError::NotFound(resource_id))?;
+
+        let free = self.compute_availability(resource_id, start, end, None).await?;
+        let blocked = subtract_intervals(&[query], &free);
+        if blocked.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let components = rs.read().await.settings.components.clone();
+        let mut causes = self.busy_causes(resource_id, &query, components.is_empty()).await?;
+        for rid in components {
+            causes.extend(self.busy_causes(rid, &query, true).await?);
+        }
+
+        let mut explained: Vec<Span> = causes.iter().map(|c| c.span).collect();
+        explained.sort_by_key(|s| s.start);
+        for span in subtract_intervals(&blocked, &merge_overlapping(&explained)) {
+            causes.push(BusySegment { span, cause: BusyCause::Policy, source_id: resource_id, interval_ids: Vec::new() });
+        }
+
+        let mut segments = Vec::new();
+        for cause in causes {
+            for span in intersect_intervals(&[cause.span], &blocked) {
+                segments.push(BusySegment { span, ..cause.clone() });
+            }
+        }
+        segments.sort_by_key(|s| (s.span.start, s.span.end, s.cause));
+        Ok(segments)
+    }
+
+    /// Everything that takes time on a resource away, unclipped to what is
+    /// actually blocked. Without `with_allocations`, only its rules.
+    async fn busy_causes(
+        &self,
+        resource_id: Ulid,
+        query: &Span,
+        with_allocations: bool,
+    ) -> Result<Vec<BusySegment>, EngineError> {
+        let Some(rs) = self.get_resource(&resource_id) else { return Ok(Vec::new()) };
+        let now = now_ms();
+        let alloc_window = Span::new((query.start - MAX_BUFFER_MS).max(0), query.end + MAX_BUFFER_MS);
+
+        // Read before taking the resource's own lock, as availability does.
+        let mut shared = Vec::new();
+        for cid in self.store.get_composites(&resource_id) {
+            if let Some(composite) = self.get_resource(&cid) {
+                let intervals = composite.read().await.shared_allocations(&alloc_window);
+                shared.extend(intervals.into_iter().map(|i| (cid, i)));
+            }
+        }
+        let mut segments = self.group_limit_segments(resource_id, &alloc_window, now).await;
+
+        let guard = rs.read().await;
+        let calendar_rules = self.collect_calendar_rules(&guard.calendar_ids, query).await;
+        let (inherited_non_blocking, inherited_blocking) = self.collect_inherited_rules(&guard, query).await?;
+
+        let mut own_non_blocking = Vec::new();
+        let mut own_blocking = Vec::new();
+        let mut capacity_rules = Vec::new();
+        for interval in guard.overlapping(query).chain(&calendar_rules) {
+            let Some(span) = intersect_intervals(&[interval.span], &[*query]).pop() else { continue };
+            match &interval.kind {
+                IntervalKind::NonBlocking => own_non_blocking.push(span),
+                IntervalKind::Blocking => {
+                    own_blocking.push(span);
+                    segments.push(BusySegment {
+                        span,
+                        cause: BusyCause::BlockingRule,
+                        source_id: resource_id,
+                        interval_ids: vec![interval.id],
+                    });
+                }
+                IntervalKind::Capacity { capacity } => capacity_rules.push((interval.id, span, *capacity)),
+                _ => {}
+            }
+        }
+
+        let (open, _) = inherit_rules(
+            guard.settings.inheritance,
+            own_non_blocking,
+            own_blocking,
+            &inherited_non_blocking,
+            &inherited_blocking,
+        );
+        for span in subtract_intervals(&[*query], &open) {
+            segments.push(BusySegment { span, cause: BusyCause::NoOpenRule, source_id: resource_id, interval_ids: Vec::new() });
+        }
+        segments.extend(self.inherited_rule_segments(&guard, query).await?);
+        if !with_allocations {
+            return Ok(segments);
+        }
+
+        let mut allocations: Vec<(Ulid, Allocation)> = active_allocations(&guard, guard.overlapping(&alloc_window), now)
+            .into_iter()
+            .map(|a| (resource_id, a))
+            .collect();
+        for (cid, interval) in &shared {
+            allocations.extend(active_allocations(&guard, std::iter::once(interval), now).into_iter().map(|a| (*cid, a)));
+        }
+
+        if capacity_rules.is_empty() && guard.capacity <= 1 {
+            for (source_id, (id, cause, span, footprint)) in allocations {
+                segments.push(BusySegment { span, cause, source_id, interval_ids: vec![id] });
+                for buffer in subtract_intervals(&[footprint], &[span]) {
+                    segments.push(BusySegment { span: buffer, cause: BusyCause::Buffer, source_id, interval_ids: vec![id] });
+                }
+            }
+        } else {
+            let mut footprints: Vec<Span> = allocations.iter().map(|(_, (.., footprint))| *footprint).collect();
+            footprints.sort_by_key(|s| s.start);
+            let rules: Vec<(Span, u32)> = capacity_rules.iter().map(|(_, span, cap)| (*span, *cap)).collect();
+            for span in compute_saturated_spans_piecewise(&footprints, guard.capacity, &rules) {
+                let rule_ids = capacity_rules.iter().filter(|(_, s, _)| s.overlaps(&span)).map(|(id, ..)| *id);
+                let alloc_ids = allocations.iter().filter(|(_, (.., f))| f.overlaps(&span)).map(|(_, (id, ..))| *id);
+                segments.push(BusySegment {
+                    span,
+                    cause: BusyCause::Capacity,
+                    source_id: resource_id,
+                    interval_ids: rule_ids.chain(alloc_ids).collect(),
+                });
+            }
+        }
+        Ok(segments)
+    }
+
+    /// Blocking rules inherited from each ancestor, walking up as
+    /// `collect_inherited_rules` does.
+    async fn inherited_rule_segments(
+        &self,
+        resource: &ResourceState,
+        query: &Span,
+    ) -> Result<Vec<BusySegment>, EngineError> {
+        let mut segments = Vec::new();
+        if resource.settings.inheritance == InheritanceMode::Isolate {
+            return Ok(segments);
+        }
+        let mut current_parent_id = resource.parent_id;
+        let mut visited = HashSet::from([resource.id]);
+        let mut depth = 0usize;
+        while let Some(pid) = current_parent_id {
+            depth += 1;
+            if depth > MAX_HIERARCHY_DEPTH {
+                return Err(EngineError::LimitExceeded("hierarchy too deep"));
+            }
+            if !visited.insert(pid) {
+                return Err(EngineError::CycleDetected(pid));
+            }
+            let parent_rs = self.get_resource(&pid).ok_or(EngineError::NotFound(pid))?;
+            let parent_guard = parent_rs.read().await;
+            let calendar_rules = self.collect_calendar_rules(&parent_guard.calendar_ids, query).await;
+            for interval in parent_guard.overlapping(query).chain(&calendar_rules) {
+                if interval.kind != IntervalKind::Blocking {
+                    continue;
+                }
+                if let Some(span) = intersect_intervals(&[interval.span], &[*query]).pop() {
+                    segments.push(BusySegment {
+                        span,
+                        cause: BusyCause::InheritedRule,
+                        source_id: pid,
+                        interval_ids: vec![interval.id],
+                    });
+                }
+            }
+            if parent_guard.settings.inheritance == InheritanceMode::Isolate {
+                break;
+            }
+            current_parent_id = parent_guard.parent_id;
+        }
+        Ok(segments)
+    }
+
+    /// Where an exclusion group of `resource_id` is at its limit, with the
+    /// member allocations involved.
+    async fn group_limit_segments(&self, resource_id: Ulid, window: &Span, now: Ms) -> Vec<BusySegment> {
+        let mut segments = Vec::new();
+        for group in self.store.groups_of(&resource_id) {
+            let mut allocations = Vec::new();
+            for member in &group.members {
+                let Some(rs) = self.get_resource(member) else { continue };
+                let shared = self.shared_allocations(*member, window).await;
+                let guard = rs.read().await;
+                let own = guard.overlapping(window).chain(shared.iter().filter(|i| i.span.overlaps(window)));
+                allocations.extend(active_allocations(&guard, own, now));
+            }
+            let mut footprints: Vec<Span> = allocations.iter().map(|(.., footprint)| *footprint).collect();
+            footprints.sort_by_key(|s| s.start);
+            for span in compute_saturated_spans(&footprints, group.limit) {
+                segments.push(BusySegment {
+                    span,
+                    cause: BusyCause::GroupLimit,
+                    source_id: group.id,
+                    interval_ids: allocations.iter().filter(|(.., f)| f.overlaps(&span)).map(|(id, ..)| *id).collect(),
+                });
+            }
+        }
+        segments
+    }
+}
+
+/// The unexpired holds and bookings among `intervals`, with their footprints
+/// on `rs`.
+fn active_allocations<'a>(
+    rs: &ResourceState,
+    intervals: impl Iterator<Item = &'a Interval>,
+    now: Ms,
+) -> Vec<Allocation> {
+    intervals
+        .filter_map(|interval| match &interval.kind {
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => None,
+            IntervalKind::Hold { options, .. } => {
+                Some((interval.id, BusyCause::Hold, interval.span, rs.footprint(&interval.span, options)))
+            }
+            IntervalKind::Booking { options, .. } => {
+                Some((interval.id, BusyCause::Booking, interval.span, rs.footprint(&interval.span, options)))
+            }
+            _ => None,
+        })
+        .collect()
+}
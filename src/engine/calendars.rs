@@ -0,0 +1,266 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use tokio::sync::{RwLock, RwLockWriteGuard};
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::conflict::validate_span;
+use super::{Engine, EngineError};
+
+impl Engine {
+    pub async fn create_calendar(&self, id: Ulid, name: Option<String>) -> Result<(), EngineError> {
+        if self.store.calendar_count() >= MAX_CALENDARS_PER_TENANT {
+            return Err(EngineError::LimitExceeded("too many calendars"));
+        }
+        if let Some(ref n) = name
+            && n.len() > MAX_NAME_LEN {
+                return Err(EngineError::LimitExceeded("calendar name too long"));
+            }
+        if self.store.contains_calendar(&id) || self.store.contains_resource(&id) {
+            return Err(EngineError::AlreadyExists(id));
+        }
+
+        let event = Event::CalendarCreated { id, name: name.clone() };
+        self.wal_append(&event).await?;
+        let cal = CalendarState::new(id, name);
+        self.store.insert_calendar(id, Arc::new(RwLock::new(cal)));
+        self.notify.send(id, &event);
+        Ok(())
+    }
+
+    pub async fn delete_calendar(&self, id: Ulid) -> Result<(), EngineError> {
+        let cal = self
+            .store
+            .get_calendar(&id)
+            .ok_or(EngineError::NotFound(id))?;
+        let guard = cal.write().await;
+        if self.store.calendar_in_use(&id) {
+            return Err(EngineError::CalendarInUse(id));
+        }
+
+        let event = Event::CalendarDeleted { id };
+        self.wal_append(&event).await?;
+        for rule in &guard.intervals {
+            self.store.unmap_calendar_rule(&rule.id);
+        }
+        self.store.remove_calendar(&id);
+        self.notify.send(id, &event);
+        Ok(())
+    }
+
+    pub async fn add_calendar_rule(
+        &self,
+        id: Ulid,
+        calendar_id: Ulid,
+        span: Span,
+        blocking: bool,
+    ) -> Result<(), EngineError> {
+        validate_span(&span)?;
+        let cal = self
+            .store
+            .get_calendar(&calendar_id)
+            .ok_or(EngineError::NotFound(calendar_id))?;
+        let guard = cal.write().await;
+        // Rule ids are global: another calendar's rule or a resource's
+        // interval with this id would be shadowed in the id indexes.
+        match self.store.get_calendar_for_rule(&id) {
+            Some(owner) if owner == calendar_id => {
+                let identical = guard
+                    .intervals
+                    .iter()
+                    .any(|i| i.id == id && i.span == span && i.kind == IntervalKind::rule(blocking, None));
+                return if identical { Ok(()) } else { Err(EngineError::AlreadyExists(id)) };
+            }
+            Some(_) => return Err(EngineError::AlreadyExists(id)),
+            None if self.store.get_resource_for_entity(&id).is_some() => return Err(EngineError::AlreadyExists(id)),
+            None => {}
+        }
+        if guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
+            return Err(EngineError::LimitExceeded("too many rules on calendar"));
+        }
+
+        let event = Event::CalendarRuleAdded { id, calendar_id, span, blocking };
+        self.persist_and_apply_calendar(calendar_id, guard, &event).await
+    }
+
+    pub async fn update_calendar_rule(
+        &self,
+        id: Ulid,
+        span: Span,
+        blocking: bool,
+    ) -> Result<Ulid, EngineError> {
+        validate_span(&span)?;
+        let calendar_id = self
+            .store
+            .get_calendar_for_rule(&id)
+            .ok_or(EngineError::NotFound(id))?;
+        let cal = self
+            .store
+            .get_calendar(&calendar_id)
+            .ok_or(EngineError::NotFound(calendar_id))?;
+        let guard = cal.write().await;
+
+        let event = Event::CalendarRuleUpdated { id, calendar_id, span, blocking };
+        self.persist_and_apply_calendar(calendar_id, guard, &event).await?;
+        Ok(calendar_id)
+    }
+
+    pub async fn remove_calendar_rule(&self, id: Ulid) -> Result<Ulid, EngineError> {
+        let calendar_id = self
+            .store
+            .get_calendar_for_rule(&id)
+            .ok_or(EngineError::NotFound(id))?;
+        let cal = self
+            .store
+            .get_calendar(&calendar_id)
+            .ok_or(EngineError::NotFound(calendar_id))?;
+        let guard = cal.write().await;
+
+        let event = Event::CalendarRuleRemoved { id, calendar_id };
+        self.persist_and_apply_calendar(calendar_id, guard, &event).await?;
+        Ok(calendar_id)
+    }
+
+    pub async fn attach_calendar(&self, calendar_id: Ulid, resource_id: Ulid) -> Result<(), EngineError> {
+        let cal = self
+            .store
+            .get_calendar(&calendar_id)
+            .ok_or(EngineError::NotFound(calendar_id))?;
+        let rs = self
+            .get_resource(&resource_id)
+            .ok_or(EngineError::NotFound(resource_id))?;
+        let mut guard = rs.write().await;
+        // Resource before calendar, as availability reads them. Holding the
+        // calendar lock keeps `delete_calendar` from running until the
+        // attachment is recorded, and it sees the attachment once it does.
+        let _cal_guard = cal.read().await;
+        if !self.store.contains_calendar(&calendar_id) {
+            return Err(EngineError::NotFound(calendar_id));
+        }
+        if guard.calendar_ids.contains(&calendar_id) {
+            return Err(EngineError::AlreadyExists(calendar_id));
+        }
+        if guard.calendar_ids.len() >= MAX_CALENDARS_PER_RESOURCE {
+            return Err(EngineError::LimitExceeded("too many calendars on resource"));
+        }
+
+        let event = Event::CalendarAttached { calendar_id, resource_id };
+        self.persist_and_apply(resource_id, &mut guard, &event).await
+    }
+
+    pub async fn detach_calendar(&self, calendar_id: Ulid, resource_id: Ulid) -> Result<(), EngineError> {
+        let rs = self
+            .get_resource(&resource_id)
+            .ok_or(EngineError::NotFound(resource_id))?;
+        let mut guard = rs.write().await;
+        if !guard.calendar_ids.contains(&calendar_id) {
+            return Err(EngineError::NotFound(calendar_id));
+        }
+
+        let event = Event::CalendarDetached { calendar_id, resource_id };
+        self.persist_and_apply(resource_id, &mut guard, &event).await
+    }
+
+    pub async fn list_calendars(&self) -> Vec<CalendarInfo> {
+        let mut result = Vec::new();
+        for cid in self.store.calendar_ids() {
+            if let Some(cal) = self.store.get_calendar(&cid) {
+                let guard = cal.read().await;
+                result.push(CalendarInfo {
+                    id: guard.id,
+                    name: guard.name.clone(),
+                });
+            }
+        }
+        result
+    }
+
+    pub async fn get_calendar_rules(&self, calendar_id: Ulid) -> Result<Vec<CalendarRuleInfo>, EngineError> {
+        let cal = match self.store.get_calendar(&calendar_id) {
+            Some(cal) => cal,
+            None => return Ok(vec![]),
+        };
+        let guard = cal.read().await;
+        Ok(guard
+            .intervals
+            .iter()
+            .map(|i| CalendarRuleInfo {
+                id: i.id,
+                calendar_id,
+                start: i.span.start,
+                end: i.span.end,
+                blocking: matches!(i.kind, IntervalKind::Blocking),
+            })
+            .collect())
+    }
+
+    /// Resources a calendar is attached to.
+    pub fn get_calendar_resources(&self, calendar_id: &Ulid) -> Vec<Ulid> {
+        self.store.get_calendar_resources(calendar_id)
+    }
+
+    /// Calendars attached to a resource, in attachment order.
+    pub async fn get_resource_calendars(&self, resource_id: Ulid) -> Vec<Ulid> {
+        match self.get_resource(&resource_id) {
+            Some(rs) => rs.read().await.calendar_ids.clone(),
+            None => vec![],
+        }
+    }
+
+    /// Rules of the given calendars overlapping `query`, clamped to it.
+    pub(super) async fn collect_calendar_rules(&self, calendar_ids: &[Ulid], query: &Span) -> Vec<Interval> {
+        let mut rules = Vec::new();
+        for cid in calendar_ids {
+            let Some(cal) = self.store.get_calendar(cid) else { continue };
+            let guard = cal.read().await;
+            for interval in guard.overlapping(query) {
+                rules.push(Interval {
+                    id: interval.id,
+                    span: Span::new(
+                        interval.span.start.max(query.start),
+                        interval.span.end.min(query.end),
+                    ),
+                    kind: interval.kind.clone(),
+                });
+            }
+        }
+        rules
+    }
+
+    /// WAL-append + apply + notify for calendar rule events. The event is sent
+    /// to the calendar's own channel and to every attached resource (and their
+    /// ancestors), each channel at most once. The calendar lock is released
+    /// before the resources are read: `attach_calendar` takes them the other
+    /// way round.
+    async fn persist_and_apply_calendar(
+        &self,
+        calendar_id: Ulid,
+        mut cal: RwLockWriteGuard<'_, CalendarState>,
+        event: &Event,
+    ) -> Result<(), EngineError> {
+        self.wal_append(event).await?;
+        self.store.apply_calendar_event(&mut cal, event);
+        drop(cal);
+
+        let mut notified = HashSet::new();
+        notified.insert(calendar_id);
+        self.notify.send(calendar_id, event);
+        for rid in self.store.get_calendar_resources(&calendar_id) {
+            let mut current = Some(rid);
+            while let Some(id) = current {
+                if !notified.insert(id) {
+                    break;
+                }
+                self.notify.send(id, event);
+                current = match self.store.get_resource(&id) {
+                    Some(rs) => rs.read().await.parent_id,
+                    None => None,
+                };
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use tokio::sync::{OwnedRwLockReadGuard, OwnedRwLockWriteGuard};
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::{Engine, EngineError, SharedResourceState};
+
+/// Write guards taken by `Engine::lock_for_allocation`.
+pub(super) struct AllocationLocks {
+    /// Sorted by id.
+    pub guards: Vec<OwnedRwLockWriteGuard<ResourceState>>,
+    /// Exclusion groups of the resources the allocations occupy.
+    pub groups: Vec<Arc<ExclusionGroup>>,
+    /// Ancestors of the targets and their components that are not among
+    /// `guards`, so rules and policies they pass down hold still.
+    pub ancestors: Vec<OwnedRwLockReadGuard<ResourceState>>,
+}
+
+impl AllocationLocks {
+    /// A locked resource or ancestor.
+    pub fn get(&self, id: Ulid) -> Option<&ResourceState> {
+        match self.guards.binary_search_by_key(&id, |g| g.id) {
+            Ok(pos) => Some(&self.guards[pos]),
+            Err(_) => self.ancestors.iter().find(|g| g.id == id).map(|g| &**g),
+        }
+    }
+
+    /// Booking policy in force on a locked resource, as `effective_policy`
+    /// resolves it.
+    pub fn policy(&self, id: Ulid) -> Result<BookingPolicy, EngineError> {
+        let rs = self.get(id).ok_or(EngineError::NotFound(id))?;
+        let mut policy = rs.settings.policy;
+        let mut current = rs.parent_id;
+        while let Some(pid) = current {
+            let parent = self.get(pid).ok_or(EngineError::NotFound(pid))?;
+            policy = policy.inherit(&parent.settings.policy);
+            current = parent.parent_id;
+        }
+        Ok(policy)
+    }
+}
+
+impl Engine {
+    /// Components must be distinct existing plain resources other than `id`.
+    pub(super) async fn validate_components(&self, id: Ulid, components: &[Ulid]) -> Result<(), EngineError> {
+        if components.len() > MAX_COMPONENTS_PER_RESOURCE {
+            return Err(EngineError::LimitExceeded("too many components"));
+        }
+        let mut seen = HashSet::new();
+        for &cid in components {
+            if cid == id {
+                return Err(EngineError::InvalidArgument("a composite cannot be its own component"));
+            }
+            if !seen.insert(cid) {
+                return Err(EngineError::InvalidArgument("duplicate component"));
+            }
+            let rs = self.get_resource(&cid).ok_or(EngineError::NotFound(cid))?;
+            if rs.read().await.settings.is_composite() {
+                return Err(EngineError::InvalidArgument("a composite cannot be a component"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Each of `resource_ids` with its components, for `lock_for_allocation`.
+    pub(super) async fn allocation_targets(
+        &self,
+        resource_ids: &[Ulid],
+    ) -> Result<Vec<(Ulid, Vec<Ulid>)>, EngineError> {
+        let mut targets = Vec::with_capacity(resource_ids.len());
+        for &rid in resource_ids {
+            let rs = self.get_resource(&rid).ok_or(EngineError::NotFound(rid))?;
+            let components = rs.read().await.settings.components.clone();
+            targets.push((rid, components));
+        }
+        Ok(targets)
+    }
+
+    /// What allocations on `targets` occupy and must lock, as (sorted lock
+    /// set, groups): the targets, their components, every composite over
+    /// any of those, and every member of their exclusion groups along with
+    /// the composites over it.
+    fn allocation_lock_set(&self, targets: &[(Ulid, Vec<Ulid>)]) -> (Vec<Ulid>, Vec<Arc<ExclusionGroup>>) {
+        let mut ids = Vec::new();
+        let mut groups: Vec<Arc<ExclusionGroup>> = Vec::new();
+        for (rid, components) in targets {
+            ids.push(*rid);
+            let members = if components.is_empty() { std::slice::from_ref(rid) } else { components };
+            for member in members {
+                ids.push(*member);
+                ids.extend(self.store.get_composites(member));
+                for group in self.store.groups_of(member) {
+                    if groups.iter().all(|g| g.id != group.id) {
+                        for other in &group.members {
+                            ids.push(*other);
+                            ids.extend(self.store.get_composites(other));
+                        }
+                        groups.push(group);
+                    }
+                }
+            }
+        }
+        ids.sort();
+        ids.dedup();
+        (ids, groups)
+    }
+
+    /// Write-lock what allocations on `targets` must check, in ULID order
+    /// like `batch_confirm_bookings`, then read-lock the ancestors of the
+    /// targets and their components. Starts over if a composite or group
+    /// appeared or went away over one of them while waiting.
+    ///
+    /// Ancestors come after their descendants here but before them on the
+    /// read paths, so they are only tried: if one is taken, everything is
+    /// let go until it is free.
+    pub(super) async fn lock_for_allocation(
+        &self,
+        targets: &[(Ulid, Vec<Ulid>)],
+    ) -> Result<AllocationLocks, EngineError> {
+        'retry: loop {
+            let (ids, _) = self.allocation_lock_set(targets);
+            let mut guards = Vec::with_capacity(ids.len());
+            for id in &ids {
+                match self.get_resource(id) {
+                    Some(rs) => guards.push(rs.write_owned().await),
+                    None if targets.iter().any(|(rid, _)| rid == id) => return Err(EngineError::NotFound(*id)),
+                    None => continue 'retry,
+                }
+            }
+            let (now_ids, groups) = self.allocation_lock_set(targets);
+            if now_ids != ids {
+                continue;
+            }
+            let mut locks = AllocationLocks { guards, groups, ancestors: Vec::new() };
+            match self.lock_ancestors(&mut locks, targets)? {
+                None => return Ok(locks),
+                Some(taken) => {
+                    drop(locks);
+                    drop(taken.read().await);
+                }
+            }
+        }
+    }
+
+    /// Read-lock the ancestors of `targets` and their components missing
+    /// from `locks`, without waiting. Returns the first one that is taken.
+    fn lock_ancestors(
+        &self,
+        locks: &mut AllocationLocks,
+        targets: &[(Ulid, Vec<Ulid>)],
+    ) -> Result<Option<SharedResourceState>, EngineError> {
+        for (rid, components) in targets {
+            for subject in std::iter::once(rid).chain(components) {
+                let mut current = locks.get(*subject).and_then(|rs| rs.parent_id);
+                let mut depth = 0usize;
+                while let Some(pid) = current {
+                    depth += 1;
+                    if depth > MAX_HIERARCHY_DEPTH {
+                        return Err(EngineError::LimitExceeded("hierarchy too deep"));
+                    }
+                    if let Some(rs) = locks.get(pid) {
+                        current = rs.parent_id;
+                        continue;
+                    }
+                    let rs = self.get_resource(&pid).ok_or(EngineError::NotFound(pid))?;
+                    let Ok(guard) = rs.clone().try_read_owned() else { return Ok(Some(rs)) };
+                    current = guard.parent_id;
+                    locks.ancestors.push(guard);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Holds and bookings on composites over `resource_id` near `query`, as
+    /// they weigh on it.
+    pub(super) async fn shared_allocations(&self, resource_id: Ulid, query: &Span) -> Vec<Interval> {
+        let mut shared = Vec::new();
+        for cid in self.store.get_composites(&resource_id) {
+            if let Some(rs) = self.get_resource(&cid) {
+                shared.extend(rs.read().await.shared_allocations(query));
+            }
+        }
+        shared
+    }
+}
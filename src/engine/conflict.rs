@@ -1,6 +1,15 @@
+use std::ops::Deref;
+use std::sync::Arc;
+
+use ulid::Ulid;
+
 use crate::model::*;
 
-use super::availability::compute_saturated_spans;
+use super::availability::{
+    compute_saturated_spans, compute_saturated_spans_piecewise, intersect_intervals, merge_overlapping,
+    subtract_intervals,
+};
+use super::error::{ConflictDetail, ConflictingInterval};
 use super::EngineError;
 
 pub(crate) fn now_ms() -> Ms {
@@ -21,57 +30,500 @@ pub(crate) fn validate_span(span: &Span) -> Result<(), EngineError> {
     Ok(())
 }
 
-pub(crate) fn check_no_conflict(rs: &ResourceState, span: &Span, now: Ms) -> Result<(), EngineError> {
-    let buffer = rs.buffer_after.unwrap_or(0);
-    // Expand the search window to catch:
-    // - Existing allocations whose end + buffer > span.start (search backwards by buffer)
-    // - Our allocation's end + buffer reaching into existing allocations
-    let search_start = (span.start - buffer).max(0);
-    let search_end = span.end + buffer;
-    let search_span = Span::new(search_start, search_end);
+pub(crate) fn validate_buffer(buffer: Option<Ms>) -> Result<(), EngineError> {
+    use crate::limits::MAX_BUFFER_MS;
+    if let Some(b) = buffer
+        && !(0..=MAX_BUFFER_MS).contains(&b) {
+            return Err(EngineError::LimitExceeded("buffer out of range"));
+        }
+    Ok(())
+}
 
-    if rs.capacity <= 1 {
-        // Fast path: any overlapping active allocation (with buffer) is a conflict
-        for interval in rs.overlapping(&search_span) {
-            match &interval.kind {
-                IntervalKind::Hold { expires_at } if *expires_at <= now => continue,
-                IntervalKind::Hold { .. } | IntervalKind::Booking { .. } => {
-                    let effective_end = interval.span.end + buffer;
-                    let effective = Span::new(interval.span.start, effective_end);
-                    if effective.overlaps(span) {
-                        return Err(EngineError::Conflict(interval.id));
-                    }
-                }
-                _ => {}
+pub(crate) fn validate_alloc_options(options: &AllocOptions) -> Result<(), EngineError> {
+    use crate::limits::{MAX_METADATA_LEN, MAX_OWNER_LEN};
+    validate_buffer(options.buffer_before)?;
+    validate_buffer(options.buffer_after)?;
+    if let Some(ref owner) = options.owner
+        && owner.len() > MAX_OWNER_LEN {
+            return Err(EngineError::LimitExceeded("owner too long"));
+        }
+    if let Some(ref metadata) = options.metadata {
+        if metadata.len() > MAX_METADATA_LEN {
+            return Err(EngineError::LimitExceeded("metadata too long"));
+        }
+        if !serde_json::from_str::<serde_json::Value>(metadata).is_ok_and(|v| v.is_object()) {
+            return Err(EngineError::InvalidArgument("metadata must be a JSON object"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_quota(quota: &Option<OwnerQuota>) -> Result<(), EngineError> {
+    use crate::limits::{MAX_QUERY_WINDOW_MS, MAX_SPAN_DURATION_MS};
+    let Some(quota) = quota else { return Ok(()) };
+    if !(1..=MAX_QUERY_WINDOW_MS).contains(&quota.window_ms) {
+        return Err(EngineError::LimitExceeded("quota window out of range"));
+    }
+    if quota.max_count.is_none() && quota.max_duration_ms.is_none() {
+        return Err(EngineError::InvalidArgument("quota sets no limit"));
+    }
+    if let Some(d) = quota.max_duration_ms
+        && !(0..=MAX_SPAN_DURATION_MS).contains(&d) {
+            return Err(EngineError::LimitExceeded("quota duration out of range"));
+        }
+    Ok(())
+}
+
+/// Check the resource's owner quota for a new allocation of `owner` at
+/// `span`. `pending` are other allocations of the same owner being made in
+/// the same operation.
+pub(crate) fn check_quota(
+    rs: &ResourceState,
+    owner: Option<&str>,
+    span: &Span,
+    pending: &[Span],
+    now: Ms,
+) -> Result<(), EngineError> {
+    let (Some(quota), Some(owner)) = (rs.settings.quota, owner) else {
+        return Ok(());
+    };
+    let w = quota.window_ms;
+    let near = Span::new(span.start - w + 1, span.start + w);
+
+    // (start, duration) of every allocation counted against the quota.
+    let mut items: Vec<(Ms, Ms)> = rs
+        .overlapping(&near)
+        .filter(|i| i.kind.owner() == Some(owner))
+        .filter(|i| !matches!(i.kind, IntervalKind::Hold { expires_at, .. } if expires_at <= now))
+        .map(|i| i.span)
+        .chain(pending.iter().copied())
+        .chain(std::iter::once(*span))
+        .filter(|s| near.contains_instant(s.start))
+        .map(|s| (s.start, s.duration_ms()))
+        .collect();
+    items.sort_unstable();
+
+    // Windows containing `span.start` need only be tried at allocation starts.
+    for &(t, _) in items.iter().filter(|(t, _)| *t <= span.start) {
+        let in_window = items.iter().filter(|(s, _)| (t..t + w).contains(s));
+        let (count, total) = in_window.fold((0u64, 0), |(c, d), (_, dur)| (c + 1, d + dur));
+        if quota.max_count.is_some_and(|max| count > max as u64)
+            || quota.max_duration_ms.is_some_and(|max| total > max)
+        {
+            return Err(EngineError::QuotaExceeded(owner.to_string()));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_policy(policy: &BookingPolicy) -> Result<(), EngineError> {
+    use crate::limits::MAX_SPAN_DURATION_MS;
+    let fields = [policy.min_duration, policy.max_duration, policy.min_lead_time, policy.max_horizon];
+    if fields.iter().flatten().any(|v| !(0..=MAX_SPAN_DURATION_MS).contains(v)) {
+        return Err(EngineError::LimitExceeded("booking policy out of range"));
+    }
+    if let (Some(min), Some(max)) = (policy.min_duration, policy.max_duration)
+        && min > max {
+            return Err(EngineError::InvalidArgument("min_duration exceeds max_duration"));
+        }
+    Ok(())
+}
+
+pub(crate) fn validate_granularity(settings: &ResourceSettings) -> Result<(), EngineError> {
+    use crate::limits::MAX_SPAN_DURATION_MS;
+    match settings.granularity_ms {
+        Some(g) if !(1..=MAX_SPAN_DURATION_MS).contains(&g) => {
+            Err(EngineError::LimitExceeded("granularity out of range"))
+        }
+        Some(g) if !(0..g).contains(&settings.granularity_offset_ms) => {
+            Err(EngineError::InvalidArgument("granularity offset out of range"))
+        }
+        None if settings.granularity_offset_ms != 0 => {
+            Err(EngineError::InvalidArgument("granularity offset without granularity"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Check that an allocation starts and ends on the resource's grid.
+pub(crate) fn check_alignment(settings: &ResourceSettings, span: &Span) -> Result<(), EngineError> {
+    if !settings.is_aligned(span.start) || !settings.is_aligned(span.end) {
+        return Err(EngineError::PolicyViolation("not aligned to granularity"));
+    }
+    Ok(())
+}
+
+/// Shrink free spans to the resource's grid, dropping those that no longer
+/// hold a whole unit.
+pub(crate) fn snap_to_grid(settings: &ResourceSettings, free: Vec<Span>) -> Vec<Span> {
+    if settings.granularity_ms.is_none() {
+        return free;
+    }
+    free.into_iter()
+        .filter_map(|s| {
+            let (start, end) = (settings.align_up(s.start), settings.align_down(s.end));
+            (end > start).then(|| Span::new(start, end))
+        })
+        .collect()
+}
+
+/// Check an allocation's span against an effective booking policy.
+pub(crate) fn check_policy(policy: &BookingPolicy, span: &Span, now: Ms) -> Result<(), EngineError> {
+    if let Some(min) = policy.min_duration
+        && span.duration_ms() < min {
+            return Err(EngineError::PolicyViolation("shorter than min_duration"));
+        }
+    if let Some(max) = policy.max_duration
+        && span.duration_ms() > max {
+            return Err(EngineError::PolicyViolation("longer than max_duration"));
+        }
+    if let Some(lead) = policy.min_lead_time
+        && span.start < now + lead {
+            return Err(EngineError::PolicyViolation("starts within min_lead_time"));
+        }
+    if let Some(horizon) = policy.max_horizon
+        && span.end > now + horizon {
+            return Err(EngineError::PolicyViolation("ends beyond max_horizon"));
+        }
+    Ok(())
+}
+
+/// Clip free spans to the policy's booking window and drop those too short
+/// to hold a booking of `min_duration`.
+pub(crate) fn apply_policy(policy: &BookingPolicy, free: Vec<Span>, now: Ms) -> Vec<Span> {
+    let earliest = policy.min_lead_time.map_or(Ms::MIN, |lead| now + lead);
+    let latest = policy.max_horizon.map_or(Ms::MAX, |horizon| now + horizon);
+    let min = policy.min_duration.unwrap_or(0);
+    free.into_iter()
+        .filter_map(|s| {
+            let (start, end) = (s.start.max(earliest), s.end.min(latest));
+            (end > start && end - start >= min).then(|| Span::new(start, end))
+        })
+        .collect()
+}
+
+/// All checks a new hold or booking must pass on its resource.
+pub(crate) fn check_allocation(
+    rs: &ResourceState,
+    policy: &BookingPolicy,
+    span: &Span,
+    options: &AllocOptions,
+    now: Ms,
+) -> Result<(), EngineError> {
+    check_alignment(&rs.settings, span)?;
+    check_policy(policy, span, now)?;
+    check_no_conflict(rs, &[], span, &rs.footprint(span, options), now)?;
+    check_quota(rs, options.owner.as_deref(), span, &[], now)
+}
+
+/// Check a new allocation on `span`, with buffers reaching to `footprint`,
+/// against the active allocations of `rs` plus `pending` ones not yet
+/// applied. Buffers may overlap each other but never another allocation:
+/// existing footprints must leave `span` free, and the new buffers must not
+/// reach into existing spans, wherever that would exceed capacity.
+pub(crate) fn check_no_conflict(
+    rs: &ResourceState,
+    pending: &[Interval],
+    span: &Span,
+    footprint: &Span,
+    now: Ms,
+) -> Result<(), EngineError> {
+    check_no_conflict_with(rs, pending, span, now)?;
+    check_buffers_clear(rs, pending, span, footprint, now)
+}
+
+/// Like `check_no_conflict` without the new allocation's buffers, counting
+/// `shared` allocations (from composites over `rs`, or pending in the same
+/// batch) as if they were its own.
+fn check_no_conflict_with(rs: &ResourceState, shared: &[Interval], span: &Span, now: Ms) -> Result<(), EngineError> {
+    let search_span = search_window(span);
+
+    let capacity_rules = collect_capacity_rules(rs, span);
+    if !capacity_rules.is_empty() {
+        // Time-varying capacity: saturation depends on which rule is in force.
+        let allocs = collect_active_allocs_with_buffer(rs, shared, &search_span, now);
+        let saturated = compute_saturated_spans_piecewise(&allocs, rs.capacity, &capacity_rules);
+        for sat in &saturated {
+            if sat.overlaps(span) {
+                let effective = capacity_rules
+                    .iter()
+                    .filter(|(s, _)| s.overlaps(sat) && s.overlaps(span))
+                    .map(|(_, c)| *c)
+                    .min()
+                    .unwrap_or(rs.capacity);
+                let detail = conflict_detail(rs, shared, span, Some(&saturated), now);
+                return Err(EngineError::CapacityExceeded(effective, Box::new(detail)));
             }
         }
+    } else if rs.capacity <= 1 {
+        // Fast path: any overlapping active allocation (with buffer) is a conflict
+        let detail = conflict_detail(rs, shared, span, None, now);
+        if let Some(first) = detail.intervals.first() {
+            return Err(EngineError::Conflict(first.id, Box::new(detail)));
+        }
     } else {
         // Capacity > 1: count overlapping active allocations using sweep line
-        let allocs = collect_active_allocs_with_buffer(rs, &search_span, now, buffer);
+        let allocs = collect_active_allocs_with_buffer(rs, shared, &search_span, now);
         let saturated = compute_saturated_spans(&allocs, rs.capacity);
-        for sat in &saturated {
-            if sat.overlaps(span) {
-                return Err(EngineError::CapacityExceeded(rs.capacity));
-            }
+        if saturated.iter().any(|sat| sat.overlaps(span)) {
+            let detail = conflict_detail(rs, shared, span, Some(&saturated), now);
+            return Err(EngineError::CapacityExceeded(rs.capacity, Box::new(detail)));
         }
     }
     Ok(())
 }
 
-/// Collect active allocation spans extended by buffer_after.
-fn collect_active_allocs_with_buffer(
+/// The new allocation's buffers (its `footprint` outside `span`) against the
+/// spans of active allocations, with capacity counted as elsewhere.
+fn check_buffers_clear(
     rs: &ResourceState,
-    query: &Span,
+    shared: &[Interval],
+    span: &Span,
+    footprint: &Span,
+    now: Ms,
+) -> Result<(), EngineError> {
+    let buffers = subtract_intervals(&[*footprint], &[*span]);
+    if buffers.is_empty() {
+        return Ok(());
+    }
+    let mut others = Vec::new();
+    for interval in overlapping_with(rs, shared, footprint) {
+        let kind = match &interval.kind {
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => continue,
+            IntervalKind::Hold { .. } => "hold",
+            IntervalKind::Booking { .. } => "booking",
+            _ => continue,
+        };
+        others.push((interval.id, kind, interval.span));
+    }
+    let mut spans: Vec<Span> = others.iter().map(|(.., s)| *s).collect();
+    spans.sort_by_key(|s| s.start);
+    let capacity_rules = collect_capacity_rules(rs, footprint);
+    let saturated = if capacity_rules.is_empty() {
+        compute_saturated_spans(&spans, rs.capacity.max(1))
+    } else {
+        compute_saturated_spans_piecewise(&spans, rs.capacity, &capacity_rules)
+    };
+    let blocked = intersect_intervals(&saturated, &buffers);
+    if blocked.is_empty() {
+        return Ok(());
+    }
+
+    let detail = ConflictDetail {
+        intervals: others
+            .iter()
+            .filter(|(.., s)| blocked.iter().any(|b| b.overlaps(s)))
+            .map(|&(id, kind, span)| ConflictingInterval { id, kind, span, buffered: true })
+            .collect(),
+        saturated: blocked,
+        alternatives: Vec::new(),
+    };
+    if capacity_rules.is_empty() && rs.capacity <= 1 {
+        let first = detail.intervals[0].id;
+        return Err(EngineError::Conflict(first, Box::new(detail)));
+    }
+    let effective = capacity_rules
+        .iter()
+        .filter(|(s, _)| detail.saturated.iter().any(|b| b.overlaps(s)))
+        .map(|(_, c)| *c)
+        .min()
+        .unwrap_or(rs.capacity);
+    Err(EngineError::CapacityExceeded(effective, Box::new(detail)))
+}
+
+/// What a new allocation on `span` runs into: the active allocations whose
+/// footprints reach into it (into its `saturated` parts, for a resource with
+/// capacity), and the parts of `span` that are full. Without `saturated`,
+/// any overlapping footprint fills the resource.
+fn conflict_detail(
+    rs: &ResourceState,
+    shared: &[Interval],
+    span: &Span,
+    saturated: Option<&[Span]>,
+    now: Ms,
+) -> ConflictDetail {
+    let full: Option<Vec<Span>> = saturated.map(|sat| sat.iter().filter_map(|s| clip(s, span)).collect());
+    let mut detail = ConflictDetail::default();
+    let mut footprints = Vec::new();
+    for interval in overlapping_with(rs, shared, &search_window(span)) {
+        let (kind, options) = match &interval.kind {
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => continue,
+            IntervalKind::Hold { options, .. } => ("hold", options),
+            IntervalKind::Booking { options, .. } => ("booking", options),
+            _ => continue,
+        };
+        let footprint = rs.footprint(&interval.span, options);
+        let hits = match &full {
+            Some(full) => full.iter().any(|s| s.overlaps(&footprint)),
+            None => footprint.overlaps(span),
+        };
+        if hits {
+            detail.intervals.push(ConflictingInterval {
+                id: interval.id,
+                kind,
+                span: interval.span,
+                buffered: !interval.span.overlaps(span),
+            });
+            footprints.push(footprint);
+        }
+    }
+    detail.saturated = full.unwrap_or_else(|| {
+        footprints.sort_by_key(|s| s.start);
+        merge_overlapping(&footprints).iter().filter_map(|s| clip(s, span)).collect()
+    });
+    detail
+}
+
+/// A conflict between two allocations of the same batch: `id` on `span`,
+/// with buffers reaching to `footprint`, is in the way of `requested`.
+pub(crate) fn pending_conflict(id: Ulid, span: Span, footprint: Span, requested: &Span) -> EngineError {
+    let detail = ConflictDetail {
+        intervals: vec![ConflictingInterval { id, kind: "booking", span, buffered: !span.overlaps(requested) }],
+        saturated: clip(&footprint, requested).into_iter().collect(),
+        alternatives: Vec::new(),
+    };
+    EngineError::Conflict(id, Box::new(detail))
+}
+
+/// The part of `span` inside `within`, if any.
+fn clip(span: &Span, within: &Span) -> Option<Span> {
+    let (start, end) = (span.start.max(within.start), span.end.min(within.end));
+    (start < end).then(|| Span::new(start, end))
+}
+
+/// Capacity rules overlapping `query`, as `(span, capacity)`.
+pub(crate) fn collect_capacity_rules(rs: &ResourceState, query: &Span) -> Vec<(Span, u32)> {
+    rs.overlapping(query)
+        .filter_map(|interval| match &interval.kind {
+            IntervalKind::Capacity { capacity } => Some((interval.span, *capacity)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Check `span` on what an allocation on `locked[target]` occupies: each
+/// component of a composite, or else the resource itself. Each is checked
+/// against its own allocations plus those of every composite over it, and
+/// then against each of `groups` it belongs to, which must not already have
+/// `limit` members allocated anywhere in `span`. `locked` must hold all of
+/// those resources.
+pub(crate) fn check_components<G: Deref<Target = ResourceState>>(
+    locked: &[G],
+    groups: &[Arc<ExclusionGroup>],
+    target: usize,
+    span: &Span,
+    footprint: &Span,
     now: Ms,
-    buffer: Ms,
-) -> Vec<Span> {
+) -> Result<(), EngineError> {
+    let rs = &*locked[target];
+    let search_span = search_window(span);
+    let members: Vec<&ResourceState> = if rs.settings.is_composite() {
+        locked.iter().map(|g| &**g).filter(|g| rs.settings.components.contains(&g.id)).collect()
+    } else {
+        vec![rs]
+    };
+    for member in members {
+        let shared = shared_from(locked, member.id, &search_span);
+        // A plain resource's own allocations were checked already.
+        if !(shared.is_empty() && member.id == rs.id) {
+            check_no_conflict(member, &shared, span, footprint, now)?;
+        }
+        for group in groups.iter().filter(|g| g.members.contains(&member.id)) {
+            check_group_limit(group, group_allocations(locked, group, span, now), span)?;
+        }
+    }
+    Ok(())
+}
+
+/// An allocation counted against an exclusion group, as reported in a
+/// refusal, with its footprint.
+pub(crate) type GroupAllocation = (ConflictingInterval, Span);
+
+/// Active allocations near `span` across the members of `group`. `locked`
+/// must hold the members and composites over them.
+pub(crate) fn group_allocations<G: Deref<Target = ResourceState>>(
+    locked: &[G],
+    group: &ExclusionGroup,
+    span: &Span,
+    now: Ms,
+) -> Vec<GroupAllocation> {
+    let search_span = search_window(span);
+    let mut allocs = Vec::new();
+    for member in locked.iter().filter(|g| group.members.contains(&g.id)) {
+        let shared = shared_from(locked, member.id, &search_span);
+        for interval in overlapping_with(member, &shared, &search_span) {
+            let (kind, options) = match &interval.kind {
+                IntervalKind::Hold { expires_at, .. } if *expires_at <= now => continue,
+                IntervalKind::Hold { options, .. } => ("hold", options),
+                IntervalKind::Booking { options, .. } => ("booking", options),
+                _ => continue,
+            };
+            let conflicting =
+                ConflictingInterval { id: interval.id, kind, span: interval.span, buffered: !interval.span.overlaps(span) };
+            allocs.push((conflicting, member.footprint(&interval.span, options)));
+        }
+    }
+    allocs
+}
+
+/// Refuse `span` where `allocs` already hold `group` at its limit, naming the
+/// allocations involved.
+pub(crate) fn check_group_limit(
+    group: &ExclusionGroup,
+    allocs: Vec<GroupAllocation>,
+    span: &Span,
+) -> Result<(), EngineError> {
+    let mut footprints: Vec<Span> = allocs.iter().map(|(_, footprint)| *footprint).collect();
+    footprints.sort_by_key(|s| s.start);
+    let saturated: Vec<Span> =
+        compute_saturated_spans(&footprints, group.limit).iter().filter_map(|s| clip(s, span)).collect();
+    if saturated.is_empty() {
+        return Ok(());
+    }
+    let detail = ConflictDetail {
+        intervals: allocs
+            .into_iter()
+            .filter(|(_, footprint)| saturated.iter().any(|s| s.overlaps(footprint)))
+            .map(|(interval, _)| interval)
+            .collect(),
+        saturated,
+        alternatives: Vec::new(),
+    };
+    Err(EngineError::GroupLimitReached(group.id, Box::new(detail)))
+}
+
+/// Allocations of the composites in `locked` over `member`.
+fn shared_from<G: Deref<Target = ResourceState>>(locked: &[G], member: Ulid, query: &Span) -> Vec<Interval> {
+    locked
+        .iter()
+        .filter(|g| g.settings.components.contains(&member))
+        .flat_map(|g| g.shared_allocations(query))
+        .collect()
+}
+
+/// Window around `span` wide enough to visit every allocation whose buffers
+/// reach into it. Buffers are capped at MAX_BUFFER_MS.
+fn search_window(span: &Span) -> Span {
+    use crate::limits::MAX_BUFFER_MS;
+    Span::new((span.start - MAX_BUFFER_MS).max(0), span.end + MAX_BUFFER_MS)
+}
+
+fn overlapping_with<'a>(
+    rs: &'a ResourceState,
+    shared: &'a [Interval],
+    query: &'a Span,
+) -> impl Iterator<Item = &'a Interval> {
+    rs.overlapping(query).chain(shared.iter().filter(move |i| i.span.overlaps(query)))
+}
+
+/// Collect active allocation footprints (span extended by its buffers).
+pub(crate) fn collect_active_allocs_with_buffer(rs: &ResourceState, shared: &[Interval], query: &Span, now: Ms) -> Vec<Span> {
     let mut allocs = Vec::new();
-    for interval in rs.overlapping(query) {
+    for interval in overlapping_with(rs, shared, query) {
         match &interval.kind {
-            IntervalKind::Hold { expires_at } if *expires_at <= now => continue,
-            IntervalKind::Hold { .. } | IntervalKind::Booking { .. } => {
-                let effective_end = interval.span.end + buffer;
-                allocs.push(Span::new(interval.span.start, effective_end));
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => continue,
+            IntervalKind::Hold { options, .. } | IntervalKind::Booking { options, .. } => {
+                allocs.push(rs.footprint(&interval.span, options));
             }
             _ => {}
         }
@@ -6,16 +6,36 @@ use crate::model::Span;
 pub enum EngineError {
     NotFound(Ulid),
     AlreadyExists(Ulid),
-    Conflict(Ulid),
+    /// The first allocation in the way, and everything the new one ran into.
+    Conflict(Ulid, Box<ConflictDetail>),
     NotCoveredByParent {
         rule_span: Span,
         uncovered: Vec<Span>,
     },
+    /// Parts of an allocation's span outside its resource's open time.
+    NotOpen {
+        resource_id: Ulid,
+        closed: Vec<Span>,
+    },
     CycleDetected(Ulid),
     HasChildren(Ulid),
-    CapacityExceeded(u32),
+    CalendarInUse(Ulid),
+    ComponentInUse(Ulid),
+    CapacityExceeded(u32, Box<ConflictDetail>),
+    /// The group, and the member allocations holding it at its limit.
+    GroupLimitReached(Ulid, Box<ConflictDetail>),
+    RequirementNotMet(usize),
+    NoFeasibleItinerary,
+    PolicyViolation(&'static str),
+    /// An argument that is malformed or contradicts itself, whatever the
+    /// limits.
+    InvalidArgument(&'static str),
+    QuotaExceeded(String),
     LimitExceeded(&'static str),
     WalError(String),
+    /// A mutation under `Engine::dry_run` passed its checks and stopped
+    /// before writing.
+    DryRun,
 }
 
 impl std::fmt::Display for EngineError {
@@ -23,7 +43,7 @@ impl std::fmt::Display for EngineError {
         match self {
             EngineError::NotFound(id) => write!(f, "not found: {id}"),
             EngineError::AlreadyExists(id) => write!(f, "already exists: {id}"),
-            EngineError::Conflict(id) => write!(f, "conflict with allocation: {id}"),
+            EngineError::Conflict(id, _) => write!(f, "conflict with allocation: {id}"),
             EngineError::NotCoveredByParent {
                 rule_span,
                 uncovered,
@@ -34,17 +54,59 @@ impl std::fmt::Display for EngineError {
                     rule_span.start, rule_span.end, uncovered
                 )
             }
+            EngineError::NotOpen { resource_id, closed } => {
+                write!(f, "resource {resource_id} is closed over {closed:?}")
+            }
             EngineError::CycleDetected(id) => write!(f, "cycle detected at resource: {id}"),
             EngineError::HasChildren(id) => {
                 write!(f, "cannot delete resource {id}: has children")
             }
-            EngineError::CapacityExceeded(cap) => {
+            EngineError::CalendarInUse(id) => {
+                write!(f, "cannot delete calendar {id}: attached to resources")
+            }
+            EngineError::ComponentInUse(id) => {
+                write!(f, "cannot delete resource {id}: component of a composite")
+            }
+            EngineError::CapacityExceeded(cap, _) => {
                 write!(f, "capacity {cap} exceeded: all slots occupied")
             }
+            EngineError::GroupLimitReached(id, _) => {
+                write!(f, "exclusion group {id} is at its limit")
+            }
+            EngineError::RequirementNotMet(index) => {
+                write!(f, "not enough free resources for requirement {index}")
+            }
+            EngineError::NoFeasibleItinerary => write!(f, "no feasible itinerary in the window"),
+            EngineError::PolicyViolation(msg) => write!(f, "booking policy violated: {msg}"),
+            EngineError::InvalidArgument(msg) => write!(f, "invalid argument: {msg}"),
+            EngineError::QuotaExceeded(owner) => write!(f, "quota exceeded for owner: {owner}"),
             EngineError::LimitExceeded(msg) => write!(f, "limit exceeded: {msg}"),
             EngineError::WalError(e) => write!(f, "WAL error: {e}"),
+            EngineError::DryRun => write!(f, "dry run: not committed"),
         }
     }
 }
 
 impl std::error::Error for EngineError {}
+
+/// What a refused hold or booking ran into.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConflictDetail {
+    pub intervals: Vec<ConflictingInterval>,
+    /// Parts of the requested span where the resource is fully occupied.
+    pub saturated: Vec<Span>,
+    /// Feasible `(resource id, span)` placements near the requested one,
+    /// when asked for.
+    pub alternatives: Vec<(Ulid, Span)>,
+}
+
+/// An active allocation in the way of a new one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictingInterval {
+    pub id: Ulid,
+    /// "booking" or "hold".
+    pub kind: &'static str,
+    pub span: Span,
+    /// Only its buffers reach into the requested span.
+    pub buffered: bool,
+}
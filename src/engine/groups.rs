@@ -0,0 +1,146 @@
+use std::collections::HashSet;
+
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::availability::{compute_saturated_spans, merge_overlapping};
+use super::conflict::{check_group_limit, collect_active_allocs_with_buffer, group_allocations, now_ms};
+use super::{Engine, EngineError};
+
+impl Engine {
+    /// Create an exclusion group. Re-submitting an existing id with the same
+    /// payload is a no-op; a different payload fails with `AlreadyExists`.
+    ///
+    /// Members are plain resources; a composite counts through its
+    /// components. The members' current allocations must not already go
+    /// over `limit` anywhere.
+    pub async fn create_group(
+        &self,
+        id: Ulid,
+        name: Option<String>,
+        limit: u32,
+        members: Vec<Ulid>,
+    ) -> Result<(), EngineError> {
+        if let Some(existing) = self.store.get_group(&id) {
+            if existing.name == name && existing.limit == limit && existing.members == members {
+                return Ok(());
+            }
+            return Err(EngineError::AlreadyExists(id));
+        }
+        if self.store.group_count() >= MAX_GROUPS_PER_TENANT {
+            return Err(EngineError::LimitExceeded("too many exclusion groups"));
+        }
+        if let Some(ref n) = name
+            && n.len() > MAX_NAME_LEN {
+                return Err(EngineError::LimitExceeded("group name too long"));
+            }
+        if limit == 0 {
+            return Err(EngineError::InvalidArgument("group limit must be at least 1"));
+        }
+        if members.len() > MAX_MEMBERS_PER_GROUP {
+            return Err(EngineError::LimitExceeded("too many group members"));
+        }
+        if self.store.contains_resource(&id) || self.store.contains_calendar(&id) {
+            return Err(EngineError::AlreadyExists(id));
+        }
+        let mut seen = HashSet::new();
+        for &member in &members {
+            if !seen.insert(member) {
+                return Err(EngineError::InvalidArgument("duplicate group member"));
+            }
+            let rs = self.get_resource(&member).ok_or(EngineError::NotFound(member))?;
+            if rs.read().await.settings.is_composite() {
+                return Err(EngineError::InvalidArgument("a composite cannot be a group member"));
+            }
+        }
+
+        let group = ExclusionGroup { id, name, limit, members };
+        let targets: Vec<(Ulid, Vec<Ulid>)> = group.members.iter().map(|&m| (m, Vec::new())).collect();
+        let locks = self.lock_for_allocation(&targets).await?;
+        if self.store.get_group(&id).is_some() {
+            return Err(EngineError::AlreadyExists(id));
+        }
+        let everywhere = Span::new(MIN_VALID_TIMESTAMP_MS, MAX_VALID_TIMESTAMP_MS);
+        let over = ExclusionGroup { limit: limit + 1, ..group.clone() };
+        check_group_limit(&over, group_allocations(&locks.guards, &group, &everywhere, now_ms()), &everywhere)?;
+
+        let event = Event::GroupCreated {
+            id,
+            name: group.name.clone(),
+            limit,
+            members: group.members.clone(),
+        };
+        self.wal_append(&event).await?;
+        self.store.insert_group(group.clone());
+        drop(locks);
+        self.notify_group(&group, &event).await;
+        Ok(())
+    }
+
+    pub async fn delete_group(&self, id: Ulid) -> Result<(), EngineError> {
+        let group = self.store.get_group(&id).ok_or(EngineError::NotFound(id))?;
+        let event = Event::GroupDeleted { id };
+        self.wal_append(&event).await?;
+        self.store.remove_group(&id);
+        self.notify_group(&group, &event).await;
+        Ok(())
+    }
+
+    /// Send a group event to the group's own channel and to each member,
+    /// the composites over it, and their ancestors, each channel at most
+    /// once: the group bounds all of their availability.
+    async fn notify_group(&self, group: &ExclusionGroup, event: &Event) {
+        let mut notified = HashSet::new();
+        notified.insert(group.id);
+        self.notify.send(group.id, event);
+        for member in &group.members {
+            for rid in std::iter::once(*member).chain(self.store.get_composites(member)) {
+                let mut current = Some(rid);
+                while let Some(id) = current {
+                    if !notified.insert(id) {
+                        break;
+                    }
+                    self.notify.send(id, event);
+                    current = match self.get_resource(&id) {
+                        Some(rs) => rs.read().await.parent_id,
+                        None => None,
+                    };
+                }
+            }
+        }
+    }
+
+    pub fn list_groups(&self) -> Vec<ExclusionGroup> {
+        let mut groups: Vec<ExclusionGroup> = self.store.groups().iter().map(|g| (**g).clone()).collect();
+        groups.sort_by_key(|g| g.id);
+        groups
+    }
+
+    /// Spans near `query` where an exclusion group of `resource_id` already
+    /// has `limit` members allocated, sorted and merged. Members are read one
+    /// at a time, so call this before locking the resource itself.
+    pub(super) async fn saturated_group_spans(&self, resource_id: Ulid, query: &Span, now: Ms) -> Vec<Span> {
+        let mut saturated = Vec::new();
+        for group in self.store.groups_of(&resource_id) {
+            let allocs = self.group_footprints(&group, query, now).await;
+            saturated.extend(compute_saturated_spans(&allocs, group.limit));
+        }
+        saturated.sort_by_key(|s| s.start);
+        merge_overlapping(&saturated)
+    }
+
+    /// Footprints of the active allocations near `query` across the members
+    /// of `group`, sorted by start. Members are read one at a time.
+    pub(super) async fn group_footprints(&self, group: &ExclusionGroup, query: &Span, now: Ms) -> Vec<Span> {
+        let mut allocs = Vec::new();
+        for member in &group.members {
+            let Some(rs) = self.get_resource(member) else { continue };
+            let shared = self.shared_allocations(*member, query).await;
+            allocs.extend(collect_active_allocs_with_buffer(&*rs.read().await, &shared, query, now));
+        }
+        allocs.sort_by_key(|s| s.start);
+        allocs
+    }
+}
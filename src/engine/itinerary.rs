@@ -0,0 +1,188 @@
+use std::collections::HashSet;
+
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::availability::{intersect_intervals, merge_overlapping};
+use super::conflict::validate_span;
+use super::{Engine, EngineError};
+
+/// How many grid starts per resource and step to track one by one.
+const MAX_GRID_STARTS: i64 = 10_000;
+
+impl Engine {
+    /// Book the earliest-starting chain of `steps` that fits in `window`, as
+    /// one atomic batch. Returns `(booking id, resource id, span)` per step.
+    ///
+    /// Start times are tracked as sets of spans: a forward pass keeps the
+    /// starts of each step reachable from some start of the one before, and
+    /// a backward pass keeps those from which the rest of the chain can
+    /// still be completed. Any start left in the first set then leads to a
+    /// complete chain, taken step by step at the earliest time. Each leg
+    /// starts on the grid of the resource it is booked on.
+    ///
+    /// Past `MAX_GRID_STARTS` grid starts on one resource, its starts are
+    /// tracked as ranges, which can take in starts off its grid, so a chain
+    /// may be missed. If none is found then, this fails with `LimitExceeded`
+    /// rather than `NoFeasibleItinerary`: a narrower window may find one.
+    pub async fn book_itinerary(
+        &self,
+        steps: &[ItineraryStep],
+        window: Span,
+        label: Option<String>,
+    ) -> Result<Vec<(Ulid, Ulid, Span)>, EngineError> {
+        validate_span(&window)?;
+        self.validate_itinerary(steps)?;
+
+        // Per step, each resource's free spans, and the starts they allow.
+        let mut free = Vec::with_capacity(steps.len());
+        let mut reachable = Vec::with_capacity(steps.len());
+        let mut coarse = false;
+        for step in steps {
+            let mut per_resource = Vec::with_capacity(step.resources.len());
+            let mut starts = Vec::new();
+            for &rid in &step.resources {
+                let spans = self.compute_availability(rid, window.start, window.end, None).await?;
+                let Some(rs) = self.get_resource(&rid) else { continue };
+                let settings = rs.read().await.settings.clone();
+                let (set, ranges) = start_set(&spans, step.duration, &settings);
+                starts.extend(set);
+                coarse |= ranges;
+                per_resource.push((rid, spans, settings));
+            }
+            starts.sort_by_key(|s| s.start);
+            free.push(per_resource);
+            reachable.push(merge_overlapping(&starts));
+        }
+
+        for k in 1..steps.len() {
+            let prev = &steps[k - 1];
+            let (lo, hi) = (prev.duration + steps[k].min_gap, prev.duration + steps[k].max_gap);
+            let after_prev = shift_expand(&reachable[k - 1], lo, hi);
+            reachable[k] = intersect_intervals(&reachable[k], &after_prev);
+        }
+        for k in (0..steps.len() - 1).rev() {
+            let next = &steps[k + 1];
+            let (lo, hi) = (steps[k].duration + next.min_gap, steps[k].duration + next.max_gap);
+            let before_next = shift_expand(&reachable[k + 1], -hi, -lo);
+            reachable[k] = intersect_intervals(&reachable[k], &before_next);
+        }
+
+        let mut bookings = Vec::with_capacity(steps.len());
+        let mut earliest = Ms::MIN;
+        let mut latest = Ms::MAX;
+        for (k, step) in steps.iter().enumerate() {
+            // The earliest grid start of any free resource, first listed on ties.
+            let found = free[k]
+                .iter()
+                .filter(|(_, _, settings)| fits_grid(settings, step.duration))
+                .filter_map(|(rid, spans, settings)| {
+                    let candidates = reachable[k].iter().flat_map(|r| spans.iter().map(move |f| (r, f)));
+                    candidates
+                        .filter_map(|(r, f)| {
+                            let start = settings.align_up(r.start.max(f.start).max(earliest));
+                            let last = (r.end - 1).min(f.end - step.duration).min(latest);
+                            (start <= last).then_some(start)
+                        })
+                        .min()
+                        .map(|start| (start, *rid))
+                })
+                .min_by_key(|(start, _)| *start);
+            let Some((start, rid)) = found else {
+                return Err(if coarse {
+                    EngineError::LimitExceeded("too many grid starts to search the window")
+                } else {
+                    EngineError::NoFeasibleItinerary
+                });
+            };
+            let span = Span::new(start, start + step.duration);
+            bookings.push((Ulid::new(), rid, span, label.clone()));
+            if let Some(next) = steps.get(k + 1) {
+                earliest = span.end + next.min_gap;
+                latest = span.end + next.max_gap;
+            }
+        }
+
+        let chosen = bookings.iter().map(|(id, rid, span, _)| (*id, *rid, *span)).collect();
+        self.batch_confirm_bookings(bookings).await?;
+        Ok(chosen)
+    }
+
+    fn validate_itinerary(&self, steps: &[ItineraryStep]) -> Result<(), EngineError> {
+        if steps.is_empty() || steps.len() > MAX_ITINERARY_STEPS {
+            return Err(EngineError::LimitExceeded("number of itinerary steps out of range"));
+        }
+        let total: usize = steps.iter().map(|s| s.resources.len()).sum();
+        if total > MAX_IN_CLAUSE_IDS {
+            return Err(EngineError::LimitExceeded("too many resource IDs"));
+        }
+        for step in steps {
+            if step.resources.is_empty() {
+                return Err(EngineError::InvalidArgument("itinerary step has no resources"));
+            }
+            if step.duration <= 0 {
+                return Err(EngineError::InvalidArgument("itinerary step duration must be positive"));
+            }
+            if step.duration > MAX_SPAN_DURATION_MS {
+                return Err(EngineError::LimitExceeded("itinerary step duration out of range"));
+            }
+            if step.min_gap < 0 || step.max_gap < step.min_gap {
+                return Err(EngineError::InvalidArgument("itinerary step gaps must satisfy 0 <= min_gap <= max_gap"));
+            }
+            if step.max_gap > MAX_SPAN_DURATION_MS {
+                return Err(EngineError::LimitExceeded("itinerary step gap out of range"));
+            }
+            let mut seen = HashSet::new();
+            for &rid in &step.resources {
+                if !seen.insert(rid) {
+                    return Err(EngineError::InvalidArgument("resource listed twice in an itinerary step"));
+                }
+                if !self.store.contains_resource(&rid) {
+                    return Err(EngineError::NotFound(rid));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Start times at which `duration` fits inside one of `free`, as half-open
+/// spans over whole milliseconds, and whether they are ranges of grid
+/// starts. On a grid, each start is its own span, up to `MAX_GRID_STARTS`
+/// per resource; past that, spans run from the first to the last grid start
+/// and the final pick snaps within them.
+fn start_set(free: &[Span], duration: Ms, settings: &ResourceSettings) -> (Vec<Span>, bool) {
+    if !fits_grid(settings, duration) {
+        return (Vec::new(), false);
+    }
+    let ranges: Vec<Span> = free
+        .iter()
+        .filter_map(|s| {
+            let (first, last) = (settings.align_up(s.start), settings.align_down(s.end - duration));
+            (first <= last).then(|| Span::new(first, last + 1))
+        })
+        .collect();
+    let Some(g) = settings.granularity_ms else { return (ranges, false) };
+    let count: i64 = ranges.iter().map(|r| (r.end - 1 - r.start) / g + 1).sum();
+    if count > MAX_GRID_STARTS {
+        return (ranges, true);
+    }
+    let starts = ranges
+        .iter()
+        .flat_map(|r| (r.start..r.end).step_by(g as usize).map(|t| Span::new(t, t + 1)))
+        .collect();
+    (starts, false)
+}
+
+/// Whether a leg of `duration` starting on the grid also ends on it.
+fn fits_grid(settings: &ResourceSettings, duration: Ms) -> bool {
+    settings.granularity_ms.is_none_or(|g| duration % g == 0)
+}
+
+/// Every `t + d` for `t` in `set` and `d` in `lo..=hi`, merged.
+fn shift_expand(set: &[Span], lo: Ms, hi: Ms) -> Vec<Span> {
+    let shifted: Vec<Span> = set.iter().map(|s| Span::new(s.start + lo, s.end + hi)).collect();
+    merge_overlapping(&shifted)
+}
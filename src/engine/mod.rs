@@ -1,21 +1,33 @@
+mod alternatives;
 mod availability;
+mod blocks;
+mod busy;
+mod calendars;
+mod composites;
 mod conflict;
 mod error;
+mod groups;
+mod itinerary;
 mod mutations;
 mod queries;
+mod requirements;
 mod store;
 #[cfg(test)]
 mod tests;
+mod utilization;
 
 pub use availability::{availability, compute_saturated_spans, merge_overlapping, subtract_intervals};
-pub use error::EngineError;
+pub use error::{ConflictDetail, ConflictingInterval, EngineError};
 pub use store::InMemoryStore;
 
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::future::Future;
 use std::io;
 use std::path::PathBuf;
 use std::sync::Arc;
 
-use tokio::sync::{mpsc, oneshot, RwLock};
+use tokio::sync::{mpsc, oneshot, OwnedRwLockWriteGuard, RwLock};
 use ulid::Ulid;
 
 use crate::model::*;
@@ -23,12 +35,13 @@ use crate::notify::NotifyHub;
 use crate::wal::Wal;
 
 pub type SharedResourceState = Arc<RwLock<ResourceState>>;
+pub type SharedCalendarState = Arc<RwLock<CalendarState>>;
 
 // ── Group-commit WAL channel ─────────────────────────────
 
 pub(super) enum WalCommand {
     Append {
-        event: Event,
+        event: Box<Event>,
         response: oneshot::Sender<io::Result<()>>,
     },
     Compact {
@@ -89,7 +102,7 @@ async fn wal_writer_loop(mut wal: Wal, mut rx: mpsc::Receiver<WalCommand>) {
     }
 }
 
-fn flush_batch(wal: &mut Wal, batch: &mut [(Event, oneshot::Sender<io::Result<()>>)]) -> io::Result<()> {
+fn flush_batch(wal: &mut Wal, batch: &mut [(Box<Event>, oneshot::Sender<io::Result<()>>)]) -> io::Result<()> {
     let mut append_err: Option<io::Error> = None;
     for (event, _) in batch.iter() {
         if let Err(e) = wal.append_buffered(event) {
@@ -109,7 +122,7 @@ fn flush_batch(wal: &mut Wal, batch: &mut [(Event, oneshot::Sender<io::Result<()
     Ok(())
 }
 
-fn respond_batch(batch: &mut Vec<(Event, oneshot::Sender<io::Result<()>>)>, result: &io::Result<()>) {
+fn respond_batch(batch: &mut Vec<(Box<Event>, oneshot::Sender<io::Result<()>>)>, result: &io::Result<()>) {
     for (_, tx) in batch.drain(..) {
         let r = match result {
             Ok(()) => Ok(()),
@@ -133,6 +146,12 @@ fn handle_non_append(wal: &mut Wal, cmd: WalCommand) {
     }
 }
 
+tokio::task_local! {
+    /// Present while a mutation runs under `Engine::dry_run`; set once it
+    /// reaches its WAL write.
+    static DRY_RUN: Cell<bool>;
+}
+
 pub struct Engine {
     pub(super) store: InMemoryStore,
     pub(super) wal_tx: mpsc::Sender<WalCommand>,
@@ -157,43 +176,112 @@ impl Engine {
         // always succeed instantly (no contention). Never use blocking_read/blocking_write
         // here because this may run inside an async context (e.g. lazy tenant creation).
         for event in &events {
-            match event {
-                Event::ResourceCreated { id, parent_id, name, capacity, buffer_after } => {
-                    let rs = ResourceState::new(*id, *parent_id, name.clone(), *capacity, *buffer_after);
-                    engine.store.insert_resource(*id, Arc::new(RwLock::new(rs)));
-                    if let Some(pid) = parent_id {
-                        engine.store.add_child(*pid, *id);
+            engine.replay_event(event);
+        }
+
+        Ok(engine)
+    }
+
+    /// Apply one replayed event. Nothing else holds the state yet, so
+    /// try_read/try_write always succeed.
+    fn replay_event(&self, event: &Event) {
+        match event {
+            Event::Batch { events } => {
+                for event in events {
+                    self.replay_event(event);
+                }
+            }
+            Event::ResourceCreated { id, parent_id, name, capacity, buffer_after, settings } => {
+                let mut rs = ResourceState::new(*id, *parent_id, name.clone(), *capacity, *buffer_after);
+                rs.settings = settings.clone();
+                self.store.insert_resource(*id, Arc::new(RwLock::new(rs)));
+                if let Some(pid) = parent_id {
+                    self.store.add_child(*pid, *id);
+                    if let Some(position) = settings.position {
+                        let _ = self.store.claim_position(*pid, position, *id);
                     }
                 }
-                Event::ResourceDeleted { id } => {
-                    if let Some(rs) = engine.store.get_resource(id) {
-                        let guard = rs.try_read().expect("replay: uncontended read");
-                        if let Some(pid) = guard.parent_id {
-                            engine.store.remove_child(&pid, id);
-                        }
+                self.store.link_composite(*id, &settings.components);
+            }
+            Event::ResourceDeleted { id } => {
+                if let Some(rs) = self.store.get_resource(id) {
+                    self.remove_resource(&rs.try_read().expect("replay: uncontended read"));
+                }
+            }
+            Event::GroupCreated { id, name, limit, members } => {
+                self.store.insert_group(ExclusionGroup {
+                    id: *id,
+                    name: name.clone(),
+                    limit: *limit,
+                    members: members.clone(),
+                });
+            }
+            Event::GroupDeleted { id } => {
+                self.store.remove_group(id);
+            }
+            Event::CalendarCreated { id, name } => {
+                let cal = CalendarState::new(*id, name.clone());
+                self.store.insert_calendar(*id, Arc::new(RwLock::new(cal)));
+            }
+            Event::CalendarDeleted { id } => {
+                if let Some(cal) = self.store.get_calendar(id) {
+                    let guard = cal.try_read().expect("replay: uncontended read");
+                    for rule in &guard.intervals {
+                        self.store.unmap_calendar_rule(&rule.id);
                     }
-                    engine.store.remove_resource(id);
                 }
-                other => {
-                    let resource_id = event_resource_id(other);
-                    if let Some(resource_id) = resource_id
-                        && let Some(rs) = engine.store.get_resource(&resource_id) {
-                            let mut guard = rs.try_write().expect("replay: uncontended write");
-                            engine.store.apply_event(&mut guard, other);
-                        }
+                self.store.remove_calendar(id);
+            }
+            Event::CalendarRuleAdded { calendar_id, .. }
+            | Event::CalendarRuleUpdated { calendar_id, .. }
+            | Event::CalendarRuleRemoved { calendar_id, .. } => {
+                if let Some(cal) = self.store.get_calendar(calendar_id) {
+                    let mut guard = cal.try_write().expect("replay: uncontended write");
+                    self.store.apply_calendar_event(&mut guard, event);
                 }
             }
+            other => {
+                let resource_id = event_resource_id(other);
+                if let Some(resource_id) = resource_id
+                    && let Some(rs) = self.store.get_resource(&resource_id) {
+                        let mut guard = rs.try_write().expect("replay: uncontended write");
+                        self.store.apply_event(&mut guard, other);
+                    }
+            }
         }
+    }
 
-        Ok(engine)
+    /// Drop a deleted resource and every index entry pointing at it.
+    fn remove_resource(&self, rs: &ResourceState) {
+        if let Some(pid) = rs.parent_id {
+            self.store.remove_child(&pid, &rs.id);
+            if let Some(position) = rs.settings.position {
+                self.store.release_position(&pid, position, &rs.id);
+            }
+        }
+        self.store.unlink_composite(&rs.id, &rs.settings.components);
+        self.store.leave_groups(&rs.id);
+        for cid in &rs.calendar_ids {
+            self.store.detach_calendar(cid, &rs.id);
+        }
+        for interval in rs.intervals.iter().chain(rs.cancelled.iter().map(|c| &c.interval)) {
+            self.store.unindex_owner(interval);
+        }
+        self.store.remove_resource(&rs.id);
     }
 
     /// Write event to WAL via the background group-commit writer.
     async fn wal_append(&self, event: &Event) -> Result<(), EngineError> {
+        // Mutations touch the store only after this append (anything taken
+        // out to validate goes back first, under the same locks), so a dry
+        // run stops here with nothing changed.
+        if DRY_RUN.try_with(|reached| reached.set(true)).is_ok() {
+            return Err(EngineError::DryRun);
+        }
         let (tx, rx) = oneshot::channel();
         self.wal_tx
             .send(WalCommand::Append {
-                event: event.clone(),
+                event: Box::new(event.clone()),
                 response: tx,
             })
             .await
@@ -203,6 +291,20 @@ impl Engine {
             .map_err(|e| EngineError::WalError(e.to_string()))
     }
 
+    /// Run `mutation` through all of its checks without writing or applying
+    /// anything: its first WAL write fails with `EngineError::DryRun`
+    /// instead. Returns whether it got that far, along with its result. A
+    /// mutation that returns `Ok` without writing (an identical retry, say)
+    /// would also succeed.
+    pub async fn dry_run<F: Future>(&self, mutation: F) -> (bool, F::Output) {
+        DRY_RUN
+            .scope(Cell::new(false), async move {
+                let output = mutation.await;
+                (DRY_RUN.with(Cell::get), output)
+            })
+            .await
+    }
+
     pub fn get_resource(&self, id: &Ulid) -> Option<SharedResourceState> {
         self.store.get_resource(id)
     }
@@ -223,10 +325,77 @@ impl Engine {
         self.wal_append(event).await?;
         self.store.apply_event(rs, event);
         self.notify.send(resource_id, event);
+        self.notify_components(rs, event);
         self.notify_ancestors(rs.parent_id, event);
         Ok(())
     }
 
+    /// An event on a composite also concerns each of its components.
+    pub(super) fn notify_components(&self, rs: &ResourceState, event: &Event) {
+        for component in &rs.settings.components {
+            self.notify.send(*component, event);
+        }
+    }
+
+    /// Write `events` as one `Batch` WAL record, apply each to its resource,
+    /// and send the batch once to every touched resource and its ancestors.
+    /// `guards` must hold every resource the events touch, sorted by id.
+    pub(super) async fn persist_and_apply_batch(
+        &self,
+        guards: &mut [OwnedRwLockWriteGuard<ResourceState>],
+        events: Vec<Event>,
+    ) -> Result<(), EngineError> {
+        if events.is_empty() {
+            return Ok(());
+        }
+        let batch = Event::Batch { events };
+        self.wal_append(&batch).await?;
+        let Event::Batch { events } = &batch else { unreachable!() };
+
+        let mut touched = Vec::new();
+        for event in events {
+            let rid = event_resource_id(event).expect("batched events belong to a resource");
+            let idx = guards
+                .binary_search_by_key(&rid, |g| g.id)
+                .expect("batched resource is locked");
+            self.store.apply_event(&mut guards[idx], event);
+            touched.push(rid);
+        }
+
+        // Ancestors may be among the locked resources, so read parents
+        // through the guards where possible.
+        let parent_of = |id: Ulid| match guards.binary_search_by_key(&id, |g| g.id) {
+            Ok(idx) => guards[idx].parent_id,
+            Err(_) => self
+                .store
+                .get_resource(&id)
+                .and_then(|rs| rs.try_read().ok().and_then(|guard| guard.parent_id)),
+        };
+        let mut notified = HashSet::new();
+        for rid in touched {
+            let mut current = Some(rid);
+            while let Some(id) = current {
+                if !notified.insert(id) {
+                    break;
+                }
+                self.notify.send(id, &batch);
+                current = parent_of(id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Every resource in the subtree rooted at `root`, root included.
+    pub(super) fn subtree(&self, root: Ulid) -> Vec<Ulid> {
+        let mut ids = vec![root];
+        let mut i = 0;
+        while i < ids.len() {
+            ids.extend(self.store.get_children(&ids[i]));
+            i += 1;
+        }
+        ids
+    }
+
     /// Walk up the parent chain, sending the event to each ancestor's channel.
     fn notify_ancestors(&self, parent_id: Option<Ulid>, event: &Event) {
         let mut current = parent_id;
@@ -242,7 +411,7 @@ impl Engine {
     pub(super) async fn resolve_entity_write(
         &self,
         entity_id: &Ulid,
-    ) -> Result<(Ulid, tokio::sync::OwnedRwLockWriteGuard<ResourceState>), EngineError> {
+    ) -> Result<(Ulid, OwnedRwLockWriteGuard<ResourceState>), EngineError> {
         let resource_id = self
             .get_resource_for_entity(entity_id)
             .ok_or(EngineError::NotFound(*entity_id))?;
@@ -263,8 +432,20 @@ fn event_resource_id(event: &Event) -> Option<Ulid> {
         | Event::HoldPlaced { resource_id, .. }
         | Event::HoldReleased { resource_id, .. }
         | Event::BookingConfirmed { resource_id, .. }
-        | Event::BookingCancelled { resource_id, .. } => Some(*resource_id),
+        | Event::BookingCancelled { resource_id, .. }
+        | Event::BookingStatusChanged { resource_id, .. }
+        | Event::CalendarAttached { resource_id, .. }
+        | Event::CalendarDetached { resource_id, .. } => Some(*resource_id),
         Event::ResourceUpdated { id, .. } => Some(*id),
-        Event::ResourceCreated { .. } | Event::ResourceDeleted { .. } => None,
+        Event::ResourceCreated { .. }
+        | Event::ResourceDeleted { .. }
+        | Event::GroupCreated { .. }
+        | Event::GroupDeleted { .. }
+        | Event::CalendarCreated { .. }
+        | Event::CalendarDeleted { .. }
+        | Event::CalendarRuleAdded { .. }
+        | Event::CalendarRuleUpdated { .. }
+        | Event::CalendarRuleRemoved { .. }
+        | Event::Batch { .. } => None,
     }
 }
@@ -1,15 +1,21 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 
-use tokio::sync::{oneshot, RwLock};
+use tokio::sync::{oneshot, OwnedRwLockWriteGuard, RwLock};
 use ulid::Ulid;
 
 use crate::limits::*;
 use crate::model::*;
 
 use super::availability::subtract_intervals;
-use super::conflict::{check_no_conflict, now_ms, validate_span};
-use super::{Engine, EngineError, WalCommand};
+use super::composites::AllocationLocks;
+use super::conflict::{
+    check_alignment, check_allocation, check_components, check_group_limit, check_no_conflict, check_policy,
+    check_quota, group_allocations, now_ms, pending_conflict,
+    validate_alloc_options, validate_buffer, validate_granularity, validate_policy, validate_quota,
+    validate_span,
+};
+use super::{ConflictingInterval, Engine, EngineError, WalCommand};
 
 impl Engine {
     pub async fn create_resource(
@@ -20,6 +26,33 @@ impl Engine {
         capacity: u32,
         buffer_after: Option<Ms>,
     ) -> Result<(), EngineError> {
+        self.create_resource_with_settings(id, parent_id, name, capacity, buffer_after, ResourceSettings::default())
+            .await
+    }
+
+    /// Create a resource. Re-submitting an existing id with the same payload
+    /// is a no-op; a different payload fails with `AlreadyExists`.
+    pub async fn create_resource_with_settings(
+        &self,
+        id: Ulid,
+        parent_id: Option<Ulid>,
+        name: Option<String>,
+        capacity: u32,
+        buffer_after: Option<Ms>,
+        settings: ResourceSettings,
+    ) -> Result<(), EngineError> {
+        if let Some(existing) = self.get_resource(&id) {
+            let guard = existing.read().await;
+            if guard.parent_id == parent_id
+                && guard.name == name
+                && guard.capacity == capacity
+                && guard.buffer_after == buffer_after
+                && guard.settings == settings
+            {
+                return Ok(());
+            }
+            return Err(EngineError::AlreadyExists(id));
+        }
         if self.store.resource_count() >= MAX_RESOURCES_PER_TENANT {
             return Err(EngineError::LimitExceeded("too many resources"));
         }
@@ -27,6 +60,12 @@ impl Engine {
             && n.len() > MAX_NAME_LEN {
                 return Err(EngineError::LimitExceeded("resource name too long"));
             }
+        validate_buffer(buffer_after)?;
+        validate_buffer(settings.buffer_before)?;
+        validate_policy(&settings.policy)?;
+        validate_granularity(&settings)?;
+        validate_quota(&settings.quota)?;
+        self.validate_components(id, &settings.components).await?;
         if let Some(pid) = parent_id {
             let mut depth = 0usize;
             let mut cur = Some(pid);
@@ -52,9 +91,29 @@ impl Engine {
             }
         }
 
-        let event = Event::ResourceCreated { id, parent_id, name: name.clone(), capacity, buffer_after };
-        self.wal_append(&event).await?;
-        let rs = ResourceState::new(id, parent_id, name, capacity, buffer_after);
+        let event = Event::ResourceCreated {
+            id,
+            parent_id,
+            name: name.clone(),
+            capacity,
+            buffer_after,
+            settings: settings.clone(),
+        };
+        // Claimed before the WAL write, so two siblings cannot both take it.
+        if let (Some(pid), Some(position)) = (parent_id, settings.position) {
+            self.store
+                .claim_position(pid, position, id)
+                .map_err(EngineError::AlreadyExists)?;
+        }
+        if let Err(e) = self.wal_append(&event).await {
+            if let (Some(pid), Some(position)) = (parent_id, settings.position) {
+                self.store.release_position(&pid, position, &id);
+            }
+            return Err(e);
+        }
+        self.store.link_composite(id, &settings.components);
+        let mut rs = ResourceState::new(id, parent_id, name, capacity, buffer_after);
+        rs.settings = settings;
         self.store.insert_resource(id, Arc::new(RwLock::new(rs)));
         if let Some(pid) = parent_id {
             self.store.add_child(pid, id);
@@ -64,6 +123,174 @@ impl Engine {
         Ok(())
     }
 
+    /// Copy the subtree rooted at `source` under `parent_id` with fresh ids:
+    /// names, capacities, buffers and settings always, rules and calendar
+    /// attachments too if `include_rules`. Returns (source id, new id) pairs,
+    /// parents before children. The copy is written as one WAL record and
+    /// attached to `parent_id` last, so it is never reachable half-built.
+    pub async fn clone_subtree(
+        &self,
+        source: Ulid,
+        parent_id: Option<Ulid>,
+        include_rules: bool,
+    ) -> Result<Vec<(Ulid, Ulid)>, EngineError> {
+        if !self.store.contains_resource(&source) {
+            return Err(EngineError::NotFound(source));
+        }
+        let mut parent_depth = 0usize;
+        if let Some(pid) = parent_id {
+            if !self.store.contains_resource(&pid) {
+                return Err(EngineError::NotFound(pid));
+            }
+            let mut cur = Some(pid);
+            while let Some(cid) = cur {
+                parent_depth += 1;
+                cur = self.get_resource(&cid).and_then(|rs| {
+                    rs.try_read().ok().and_then(|g| g.parent_id)
+                });
+            }
+        }
+
+        let mut mapping = Vec::new();
+        let mut clones: Vec<(ResourceState, Vec<Event>)> = Vec::new();
+        let mut queue = VecDeque::from([(source, parent_id, parent_depth + 1)]);
+        while let Some((old_id, new_parent, depth)) = queue.pop_front() {
+            if depth > MAX_HIERARCHY_DEPTH {
+                return Err(EngineError::LimitExceeded("hierarchy too deep"));
+            }
+            if self.store.resource_count() + clones.len() >= MAX_RESOURCES_PER_TENANT {
+                return Err(EngineError::LimitExceeded("too many resources"));
+            }
+            let rs = self.get_resource(&old_id).ok_or(EngineError::NotFound(old_id))?;
+            let src = rs.read().await;
+            let id = Ulid::new();
+            let mut state = ResourceState::new(id, new_parent, src.name.clone(), src.capacity, src.buffer_after);
+            state.settings = src.settings.clone();
+            let mut events = Vec::new();
+            if include_rules {
+                for interval in src.intervals.iter().filter(|i| i.is_rule()) {
+                    let (blocking, capacity) = match interval.kind {
+                        IntervalKind::Blocking => (true, None),
+                        IntervalKind::Capacity { capacity } => (false, Some(capacity)),
+                        _ => (false, None),
+                    };
+                    events.push(Event::RuleAdded {
+                        id: Ulid::new(),
+                        resource_id: id,
+                        span: interval.span,
+                        blocking,
+                        capacity,
+                    });
+                }
+                events.extend(src.calendar_ids.iter().map(|&calendar_id| Event::CalendarAttached {
+                    calendar_id,
+                    resource_id: id,
+                }));
+            }
+            drop(src);
+            // Only the root moves to a new parent; below it the copy keeps
+            // the source's shape, so its rules stay covered.
+            if old_id == source {
+                for event in &events {
+                    if let Event::RuleAdded { span, blocking, capacity, .. } = event {
+                        self.check_covered_by_parent(&state, *span, *blocking, *capacity).await?;
+                    }
+                }
+            }
+            for child in self.store.get_children(&old_id) {
+                queue.push_back((child, Some(id), depth + 1));
+            }
+            mapping.push((old_id, id));
+            clones.push((state, events));
+        }
+        // Composites copied along with their components use the copies.
+        let new_ids: HashMap<Ulid, Ulid> = mapping.iter().copied().collect();
+        for (rs, _) in &mut clones {
+            for component in &mut rs.settings.components {
+                if let Some(&new_id) = new_ids.get(component) {
+                    *component = new_id;
+                }
+            }
+        }
+
+        let events = clones
+            .iter()
+            .flat_map(|(rs, events)| {
+                std::iter::once(Event::ResourceCreated {
+                    id: rs.id,
+                    parent_id: rs.parent_id,
+                    name: rs.name.clone(),
+                    capacity: rs.capacity,
+                    buffer_after: rs.buffer_after,
+                    settings: rs.settings.clone(),
+                })
+                .chain(events.iter().cloned())
+            })
+            .collect();
+        // As in `attach_calendar`: hold the attached calendars until the
+        // copies are in place, so `delete_calendar` sees the attachments.
+        let mut calendar_ids: Vec<Ulid> = clones
+            .iter()
+            .flat_map(|(_, events)| events)
+            .filter_map(|event| match event {
+                Event::CalendarAttached { calendar_id, .. } => Some(*calendar_id),
+                _ => None,
+            })
+            .collect();
+        calendar_ids.sort();
+        calendar_ids.dedup();
+        let mut calendar_guards = Vec::with_capacity(calendar_ids.len());
+        for calendar_id in calendar_ids {
+            let cal = self
+                .store
+                .get_calendar(&calendar_id)
+                .ok_or(EngineError::NotFound(calendar_id))?;
+            calendar_guards.push(cal.read_owned().await);
+            if !self.store.contains_calendar(&calendar_id) {
+                return Err(EngineError::NotFound(calendar_id));
+            }
+        }
+        // Below the root, positions come from unique source siblings, so
+        // only the root can collide with a child of `parent_id`.
+        let positioned: Vec<(Ulid, i64, Ulid)> = clones
+            .iter()
+            .filter_map(|(rs, _)| Some((rs.parent_id?, rs.settings.position?, rs.id)))
+            .collect();
+        let release = |claimed: &[(Ulid, i64, Ulid)]| {
+            for (pid, position, id) in claimed {
+                self.store.release_position(pid, *position, id);
+            }
+        };
+        for (i, &(pid, position, id)) in positioned.iter().enumerate() {
+            if let Err(holder) = self.store.claim_position(pid, position, id) {
+                release(&positioned[..i]);
+                return Err(EngineError::AlreadyExists(holder));
+            }
+        }
+        let batch = Event::Batch { events };
+        if let Err(e) = self.wal_append(&batch).await {
+            release(&positioned);
+            return Err(e);
+        }
+
+        // Leaves first, so the copy only becomes reachable from `parent_id`
+        // once all of it is in place.
+        for (mut rs, events) in clones.into_iter().rev() {
+            for event in &events {
+                self.store.apply_event(&mut rs, event);
+            }
+            let (id, parent) = (rs.id, rs.parent_id);
+            self.store.link_composite(id, &rs.settings.components);
+            self.store.insert_resource(id, Arc::new(RwLock::new(rs)));
+            if let Some(pid) = parent {
+                self.store.add_child(pid, id);
+            }
+        }
+        drop(calendar_guards);
+        self.notify_ancestors(parent_id, &batch);
+        Ok(mapping)
+    }
+
     pub async fn delete_resource(&self, id: Ulid) -> Result<(), EngineError> {
         if !self.store.contains_resource(&id) {
             return Err(EngineError::NotFound(id));
@@ -71,18 +298,18 @@ impl Engine {
         if self.store.has_children(&id) {
             return Err(EngineError::HasChildren(id));
         }
+        if self.store.has_composites(&id) {
+            return Err(EngineError::ComponentInUse(id));
+        }
 
         let rs = self.get_resource(&id).unwrap();
         let guard = rs.read().await;
         let parent_id = guard.parent_id;
-        if let Some(pid) = parent_id {
-            self.store.remove_child(&pid, &id);
-        }
-        drop(guard);
 
         let event = Event::ResourceDeleted { id };
         self.wal_append(&event).await?;
-        self.store.remove_resource(&id);
+        self.remove_resource(&guard);
+        drop(guard);
         self.notify.send(id, &event);
         self.notify_ancestors(parent_id, &event);
         Ok(())
@@ -94,20 +321,66 @@ impl Engine {
         resource_id: Ulid,
         span: Span,
         blocking: bool,
+    ) -> Result<(), EngineError> {
+        self.add_rule_inner(id, resource_id, span, blocking, None).await
+    }
+
+    /// Add a rule overriding the resource's capacity within `span`.
+    pub async fn add_capacity_rule(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        capacity: u32,
+    ) -> Result<(), EngineError> {
+        self.add_rule_inner(id, resource_id, span, false, Some(capacity)).await
+    }
+
+    async fn add_rule_inner(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        blocking: bool,
+        capacity: Option<u32>,
     ) -> Result<(), EngineError> {
         validate_span(&span)?;
         let rs = self
             .get_resource(&resource_id)
             .ok_or(EngineError::NotFound(resource_id))?;
         let mut guard = rs.write().await;
+        if let Some(pos) = self.existing_position(&guard, id, Interval::is_rule)? {
+            let existing = &guard.intervals[pos];
+            if existing.span == span && existing.kind == IntervalKind::rule(blocking, capacity) {
+                return Ok(());
+            }
+            return Err(EngineError::AlreadyExists(id));
+        }
         if guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
             return Err(EngineError::LimitExceeded("too many intervals on resource"));
         }
 
+        self.check_covered_by_parent(&guard, span, blocking, capacity).await?;
+
+        let event = Event::RuleAdded { id, resource_id, span, blocking, capacity };
+        self.persist_and_apply(resource_id, &mut guard, &event).await
+    }
+
+    /// Only override-mode children are confined to the parent's hours:
+    /// intersect clips to them anyway, union and isolate may extend past them.
+    async fn check_covered_by_parent(
+        &self,
+        rs: &ResourceState,
+        span: Span,
+        blocking: bool,
+        capacity: Option<u32>,
+    ) -> Result<(), EngineError> {
         if !blocking
-            && let Some(parent_id) = guard.parent_id {
+            && capacity.is_none()
+            && rs.settings.inheritance == InheritanceMode::Override
+            && let Some(parent_id) = rs.parent_id {
                 let parent_free = self
-                    .compute_availability(parent_id, span.start, span.end, None)
+                    .compute_availability_inner(parent_id, span.start, span.end, false)
                     .await?;
                 let rule_as_slice = [span];
                 let uncovered = subtract_intervals(&rule_as_slice, &parent_free);
@@ -118,9 +391,85 @@ impl Engine {
                     });
                 }
             }
+        Ok(())
+    }
+
+    /// Replace every rule of `resource_id` lying wholly within `span` with
+    /// `rules` (id, span, blocking, capacity), under one write lock and as
+    /// one WAL record, so readers never see the resource half-published.
+    /// New rules must lie within `span`. Returns the new rule ids.
+    pub async fn replace_rules(
+        &self,
+        resource_id: Ulid,
+        span: Span,
+        rules: Vec<(Ulid, Span, bool, Option<u32>)>,
+    ) -> Result<Vec<Ulid>, EngineError> {
+        validate_span(&span)?;
+        let mut ids = HashSet::new();
+        for (id, rule_span, _, _) in &rules {
+            validate_span(rule_span)?;
+            if !span.contains_span(rule_span) {
+                return Err(EngineError::InvalidArgument("rule outside the replaced span"));
+            }
+            if !ids.insert(*id) {
+                return Err(EngineError::AlreadyExists(*id));
+            }
+        }
+        let rs = self
+            .get_resource(&resource_id)
+            .ok_or(EngineError::NotFound(resource_id))?;
+        let mut guard = rs.write_owned().await;
 
-        let event = Event::RuleAdded { id, resource_id, span, blocking };
-        self.persist_and_apply(resource_id, &mut guard, &event).await
+        let old: Vec<Ulid> = guard
+            .intervals
+            .iter()
+            .filter(|i| i.is_rule() && span.contains_span(&i.span))
+            .map(|i| i.id)
+            .collect();
+        // A new rule may reuse the id of a rule it replaces, nothing else.
+        let replaced: HashSet<Ulid> = old.iter().copied().collect();
+        for id in &ids {
+            if self.get_resource_for_entity(id).is_some() && !replaced.contains(id) {
+                return Err(EngineError::AlreadyExists(*id));
+            }
+        }
+        if guard.intervals.len() - old.len() + rules.len() > MAX_INTERVALS_PER_RESOURCE {
+            return Err(EngineError::LimitExceeded("too many intervals on resource"));
+        }
+        for (_, rule_span, blocking, capacity) in &rules {
+            self.check_covered_by_parent(&guard, *rule_span, *blocking, *capacity).await?;
+        }
+
+        let mut events: Vec<Event> = old.into_iter().map(|id| Event::RuleRemoved { id, resource_id }).collect();
+        let new_ids = rules.iter().map(|(id, ..)| *id).collect();
+        events.extend(rules.into_iter().map(|(id, span, blocking, capacity)| Event::RuleAdded {
+            id,
+            resource_id,
+            span,
+            blocking,
+            capacity,
+        }));
+        self.persist_and_apply_batch(std::slice::from_mut(&mut guard), events).await?;
+        Ok(new_ids)
+    }
+
+    /// Add a rule, or update it in place if `id` is already a rule on
+    /// `resource_id`.
+    pub async fn replace_rule(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        blocking: bool,
+        capacity: Option<u32>,
+    ) -> Result<(), EngineError> {
+        match self.store.get_resource_for_entity(&id) {
+            None => self.add_rule_inner(id, resource_id, span, blocking, capacity).await,
+            Some(rid) if rid == resource_id => {
+                self.update_rule_inner(id, span, blocking, capacity).await.map(|_| ())
+            }
+            Some(_) => Err(EngineError::AlreadyExists(id)),
+        }
     }
 
     pub async fn remove_rule(&self, id: Ulid) -> Result<Ulid, EngineError> {
@@ -136,20 +485,71 @@ impl Engine {
         resource_id: Ulid,
         span: Span,
         expires_at: Ms,
+    ) -> Result<(), EngineError> {
+        self.place_hold_with_options(id, resource_id, span, expires_at, AllocOptions::default())
+            .await
+    }
+
+    /// Place a hold. Re-submitting an existing id with the same payload is a
+    /// no-op; a different payload fails with `AlreadyExists`.
+    pub async fn place_hold_with_options(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        expires_at: Ms,
+        options: AllocOptions,
+    ) -> Result<(), EngineError> {
+        self.place_hold_inner(id, resource_id, span, expires_at, options, false).await
+    }
+
+    /// Place a hold, or replace the hold with this id on the same resource.
+    pub async fn replace_hold(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        expires_at: Ms,
+        options: AllocOptions,
+    ) -> Result<(), EngineError> {
+        self.place_hold_inner(id, resource_id, span, expires_at, options, true).await
+    }
+
+    async fn place_hold_inner(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        expires_at: Ms,
+        options: AllocOptions,
+        replace: bool,
     ) -> Result<(), EngineError> {
         validate_span(&span)?;
-        let rs = self
-            .get_resource(&resource_id)
-            .ok_or(EngineError::NotFound(resource_id))?;
-        let mut guard = rs.write().await;
-        if guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
+        validate_alloc_options(&options)?;
+        let (policy, mut locks, target) = self.lock_allocation_target(resource_id).await?;
+        let guard = &locks.guards[target];
+        let existing = self.existing_position(guard, id, |i| matches!(i.kind, IntervalKind::Hold { .. }))?;
+        if let Some(pos) = existing
+            && !replace {
+                let kind = IntervalKind::Hold { expires_at, options };
+                if guard.intervals[pos].span == span && guard.intervals[pos].kind == kind {
+                    return Ok(());
+                }
+                return Err(EngineError::AlreadyExists(id));
+            }
+        if existing.is_none() && guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
             return Err(EngineError::LimitExceeded("too many intervals on resource"));
         }
 
-        check_no_conflict(&guard, &span, now_ms())?;
+        self.check_open(&locks, resource_id, &span).await?;
+        let now = now_ms();
+        check_replacing(&mut locks.guards, target, existing, |locked| {
+            check_allocation(&locked[target], &policy, &span, &options, now)?;
+            check_components(locked, &locks.groups, target, &span, &locked[target].footprint(&span, &options), now)
+        })?;
 
-        let event = Event::HoldPlaced { id, resource_id, span, expires_at };
-        self.persist_and_apply(resource_id, &mut guard, &event).await
+        let event = Event::HoldPlaced { id, resource_id, span, expires_at, options };
+        self.persist_and_apply(resource_id, &mut locks.guards[target], &event).await
     }
 
     pub async fn release_hold(&self, id: Ulid) -> Result<Ulid, EngineError> {
@@ -165,24 +565,76 @@ impl Engine {
         resource_id: Ulid,
         span: Span,
         label: Option<String>,
+    ) -> Result<(), EngineError> {
+        self.confirm_booking_with_options(id, resource_id, span, label, AllocOptions::default())
+            .await
+    }
+
+    /// Confirm a booking. Re-submitting an existing id with the same payload
+    /// is a no-op; a different payload fails with `AlreadyExists`.
+    pub async fn confirm_booking_with_options(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        label: Option<String>,
+        options: AllocOptions,
+    ) -> Result<(), EngineError> {
+        self.confirm_booking_inner(id, resource_id, span, label, options, false).await
+    }
+
+    /// Confirm a booking, or replace the live booking with this id on the
+    /// same resource. The replacement is checked as if the old booking were
+    /// gone, and starts out confirmed.
+    pub async fn replace_booking(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        label: Option<String>,
+        options: AllocOptions,
+    ) -> Result<(), EngineError> {
+        self.confirm_booking_inner(id, resource_id, span, label, options, true).await
+    }
+
+    async fn confirm_booking_inner(
+        &self,
+        id: Ulid,
+        resource_id: Ulid,
+        span: Span,
+        label: Option<String>,
+        options: AllocOptions,
+        replace: bool,
     ) -> Result<(), EngineError> {
         validate_span(&span)?;
         if let Some(ref l) = label
             && l.len() > MAX_LABEL_LEN {
                 return Err(EngineError::LimitExceeded("label too long"));
             }
-        let rs = self
-            .get_resource(&resource_id)
-            .ok_or(EngineError::NotFound(resource_id))?;
-        let mut guard = rs.write().await;
-        if guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
+        validate_alloc_options(&options)?;
+        let (policy, mut locks, target) = self.lock_allocation_target(resource_id).await?;
+        let guard = &locks.guards[target];
+        let existing = self.existing_position(guard, id, |i| matches!(i.kind, IntervalKind::Booking { .. }))?;
+        if let Some(pos) = existing
+            && !replace {
+                if is_same_booking(&guard.intervals[pos], &span, &label, &options) {
+                    return Ok(());
+                }
+                return Err(EngineError::AlreadyExists(id));
+            }
+        if existing.is_none() && guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
             return Err(EngineError::LimitExceeded("too many intervals on resource"));
         }
 
-        check_no_conflict(&guard, &span, now_ms())?;
+        self.check_open(&locks, resource_id, &span).await?;
+        let now = now_ms();
+        check_replacing(&mut locks.guards, target, existing, |locked| {
+            check_allocation(&locked[target], &policy, &span, &options, now)?;
+            check_components(locked, &locks.groups, target, &span, &locked[target].footprint(&span, &options), now)
+        })?;
 
-        let event = Event::BookingConfirmed { id, resource_id, span, label };
-        self.persist_and_apply(resource_id, &mut guard, &event).await
+        let event = Event::BookingConfirmed { id, resource_id, span, label, options };
+        self.persist_and_apply(resource_id, &mut locks.guards[target], &event).await
     }
 
     /// Atomically book multiple slots. All-or-nothing: if any booking conflicts,
@@ -191,111 +643,398 @@ impl Engine {
         &self,
         bookings: Vec<(Ulid, Ulid, Span, Option<String>)>,
     ) -> Result<(), EngineError> {
+        let bookings = bookings
+            .into_iter()
+            .map(|(id, rid, span, label)| (id, rid, span, label, AllocOptions::default()))
+            .collect();
+        self.batch_confirm_bookings_with_options(bookings).await
+    }
+
+    /// Like `batch_confirm_bookings`, with per-booking options.
+    pub async fn batch_confirm_bookings_with_options(
+        &self,
+        bookings: Vec<(Ulid, Ulid, Span, Option<String>, AllocOptions)>,
+    ) -> Result<(), EngineError> {
+        self.batch_upsert_bookings(bookings, OnConflict::Error).await.map(|_| ())
+    }
+
+    /// Atomic batch where ids that already exist are handled per `on_conflict`:
+    /// identical retries are always skipped, `DoNothing` skips any existing
+    /// id, and `DoUpdate` replaces the existing booking. Returns the ids of
+    /// the bookings now in place, identical retries included, in batch order.
+    pub async fn batch_upsert_bookings(
+        &self,
+        bookings: Vec<(Ulid, Ulid, Span, Option<String>, AllocOptions)>,
+        on_conflict: OnConflict,
+    ) -> Result<Vec<Ulid>, EngineError> {
         if bookings.is_empty() {
-            return Ok(());
+            return Ok(Vec::new());
         }
         if bookings.len() > MAX_BATCH_SIZE {
             return Err(EngineError::LimitExceeded("batch too large"));
         }
-        for (_, _, span, label) in &bookings {
+        let mut ids = HashSet::new();
+        for (id, _, span, label, options) in &bookings {
+            if !ids.insert(*id) {
+                return Err(EngineError::AlreadyExists(*id));
+            }
             validate_span(span)?;
             if let Some(l) = label
                 && l.len() > MAX_LABEL_LEN {
                     return Err(EngineError::LimitExceeded("label too long"));
                 }
+            validate_alloc_options(options)?;
         }
 
         // Acquire write locks in sorted order to prevent deadlocks.
-        let mut resource_ids: Vec<Ulid> = bookings.iter().map(|(_, rid, _, _)| *rid).collect();
+        let mut resource_ids: Vec<Ulid> = bookings.iter().map(|(_, rid, _, _, _)| *rid).collect();
         resource_ids.sort();
         resource_ids.dedup();
 
-        let mut guards = Vec::with_capacity(resource_ids.len());
-        let mut rs_map = HashMap::new();
-
+        // Composites and their components are locked alongside.
+        let targets = self.allocation_targets(&resource_ids).await?;
+        let mut locks = self.lock_for_allocation(&targets).await?;
+        let mut policies = HashMap::new();
         for rid in &resource_ids {
-            let rs = self
-                .get_resource(rid)
-                .ok_or(EngineError::NotFound(*rid))?;
-            let guard = rs.write_owned().await;
-            if guard.intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
+            policies.insert(*rid, locks.policy(*rid)?);
+        }
+        let rs_map: HashMap<Ulid, usize> = locks.guards.iter().enumerate().map(|(i, g)| (g.id, i)).collect();
+        for rid in &resource_ids {
+            if locks.guards[rs_map[rid]].intervals.len() >= MAX_INTERVALS_PER_RESOURCE {
                 return Err(EngineError::LimitExceeded("too many intervals on resource"));
             }
-            rs_map.insert(*rid, guards.len());
-            guards.push(guard);
+        }
+
+        // Sort out ids that already exist before validating the rest.
+        let mut pending = Vec::with_capacity(bookings.len());
+        let mut replaced = Vec::new();
+        let mut accepted = Vec::with_capacity(bookings.len());
+        for booking in bookings {
+            let (id, rid, span, label, options) = &booking;
+            let guard = &locks.guards[rs_map[rid]];
+            let existing = match self.existing_position(guard, *id, |i| matches!(i.kind, IntervalKind::Booking { .. })) {
+                Ok(existing) => existing,
+                Err(_) if on_conflict == OnConflict::DoNothing => continue,
+                Err(e) => return Err(e),
+            };
+            match (existing, on_conflict) {
+                (None, _) => {
+                    accepted.push(*id);
+                    pending.push(booking);
+                }
+                (Some(pos), _) if is_same_booking(&guard.intervals[pos], span, label, options) => accepted.push(*id),
+                (Some(_), OnConflict::DoNothing) => {}
+                (Some(_), OnConflict::Error) => return Err(EngineError::AlreadyExists(*id)),
+                (Some(_), OnConflict::DoUpdate) => {
+                    accepted.push(*id);
+                    replaced.push((*id, *rid));
+                    pending.push(booking);
+                }
+            }
+        }
+
+        for (_, rid, span, ..) in &pending {
+            self.check_open(&locks, *rid, span).await?;
+        }
+
+        // Bookings being replaced must not conflict with their new versions.
+        let mut taken_out = Vec::with_capacity(replaced.len());
+        for (id, rid) in &replaced {
+            let guard = &mut locks.guards[rs_map[rid]];
+            if let Some(old) = guard.remove_interval(*id) {
+                taken_out.push((*rid, old));
+            }
         }
 
         // Phase 1: Validate all bookings against current state + intra-batch.
         let now = now_ms();
 
-        let mut by_resource: HashMap<Ulid, Vec<(Ulid, Span)>> = HashMap::new();
-        for (id, rid, span, _) in &bookings {
-            by_resource.entry(*rid).or_default().push((*id, *span));
+        let mut by_resource: HashMap<Ulid, Vec<(Ulid, Span, &AllocOptions)>> = HashMap::new();
+        for (id, rid, span, _, options) in &pending {
+            by_resource.entry(*rid).or_default().push((*id, *span, options));
         }
 
-        for (rid, batch) in &by_resource {
-            let guard = &guards[rs_map[rid]];
+        let validate = || -> Result<(), EngineError> {
+            for (rid, batch) in &by_resource {
+                let guard = &locks.guards[rs_map[rid]];
 
-            for (_, span) in batch {
-                check_no_conflict(guard, span, now)?;
+                // The rest of the batch on this resource counts as if applied.
+                let batch_intervals: Vec<Interval> = batch
+                    .iter()
+                    .map(|(id, span, options)| Interval {
+                        id: *id,
+                        span: *span,
+                        kind: IntervalKind::Booking {
+                            label: None,
+                            options: (*options).clone(),
+                            status: BookingStatus::default(),
+                        },
+                    })
+                    .collect();
+                for (i, (id, span, options)) in batch.iter().enumerate() {
+                    let others: Vec<Interval> = batch_intervals.iter().filter(|b| b.id != *id).cloned().collect();
+                    let footprint = guard.footprint(span, options);
+                    check_alignment(&guard.settings, span)?;
+                    check_policy(&policies[rid], span, now)?;
+                    check_no_conflict(guard, &others, span, &footprint, now)?;
+                    check_components(&locks.guards, &locks.groups, rs_map[rid], span, &footprint, now)?;
+                    if options.owner.is_some() {
+                        let pending: Vec<Span> = batch
+                            .iter()
+                            .enumerate()
+                            .filter(|(j, (_, _, other))| *j != i && other.owner == options.owner)
+                            .map(|(_, (_, s, _))| *s)
+                            .collect();
+                        check_quota(guard, options.owner.as_deref(), span, &pending, now)?;
+                    }
+                }
             }
 
-            if batch.len() > 1 {
-                let buffer = guard.buffer_after.unwrap_or(0);
-                for i in 0..batch.len() {
-                    for j in (i + 1)..batch.len() {
-                        let effective_i = Span::new(batch[i].1.start, batch[i].1.end + buffer);
-                        if effective_i.overlaps(&batch[j].1) {
-                            return Err(EngineError::Conflict(batch[i].0));
-                        }
-                        let effective_j = Span::new(batch[j].1.start, batch[j].1.end + buffer);
-                        if effective_j.overlaps(&batch[i].1) {
-                            return Err(EngineError::Conflict(batch[j].0));
-                        }
+            // Bookings on different resources may still share a component.
+            let occupied: Vec<&[Ulid]> = pending
+                .iter()
+                .map(|(_, rid, ..)| {
+                    let components = &locks.guards[rs_map[rid]].settings.components;
+                    if components.is_empty() { std::slice::from_ref(rid) } else { components.as_slice() }
+                })
+                .collect();
+            for (i, (id_a, rid_a, span_a, _, options_a)) in pending.iter().enumerate() {
+                for (j, (id_b, rid_b, span_b, _, options_b)) in pending.iter().enumerate().skip(i + 1) {
+                    if rid_a == rid_b || !occupied[i].iter().any(|c| occupied[j].contains(c)) {
+                        continue;
+                    }
+                    let footprint_a = locks.guards[rs_map[rid_a]].footprint(span_a, options_a);
+                    if footprint_a.overlaps(span_b) {
+                        return Err(pending_conflict(*id_a, *span_a, footprint_a, span_b));
+                    }
+                    let footprint_b = locks.guards[rs_map[rid_b]].footprint(span_b, options_b);
+                    if footprint_b.overlaps(span_a) {
+                        return Err(pending_conflict(*id_b, *span_b, footprint_b, span_a));
                     }
                 }
             }
+
+            // Bookings in one exclusion group count against its limit together.
+            for group in &locks.groups {
+                let in_group: Vec<usize> = (0..pending.len())
+                    .filter(|&i| occupied[i].iter().any(|m| group.members.contains(m)))
+                    .collect();
+                if in_group.len() < 2 {
+                    continue;
+                }
+                for &i in &in_group {
+                    let span = &pending[i].2;
+                    let mut allocs = group_allocations(&locks.guards, group, span, now);
+                    allocs.extend(in_group.iter().filter(|&&j| j != i).map(|&j| {
+                        let (id, rid, other, _, options) = &pending[j];
+                        let interval =
+                            ConflictingInterval { id: *id, kind: "booking", span: *other, buffered: !other.overlaps(span) };
+                        (interval, locks.guards[rs_map[rid]].footprint(other, options))
+                    }));
+                    check_group_limit(group, allocs, span)?;
+                }
+            }
+            Ok(())
+        };
+        let validated = validate();
+        for (rid, old) in taken_out {
+            locks.guards[rs_map[&rid]].insert_interval(old);
         }
+        validated?;
 
         // Phase 2: All validated — commit all bookings.
-        for (id, resource_id, span, label) in bookings {
-            let event = Event::BookingConfirmed { id, resource_id, span, label };
+        for (id, resource_id, span, label, options) in pending {
+            let event = Event::BookingConfirmed { id, resource_id, span, label, options };
             self.wal_append(&event).await?;
-            let guard_idx = rs_map[&resource_id];
-            let parent_id = guards[guard_idx].parent_id;
-            self.store.apply_event(&mut guards[guard_idx], &event);
+            let guard = &mut locks.guards[rs_map[&resource_id]];
+            self.store.apply_event(guard, &event);
             self.notify.send(resource_id, &event);
-            self.notify_ancestors(parent_id, &event);
+            self.notify_components(guard, &event);
+            self.notify_ancestors(guard.parent_id, &event);
         }
 
-        Ok(())
+        Ok(accepted)
     }
 
     pub async fn cancel_booking(&self, id: Ulid) -> Result<Ulid, EngineError> {
+        self.cancel_booking_with_reason(id, None).await
+    }
+
+    /// Cancel a booking, freeing its time. The booking is kept, with its
+    /// reason and cancellation time, until GC.
+    pub async fn cancel_booking_with_reason(&self, id: Ulid, reason: Option<String>) -> Result<Ulid, EngineError> {
+        if let Some(ref r) = reason
+            && r.len() > MAX_LABEL_LEN {
+                return Err(EngineError::LimitExceeded("cancel reason too long"));
+            }
+        let (resource_id, mut guard) = self.resolve_entity_write(&id).await?;
+        if !is_active_booking(&guard, id) {
+            return Err(EngineError::NotFound(id));
+        }
+        let event = Event::BookingCancelled { id, resource_id, reason, at: now_ms() };
+        self.persist_and_apply(resource_id, &mut guard, &event).await?;
+        Ok(resource_id)
+    }
+
+    /// Mark a booking confirmed, no-show or completed. Use
+    /// `cancel_booking_with_reason` to cancel.
+    pub async fn set_booking_status(&self, id: Ulid, status: BookingStatus) -> Result<Ulid, EngineError> {
+        if status == BookingStatus::Cancelled {
+            return self.cancel_booking(id).await;
+        }
         let (resource_id, mut guard) = self.resolve_entity_write(&id).await?;
-        let event = Event::BookingCancelled { id, resource_id };
+        if !is_active_booking(&guard, id) {
+            return Err(EngineError::NotFound(id));
+        }
+        let event = Event::BookingStatusChanged { id, resource_id, status };
         self.persist_and_apply(resource_id, &mut guard, &event).await?;
         Ok(resource_id)
     }
 
+    /// Remove the rules or holds, or cancel the bookings, of `resource_id`
+    /// (and with `descendants`, of every resource below it) that lie wholly
+    /// within `span`. All of it is one WAL record. Returns the affected ids.
+    pub async fn delete_in_range(
+        &self,
+        class: IntervalClass,
+        resource_id: Ulid,
+        descendants: bool,
+        span: Span,
+    ) -> Result<Vec<Ulid>, EngineError> {
+        validate_span(&span)?;
+        if self.get_resource(&resource_id).is_none() {
+            return Err(EngineError::NotFound(resource_id));
+        }
+        let mut resource_ids = if descendants { self.subtree(resource_id) } else { vec![resource_id] };
+        // Lock in sorted order, like every multi-resource write.
+        resource_ids.sort();
+        let mut guards = Vec::with_capacity(resource_ids.len());
+        for rid in &resource_ids {
+            if let Some(rs) = self.get_resource(rid) {
+                guards.push(rs.write_owned().await);
+            }
+        }
+
+        let now = now_ms();
+        let mut ids = Vec::new();
+        let mut events = Vec::new();
+        for guard in &guards {
+            for interval in guard.intervals.iter() {
+                if !class.matches(interval) || !span.contains_span(&interval.span) {
+                    continue;
+                }
+                let (id, resource_id) = (interval.id, guard.id);
+                ids.push(id);
+                events.push(match class {
+                    IntervalClass::Rules => Event::RuleRemoved { id, resource_id },
+                    IntervalClass::Holds => Event::HoldReleased { id, resource_id },
+                    IntervalClass::Bookings => Event::BookingCancelled { id, resource_id, reason: None, at: now },
+                });
+            }
+        }
+        self.persist_and_apply_batch(&mut guards, events).await?;
+        Ok(ids)
+    }
+
+    /// Create a resource, or update it in place if `id` exists. The parent
+    /// of an existing resource cannot change.
+    pub async fn replace_resource(
+        &self,
+        id: Ulid,
+        parent_id: Option<Ulid>,
+        name: Option<String>,
+        capacity: u32,
+        buffer_after: Option<Ms>,
+        settings: ResourceSettings,
+    ) -> Result<(), EngineError> {
+        let Some(existing) = self.get_resource(&id) else {
+            return self
+                .create_resource_with_settings(id, parent_id, name, capacity, buffer_after, settings)
+                .await;
+        };
+        if existing.read().await.parent_id != parent_id {
+            return Err(EngineError::AlreadyExists(id));
+        }
+        self.update_resource_with_settings(id, name, capacity, buffer_after, settings).await
+    }
+
+    /// Update name, capacity and buffer, keeping the resource's current settings.
     pub async fn update_resource(
         &self,
         id: Ulid,
         name: Option<String>,
         capacity: u32,
         buffer_after: Option<Ms>,
+    ) -> Result<(), EngineError> {
+        self.update_resource_inner(id, name, capacity, buffer_after, SettingsPatch::default()).await
+    }
+
+    /// Update a resource, replacing every setting but its components.
+    pub async fn update_resource_with_settings(
+        &self,
+        id: Ulid,
+        name: Option<String>,
+        capacity: u32,
+        buffer_after: Option<Ms>,
+        settings: ResourceSettings,
+    ) -> Result<(), EngineError> {
+        self.update_resource_inner(id, name, capacity, buffer_after, settings.into()).await
+    }
+
+    /// Update a resource, changing only the settings `patch` assigns.
+    pub async fn update_resource_with_patch(
+        &self,
+        id: Ulid,
+        name: Option<String>,
+        capacity: u32,
+        buffer_after: Option<Ms>,
+        patch: SettingsPatch,
+    ) -> Result<(), EngineError> {
+        self.update_resource_inner(id, name, capacity, buffer_after, patch).await
+    }
+
+    async fn update_resource_inner(
+        &self,
+        id: Ulid,
+        name: Option<String>,
+        capacity: u32,
+        buffer_after: Option<Ms>,
+        patch: SettingsPatch,
     ) -> Result<(), EngineError> {
         if let Some(ref n) = name
             && n.len() > MAX_NAME_LEN {
                 return Err(EngineError::LimitExceeded("resource name too long"));
             }
+        validate_buffer(buffer_after)?;
         let rs = self
             .get_resource(&id)
             .ok_or(EngineError::NotFound(id))?;
         let mut guard = rs.write().await;
 
-        let event = Event::ResourceUpdated { id, name, capacity, buffer_after };
-        self.persist_and_apply(id, &mut guard, &event).await
+        let mut settings = guard.settings.clone();
+        patch.apply(&mut settings);
+        validate_buffer(settings.buffer_before)?;
+        validate_policy(&settings.policy)?;
+        validate_granularity(&settings)?;
+        validate_quota(&settings.quota)?;
+        // A new position is claimed before the WAL write, as on create;
+        // applying the event releases the old one.
+        let claimed = match (guard.parent_id, settings.position) {
+            (Some(pid), Some(position)) if guard.settings.position != Some(position) => {
+                self.store
+                    .claim_position(pid, position, id)
+                    .map_err(EngineError::AlreadyExists)?;
+                Some((pid, position))
+            }
+            _ => None,
+        };
+        let event = Event::ResourceUpdated { id, name, capacity, buffer_after, settings };
+        let result = self.persist_and_apply(id, &mut guard, &event).await;
+        if result.is_err()
+            && let Some((pid, position)) = claimed {
+                self.store.release_position(&pid, position, &id);
+            }
+        result
     }
 
     pub async fn update_rule(
@@ -303,10 +1042,30 @@ impl Engine {
         id: Ulid,
         span: Span,
         blocking: bool,
+    ) -> Result<Ulid, EngineError> {
+        self.update_rule_inner(id, span, blocking, None).await
+    }
+
+    /// Update a rule to override the resource's capacity within `span`.
+    pub async fn update_capacity_rule(
+        &self,
+        id: Ulid,
+        span: Span,
+        capacity: u32,
+    ) -> Result<Ulid, EngineError> {
+        self.update_rule_inner(id, span, false, Some(capacity)).await
+    }
+
+    async fn update_rule_inner(
+        &self,
+        id: Ulid,
+        span: Span,
+        blocking: bool,
+        capacity: Option<u32>,
     ) -> Result<Ulid, EngineError> {
         validate_span(&span)?;
         let (resource_id, mut guard) = self.resolve_entity_write(&id).await?;
-        let event = Event::RuleUpdated { id, resource_id, span, blocking };
+        let event = Event::RuleUpdated { id, resource_id, span, blocking, capacity };
         self.persist_and_apply(resource_id, &mut guard, &event).await?;
         Ok(resource_id)
     }
@@ -317,7 +1076,7 @@ impl Engine {
             if let Some(rs) = self.store.get_resource(&rid)
                 && let Ok(guard) = rs.try_read() {
                     for interval in &guard.intervals {
-                        if let IntervalKind::Hold { expires_at } = interval.kind
+                        if let IntervalKind::Hold { expires_at, .. } = interval.kind
                             && expires_at <= now {
                                 expired.push((interval.id, guard.id));
                             }
@@ -345,15 +1104,26 @@ impl Engine {
             };
 
             let mut removed_ids = Vec::new();
+            guard.cancelled.retain(|c| {
+                let expired = c.interval.span.end < cutoff;
+                if expired {
+                    self.store.unindex_owner(&c.interval);
+                    removed_ids.push(c.interval.id);
+                }
+                !expired
+            });
             guard.intervals.retain(|interval| {
                 let dominated = match &interval.kind {
                     IntervalKind::Booking { .. } => interval.span.end < cutoff,
-                    IntervalKind::Hold { expires_at } => {
+                    IntervalKind::Hold { expires_at, .. } => {
                         *expires_at <= now && interval.span.end < cutoff
                     }
-                    IntervalKind::NonBlocking | IntervalKind::Blocking => false,
+                    IntervalKind::NonBlocking
+                    | IntervalKind::Blocking
+                    | IntervalKind::Capacity { .. } => false,
                 };
                 if dominated {
+                    self.store.unindex_owner(interval);
                     removed_ids.push(interval.id);
                 }
                 !dominated
@@ -373,6 +1143,40 @@ impl Engine {
         let mut events = Vec::new();
         let mut visited = HashSet::new();
 
+        // Calendars first: resources reference them via CalendarAttached.
+        // One created since is emitted by the resource attaching it.
+        fn emit_calendar(
+            id: Ulid,
+            store: &super::InMemoryStore,
+            events: &mut Vec<Event>,
+            visited: &mut HashSet<Ulid>,
+        ) {
+            if !visited.insert(id) {
+                return;
+            }
+            let cal = match store.get_calendar(&id) {
+                Some(cal) => cal,
+                None => return,
+            };
+            let guard = cal.try_read().expect("compact: uncontended read");
+            events.push(Event::CalendarCreated {
+                id: guard.id,
+                name: guard.name.clone(),
+            });
+            for interval in &guard.intervals {
+                events.push(Event::CalendarRuleAdded {
+                    id: interval.id,
+                    calendar_id: guard.id,
+                    span: interval.span,
+                    blocking: matches!(interval.kind, IntervalKind::Blocking),
+                });
+            }
+        }
+
+        for cid in self.store.calendar_ids() {
+            emit_calendar(cid, &self.store, &mut events, &mut visited);
+        }
+
         fn emit_resource(
             id: Ulid,
             store: &super::InMemoryStore,
@@ -398,8 +1202,18 @@ impl Engine {
                 name: guard.name.clone(),
                 capacity: guard.capacity,
                 buffer_after: guard.buffer_after,
+                settings: guard.settings.clone(),
             });
 
+            // Under the resource lock, as in `attach_calendar`.
+            for calendar_id in &guard.calendar_ids {
+                emit_calendar(*calendar_id, store, events, visited);
+                events.push(Event::CalendarAttached {
+                    calendar_id: *calendar_id,
+                    resource_id: guard.id,
+                });
+            }
+
             for interval in &guard.intervals {
                 match &interval.kind {
                     IntervalKind::NonBlocking => events.push(Event::RuleAdded {
@@ -407,25 +1221,63 @@ impl Engine {
                         resource_id: guard.id,
                         span: interval.span,
                         blocking: false,
+                        capacity: None,
                     }),
                     IntervalKind::Blocking => events.push(Event::RuleAdded {
                         id: interval.id,
                         resource_id: guard.id,
                         span: interval.span,
                         blocking: true,
+                        capacity: None,
                     }),
-                    IntervalKind::Hold { expires_at } => events.push(Event::HoldPlaced {
+                    IntervalKind::Capacity { capacity } => events.push(Event::RuleAdded {
                         id: interval.id,
                         resource_id: guard.id,
                         span: interval.span,
-                        expires_at: *expires_at,
+                        blocking: false,
+                        capacity: Some(*capacity),
                     }),
-                    IntervalKind::Booking { label } => events.push(Event::BookingConfirmed {
+                    IntervalKind::Hold { expires_at, options } => events.push(Event::HoldPlaced {
                         id: interval.id,
                         resource_id: guard.id,
                         span: interval.span,
-                        label: label.clone(),
+                        expires_at: *expires_at,
+                        options: options.clone(),
                     }),
+                    IntervalKind::Booking { label, options, status } => {
+                        events.push(Event::BookingConfirmed {
+                            id: interval.id,
+                            resource_id: guard.id,
+                            span: interval.span,
+                            label: label.clone(),
+                            options: options.clone(),
+                        });
+                        if *status != BookingStatus::Confirmed {
+                            events.push(Event::BookingStatusChanged {
+                                id: interval.id,
+                                resource_id: guard.id,
+                                status: *status,
+                            });
+                        }
+                    }
+                }
+            }
+
+            for cancelled in &guard.cancelled {
+                if let IntervalKind::Booking { label, options, .. } = &cancelled.interval.kind {
+                    events.push(Event::BookingConfirmed {
+                        id: cancelled.interval.id,
+                        resource_id: guard.id,
+                        span: cancelled.interval.span,
+                        label: label.clone(),
+                        options: options.clone(),
+                    });
+                    events.push(Event::BookingCancelled {
+                        id: cancelled.interval.id,
+                        resource_id: guard.id,
+                        reason: cancelled.reason.clone(),
+                        at: cancelled.cancelled_at,
+                    });
                 }
             }
         }
@@ -435,6 +1287,16 @@ impl Engine {
             emit_resource(id, &self.store, &mut events, &mut visited);
         }
 
+        // Groups last: they reference resources.
+        for group in self.store.groups() {
+            events.push(Event::GroupCreated {
+                id: group.id,
+                name: group.name.clone(),
+                limit: group.limit,
+                members: group.members.clone(),
+            });
+        }
+
         let (tx, rx) = oneshot::channel();
         self.wal_tx
             .send(WalCommand::Compact { events, response: tx })
@@ -458,3 +1320,73 @@ impl Engine {
         rx.await.unwrap_or(0)
     }
 }
+
+fn is_active_booking(rs: &ResourceState, id: Ulid) -> bool {
+    rs.intervals
+        .iter()
+        .any(|i| i.id == id && matches!(i.kind, IntervalKind::Booking { .. }))
+}
+
+impl Engine {
+    /// Write-lock `resource_id` with everything an allocation on it must
+    /// check, and resolve its booking policy under those locks. Returns the
+    /// policy, the locks, and the resource's position among the guards.
+    async fn lock_allocation_target(
+        &self,
+        resource_id: Ulid,
+    ) -> Result<(BookingPolicy, AllocationLocks, usize), EngineError> {
+        let targets = self.allocation_targets(&[resource_id]).await?;
+        let locked = self.lock_for_allocation(&targets).await?;
+        let policy = locked.policy(resource_id)?;
+        let target = locked
+            .guards
+            .binary_search_by_key(&resource_id, |g| g.id)
+            .expect("allocation target is locked");
+        Ok((policy, locked, target))
+    }
+
+    /// Where an insert's `id` already lives on `rs`, for retries and upserts.
+    /// `Ok(None)` if the id is unused; `AlreadyExists` if it belongs to
+    /// another resource or is not an entity of the kind `is_kind` accepts.
+    fn existing_position(
+        &self,
+        rs: &ResourceState,
+        id: Ulid,
+        is_kind: impl Fn(&Interval) -> bool,
+    ) -> Result<Option<usize>, EngineError> {
+        match self.store.get_resource_for_entity(&id) {
+            None => Ok(None),
+            Some(rid) if rid == rs.id => rs
+                .intervals
+                .iter()
+                .position(|i| i.id == id && is_kind(i))
+                .map(Some)
+                .ok_or(EngineError::AlreadyExists(id)),
+            Some(_) => Err(EngineError::AlreadyExists(id)),
+        }
+    }
+}
+
+/// Run `check` with the interval at `existing` on `locked[target]` (the one
+/// being replaced, if any) taken out, so an upsert never conflicts with itself.
+fn check_replacing(
+    locked: &mut [OwnedRwLockWriteGuard<ResourceState>],
+    target: usize,
+    existing: Option<usize>,
+    check: impl FnOnce(&[OwnedRwLockWriteGuard<ResourceState>]) -> Result<(), EngineError>,
+) -> Result<(), EngineError> {
+    let old = existing.map(|pos| locked[target].intervals.remove(pos));
+    let result = check(locked);
+    if let Some(old) = old {
+        locked[target].insert_interval(old);
+    }
+    result
+}
+
+/// Booking payload equality for retries; the booking's status is not part of it.
+fn is_same_booking(interval: &Interval, span: &Span, label: &Option<String>, options: &AllocOptions) -> bool {
+    match &interval.kind {
+        IntervalKind::Booking { label: l, options: o, .. } => interval.span == *span && l == label && o == options,
+        _ => false,
+    }
+}
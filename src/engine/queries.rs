@@ -1,19 +1,25 @@
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 use ulid::Ulid;
 
 use crate::limits::*;
 use crate::model::*;
 
-use super::availability::availability;
-use super::conflict::now_ms;
+use super::availability::{availability, inherit_rules, intersect_intervals, subtract_intervals};
+use super::conflict::{apply_policy, now_ms, snap_to_grid};
+use super::composites::AllocationLocks;
 use super::{Engine, EngineError};
 
+/// A resource's inheritance mode with its own `(non_blocking, blocking)` rules.
+type RuleLevel = (InheritanceMode, Vec<Span>, Vec<Span>);
+
 impl Engine {
-    /// Walk up from a resource collecting inherited rules from ancestors.
+    /// Walk up from a resource collecting the effective rules of its parent.
+    /// Each ancestor's attached calendars count as part of that ancestor's rules.
     ///
-    /// Non-blocking: OVERRIDE — first ancestor with non-blocking rules wins.
-    /// Blocking: ACCUMULATE — all ancestors' blocking rules are collected.
+    /// Every ancestor combines its own rules with its parent's according to its
+    /// `InheritanceMode`, so the fold runs from the topmost ancestor down. An
+    /// isolated ancestor ends the walk; an isolated resource inherits nothing.
     ///
     /// Returns `(inherited_non_blocking, inherited_blocking)` clamped to query.
     pub(super) async fn collect_inherited_rules(
@@ -21,9 +27,12 @@ impl Engine {
         resource: &ResourceState,
         query: &Span,
     ) -> Result<(Vec<Span>, Vec<Span>), EngineError> {
-        let mut inherited_non_blocking: Vec<Span> = Vec::new();
-        let mut inherited_blocking: Vec<Span> = Vec::new();
-        let mut found_non_blocking = false;
+        if resource.settings.inheritance == InheritanceMode::Isolate {
+            return Ok((Vec::new(), Vec::new()));
+        }
+
+        // Own rules of each ancestor, nearest first.
+        let mut levels: Vec<RuleLevel> = Vec::new();
 
         let mut current_parent_id = resource.parent_id;
         let mut visited = HashSet::new();
@@ -42,44 +51,135 @@ impl Engine {
                 .get_resource(&pid)
                 .ok_or(EngineError::NotFound(pid))?;
             let parent_guard = parent_rs.read().await;
-
-            for interval in parent_guard.overlapping(query) {
-                match &interval.kind {
-                    IntervalKind::Blocking => {
-                        inherited_blocking.push(Span::new(
-                            interval.span.start.max(query.start),
-                            interval.span.end.min(query.end),
-                        ));
-                    }
-                    IntervalKind::NonBlocking if !found_non_blocking => {
-                        inherited_non_blocking.push(Span::new(
-                            interval.span.start.max(query.start),
-                            interval.span.end.min(query.end),
-                        ));
-                    }
-                    _ => {}
-                }
+            let level = self.rule_level(&parent_guard, query).await;
+            levels.push(level);
+            if parent_guard.settings.inheritance == InheritanceMode::Isolate {
+                break;
             }
+            current_parent_id = parent_guard.parent_id;
+        }
+
+        Ok(fold_levels(levels))
+    }
 
-            if !found_non_blocking && !inherited_non_blocking.is_empty() {
-                found_non_blocking = true;
+    /// A resource's mode and own rules over `query`, calendars included.
+    async fn rule_level(&self, rs: &ResourceState, query: &Span) -> RuleLevel {
+        let calendar_rules = self.collect_calendar_rules(&rs.calendar_ids, query).await;
+        let mut non_blocking = Vec::new();
+        let mut blocking = Vec::new();
+        for interval in rs.overlapping(query).chain(&calendar_rules) {
+            let clamped = Span::new(
+                interval.span.start.max(query.start),
+                interval.span.end.min(query.end),
+            );
+            match &interval.kind {
+                IntervalKind::NonBlocking => non_blocking.push(clamped),
+                IntervalKind::Blocking => blocking.push(clamped),
+                _ => {}
             }
+        }
+        (rs.settings.inheritance, non_blocking, blocking)
+    }
 
-            current_parent_id = parent_guard.parent_id;
+    /// Rule levels of `resource_id` and the ancestors it inherits from,
+    /// nearest first, read from `locks`.
+    async fn locked_rule_levels(
+        &self,
+        locks: &AllocationLocks,
+        resource_id: Ulid,
+        query: &Span,
+    ) -> Result<Vec<RuleLevel>, EngineError> {
+        let mut levels = Vec::new();
+        let mut current = Some(resource_id);
+        while let Some(id) = current {
+            let rs = locks.get(id).ok_or(EngineError::NotFound(id))?;
+            levels.push(self.rule_level(rs, query).await);
+            if rs.settings.inheritance == InheritanceMode::Isolate {
+                break;
+            }
+            current = rs.parent_id;
         }
+        Ok(levels)
+    }
 
-        inherited_non_blocking.sort_by_key(|s| s.start);
-        inherited_blocking.sort_by_key(|s| s.start);
+    /// Holds and bookings must lie in open time, by the same rules and
+    /// inheritance modes as availability, on the resource and on each of
+    /// its components. A resource without any rules in effect is open
+    /// throughout.
+    pub(super) async fn check_open(
+        &self,
+        locks: &AllocationLocks,
+        resource_id: Ulid,
+        span: &Span,
+    ) -> Result<(), EngineError> {
+        let components = locks.get(resource_id).map(|rs| rs.settings.components.clone()).unwrap_or_default();
+        for subject in std::iter::once(resource_id).chain(components) {
+            let (open, blocked) = fold_levels(self.locked_rule_levels(locks, subject, span).await?);
+            let closed = subtract_intervals(&[*span], &subtract_intervals(&open, &blocked));
+            if closed.is_empty() {
+                continue;
+            }
+            let everywhere = Span::new(MIN_VALID_TIMESTAMP_MS, MAX_VALID_TIMESTAMP_MS);
+            let governed = self
+                .locked_rule_levels(locks, subject, &everywhere)
+                .await?
+                .iter()
+                .any(|(_, non_blocking, blocking)| !non_blocking.is_empty() || !blocking.is_empty());
+            if governed {
+                return Err(EngineError::NotOpen { resource_id: subject, closed });
+            }
+        }
+        Ok(())
+    }
 
-        Ok((inherited_non_blocking, inherited_blocking))
+    /// Booking policy in force on a resource: its own fields, with unset ones
+    /// taken from the nearest ancestor that sets them.
+    pub(super) async fn effective_policy(&self, resource: &ResourceState) -> Result<BookingPolicy, EngineError> {
+        let mut policy = resource.settings.policy;
+        let mut current_parent_id = resource.parent_id;
+        let mut depth = 0usize;
+        while let Some(pid) = current_parent_id {
+            depth += 1;
+            if depth > MAX_HIERARCHY_DEPTH {
+                return Err(EngineError::LimitExceeded("hierarchy too deep"));
+            }
+            let parent_rs = self
+                .get_resource(&pid)
+                .ok_or(EngineError::NotFound(pid))?;
+            let parent_guard = parent_rs.read().await;
+            policy = policy.inherit(&parent_guard.settings.policy);
+            current_parent_id = parent_guard.parent_id;
+        }
+        Ok(policy)
     }
 
+    /// Free time on a resource, restricted to what its booking policy allows.
     pub async fn compute_availability(
         &self,
         resource_id: Ulid,
         query_start: Ms,
         query_end: Ms,
         min_duration_ms: Option<Ms>,
+    ) -> Result<Vec<Span>, EngineError> {
+        let mut free = self
+            .compute_availability_inner(resource_id, query_start, query_end, true)
+            .await?;
+
+        if let Some(min_dur) = min_duration_ms {
+            free.retain(|span| span.duration_ms() >= min_dur);
+        }
+
+        Ok(free)
+    }
+
+    /// Free time on a resource. With `with_policy` unset, the booking policy
+    /// is ignored (e.g. when checking rule coverage against a parent).
+    pub(super) async fn compute_availability_inner(
+        &self,
+        resource_id: Ulid,
+        query_start: Ms,
+        query_end: Ms,
+        with_policy: bool,
     ) -> Result<Vec<Span>, EngineError> {
         if query_end - query_start > MAX_QUERY_WINDOW_MS {
             return Err(EngineError::LimitExceeded("query window too wide"));
@@ -88,28 +188,86 @@ impl Engine {
             Some(rs) => rs,
             None => return Ok(vec![]),
         };
+        let query = Span::new(query_start, query_end);
+
+        // A composite is free only where it is itself and all of its
+        // components are. Its own policy applies to the combined time.
+        let components = rs.read().await.settings.components.clone();
+        if !components.is_empty() {
+            let mut free = self.resource_availability(resource_id, &query, false).await?;
+            for cid in components {
+                let spans = self.resource_availability(cid, &query, with_policy).await?;
+                free = intersect_intervals(&free, &spans);
+            }
+            if !with_policy {
+                return Ok(free);
+            }
+            let guard = rs.read().await;
+            return self.restrict_by_policy(&guard, free, now_ms()).await;
+        }
+        self.resource_availability(resource_id, &query, with_policy).await
+    }
+
+    /// `compute_availability_inner` for a plain resource, counting
+    /// allocations on composites over it as its own.
+    async fn resource_availability(
+        &self,
+        resource_id: Ulid,
+        query: &Span,
+        with_policy: bool,
+    ) -> Result<Vec<Span>, EngineError> {
+        let rs = match self.get_resource(&resource_id) {
+            Some(rs) => rs,
+            None => return Ok(vec![]),
+        };
+        let query = *query;
+        // Read before taking the resource's own lock: writers lock composites
+        // and components together in ULID order.
+        let alloc_window = Span::new((query.start - MAX_BUFFER_MS).max(0), query.end + MAX_BUFFER_MS);
+        let shared = self.shared_allocations(resource_id, &alloc_window).await;
+        let now = now_ms();
+        let saturated = self.saturated_group_spans(resource_id, &alloc_window, now).await;
         let guard = rs.read().await;
 
-        let query = Span::new(query_start, query_end);
+        // Like calendar rules, composite allocations count as the resource's own.
+        let mut calendar_rules = self.collect_calendar_rules(&guard.calendar_ids, &query).await;
+        calendar_rules.extend(shared);
         let (inherited_non_blocking, inherited_blocking) =
             self.collect_inherited_rules(&guard, &query).await?;
 
-        let now = now_ms();
-        let mut free = availability(
+        let free = availability(
             &guard,
             &query,
+            &calendar_rules,
             &inherited_non_blocking,
             &inherited_blocking,
             now,
         );
-
-        if let Some(min_dur) = min_duration_ms {
-            free.retain(|span| span.duration_ms() >= min_dur);
+        let free = subtract_intervals(&free, &saturated);
+        if !with_policy {
+            return Ok(free);
         }
+        self.restrict_by_policy(&guard, free, now).await
+    }
 
+    /// Free time cut down to what the resource's booking policy and
+    /// granularity allow.
+    async fn restrict_by_policy(&self, rs: &ResourceState, free: Vec<Span>, now: Ms) -> Result<Vec<Span>, EngineError> {
+        let policy = self.effective_policy(rs).await?;
+        let free = if policy.is_empty() { free } else { apply_policy(&policy, free, now) };
+        let mut free = snap_to_grid(&rs.settings, free);
+        if let Some(min) = policy.min_duration {
+            free.retain(|span| span.duration_ms() >= min);
+        }
         Ok(free)
     }
 
+    /// Whether `resource_id` is available for the whole of `span`.
+    pub(super) async fn is_free_for(&self, resource_id: Ulid, span: &Span) -> Result<bool, EngineError> {
+        let free = self.compute_availability(resource_id, span.start, span.end, None).await?;
+        Ok(free.iter().any(|s| s.start <= span.start && s.end >= span.end))
+    }
+
     /// Compute combined availability across multiple independent resources.
     pub async fn compute_multi_availability(
         &self,
@@ -171,13 +329,7 @@ impl Engine {
         for rid in self.store.resource_ids() {
             if let Some(rs) = self.store.get_resource(&rid)
                 && let Ok(guard) = rs.try_read() {
-                    result.push(ResourceInfo {
-                        id: guard.id,
-                        parent_id: guard.parent_id,
-                        name: guard.name.clone(),
-                        capacity: guard.capacity,
-                        buffer_after: guard.buffer_after,
-                    });
+                    result.push(resource_info(&guard));
                 }
         }
         result
@@ -192,23 +344,7 @@ impl Engine {
         Ok(guard
             .intervals
             .iter()
-            .filter_map(|i| match &i.kind {
-                IntervalKind::NonBlocking => Some(RuleInfo {
-                    id: i.id,
-                    resource_id,
-                    start: i.span.start,
-                    end: i.span.end,
-                    blocking: false,
-                }),
-                IntervalKind::Blocking => Some(RuleInfo {
-                    id: i.id,
-                    resource_id,
-                    start: i.span.start,
-                    end: i.span.end,
-                    blocking: true,
-                }),
-                _ => None,
-            })
+            .filter_map(|i| rule_info(resource_id, i))
             .collect())
     }
 
@@ -221,16 +357,7 @@ impl Engine {
         Ok(guard
             .intervals
             .iter()
-            .filter_map(|i| match &i.kind {
-                IntervalKind::Booking { label } => Some(BookingInfo {
-                    id: i.id,
-                    resource_id,
-                    start: i.span.start,
-                    end: i.span.end,
-                    label: label.clone(),
-                }),
-                _ => None,
-            })
+            .filter_map(|i| booking_info(resource_id, i, None))
             .collect())
     }
 
@@ -243,16 +370,187 @@ impl Engine {
         Ok(guard
             .intervals
             .iter()
-            .filter_map(|i| match &i.kind {
-                IntervalKind::Hold { expires_at } => Some(HoldInfo {
-                    id: i.id,
-                    resource_id,
-                    start: i.span.start,
-                    end: i.span.end,
-                    expires_at: *expires_at,
-                }),
-                _ => None,
-            })
+            .filter_map(|i| hold_info(resource_id, i))
             .collect())
     }
+
+    /// Live (not cancelled) bookings of `owner` across every resource of the
+    /// tenant, by start time.
+    pub async fn get_bookings_by_owner(&self, owner: &str) -> Vec<BookingInfo> {
+        let ids = self.store.get_owner_entities(owner);
+        let mut result = self.collect_entities(&ids, |rid, i, _| booking_info(rid, i, None)).await;
+        result.retain(|b| b.status != BookingStatus::Cancelled);
+        result.sort_by_key(|b| (b.start, b.id));
+        result
+    }
+
+    /// Holds of `owner` across every resource of the tenant, by start time.
+    pub async fn get_holds_by_owner(&self, owner: &str) -> Vec<HoldInfo> {
+        let ids = self.store.get_owner_entities(owner);
+        let mut result = self.collect_entities(&ids, |rid, i, _| hold_info(rid, i)).await;
+        result.sort_by_key(|h| (h.start, h.id));
+        result
+    }
+
+    /// Bookings in `status` on one resource, or across the tenant when
+    /// `resource_id` is `None`, by start time.
+    pub async fn get_bookings_by_status(&self, status: BookingStatus, resource_id: Option<Ulid>) -> Vec<BookingInfo> {
+        let resource_ids = match resource_id {
+            Some(rid) => vec![rid],
+            None => self.store.resource_ids(),
+        };
+        let mut result = Vec::new();
+        for rid in resource_ids {
+            let Some(rs) = self.get_resource(&rid) else { continue };
+            let guard = rs.read().await;
+            if status == BookingStatus::Cancelled {
+                result.extend(guard.cancelled.iter().filter_map(|c| booking_info(rid, &c.interval, Some(c))));
+            } else {
+                result.extend(
+                    guard
+                        .intervals
+                        .iter()
+                        .filter_map(|i| booking_info(rid, i, None))
+                        .filter(|b| b.status == status),
+                );
+            }
+        }
+        result.sort_by_key(|b| (b.start, b.id));
+        result
+    }
+
+    /// Resources by id, in the order given. Unknown ids are skipped.
+    pub async fn get_resources_by_id(&self, ids: &[Ulid]) -> Vec<ResourceInfo> {
+        let mut result = Vec::with_capacity(ids.len());
+        for id in ids {
+            if let Some(rs) = self.store.get_resource(id) {
+                result.push(resource_info(&*rs.read().await));
+            }
+        }
+        result
+    }
+
+    /// Bookings by id, cancelled ones included, in the order given. Unknown
+    /// ids are skipped.
+    pub async fn get_bookings_by_id(&self, ids: &[Ulid]) -> Vec<BookingInfo> {
+        self.collect_entities(ids, booking_info).await
+    }
+
+    /// Holds by id, in the order given. Unknown ids are skipped.
+    pub async fn get_holds_by_id(&self, ids: &[Ulid]) -> Vec<HoldInfo> {
+        self.collect_entities(ids, |rid, i, _| hold_info(rid, i)).await
+    }
+
+    /// Rules by id, in the order given. Unknown ids are skipped.
+    pub async fn get_rules_by_id(&self, ids: &[Ulid]) -> Vec<RuleInfo> {
+        self.collect_entities(ids, |rid, i, _| rule_info(rid, i)).await
+    }
+
+    /// Resolve entity ids through the entity index and convert each matching
+    /// interval with `f`, preserving the order of `ids`. Cancelled bookings
+    /// are passed with their cancellation. Each resource is read-locked once,
+    /// in ULID order.
+    async fn collect_entities<T>(
+        &self,
+        ids: &[Ulid],
+        f: impl Fn(Ulid, &Interval, Option<&CancelledBooking>) -> Option<T>,
+    ) -> Vec<T> {
+        let mut by_resource: BTreeMap<Ulid, HashSet<Ulid>> = BTreeMap::new();
+        for id in ids {
+            if let Some(rid) = self.store.get_resource_for_entity(id) {
+                by_resource.entry(rid).or_default().insert(*id);
+            }
+        }
+
+        let mut found: HashMap<Ulid, T> = HashMap::new();
+        for (rid, wanted) in by_resource {
+            let Some(rs) = self.get_resource(&rid) else { continue };
+            let guard = rs.read().await;
+            let live = guard.intervals.iter().map(|i| (i, None));
+            let cancelled = guard.cancelled.iter().map(|c| (&c.interval, Some(c)));
+            for (interval, cancellation) in live.chain(cancelled).filter(|(i, _)| wanted.contains(&i.id)) {
+                if let Some(item) = f(rid, interval, cancellation) {
+                    found.insert(interval.id, item);
+                }
+            }
+        }
+        ids.iter().filter_map(|id| found.remove(id)).collect()
+    }
+}
+
+fn resource_info(rs: &ResourceState) -> ResourceInfo {
+    ResourceInfo {
+        id: rs.id,
+        parent_id: rs.parent_id,
+        name: rs.name.clone(),
+        capacity: rs.capacity,
+        buffer_after: rs.buffer_after,
+        settings: rs.settings.clone(),
+    }
+}
+
+fn rule_info(resource_id: Ulid, i: &Interval) -> Option<RuleInfo> {
+    let (blocking, capacity) = match &i.kind {
+        IntervalKind::NonBlocking => (false, None),
+        IntervalKind::Blocking => (true, None),
+        IntervalKind::Capacity { capacity } => (false, Some(*capacity)),
+        _ => return None,
+    };
+    Some(RuleInfo {
+        id: i.id,
+        resource_id,
+        start: i.span.start,
+        end: i.span.end,
+        blocking,
+        capacity,
+    })
+}
+
+fn booking_info(resource_id: Ulid, i: &Interval, cancellation: Option<&CancelledBooking>) -> Option<BookingInfo> {
+    match &i.kind {
+        IntervalKind::Booking { label, options, status } => Some(BookingInfo {
+            id: i.id,
+            resource_id,
+            start: i.span.start,
+            end: i.span.end,
+            label: label.clone(),
+            buffer_before: options.buffer_before,
+            buffer_after: options.buffer_after,
+            owner: options.owner.clone(),
+            metadata: options.metadata.clone(),
+            status: *status,
+            cancel_reason: cancellation.and_then(|c| c.reason.clone()),
+            cancelled_at: cancellation.map(|c| c.cancelled_at),
+        }),
+        _ => None,
+    }
+}
+
+fn hold_info(resource_id: Ulid, i: &Interval) -> Option<HoldInfo> {
+    match &i.kind {
+        IntervalKind::Hold { expires_at, options } => Some(HoldInfo {
+            id: i.id,
+            resource_id,
+            start: i.span.start,
+            end: i.span.end,
+            expires_at: *expires_at,
+            buffer_before: options.buffer_before,
+            buffer_after: options.buffer_after,
+            owner: options.owner.clone(),
+            metadata: options.metadata.clone(),
+        }),
+        _ => None,
+    }
+}
+
+/// Effective `(non_blocking, blocking)` rules of the first of `levels`,
+/// nearest first, folding each level's own rules onto its parent's from the
+/// top down.
+fn fold_levels(levels: Vec<RuleLevel>) -> (Vec<Span>, Vec<Span>) {
+    let mut non_blocking: Vec<Span> = Vec::new();
+    let mut blocking: Vec<Span> = Vec::new();
+    for (mode, own_non_blocking, own_blocking) in levels.into_iter().rev() {
+        (non_blocking, blocking) = inherit_rules(mode, own_non_blocking, own_blocking, &non_blocking, &blocking);
+    }
+    (non_blocking, blocking)
 }
@@ -0,0 +1,289 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::conflict::{now_ms, validate_span};
+use super::{Engine, EngineError};
+
+/// What booking one resource takes up: the plain resources it occupies (a
+/// composite's components), how many of those count against each exclusion
+/// group, and when it is free.
+struct Candidate {
+    occupies: Vec<Ulid>,
+    groups: Vec<(Ulid, u32)>,
+    free: Vec<Span>,
+}
+
+/// The resources named by a set of requirements and the exclusion groups
+/// they count against, read once for a query window.
+struct Pool {
+    candidates: HashMap<Ulid, Candidate>,
+    /// Group → its limit, and the footprints of its members' allocations.
+    groups: HashMap<Ulid, (u32, Vec<Span>)>,
+}
+
+impl Engine {
+    /// Times within `[query_start, query_end)` when every requirement can be
+    /// met at once, each by its own resources. A span is bookable with
+    /// `book_requirements` if one choice of resources serves all of it;
+    /// where the times shown need different choices one after the other,
+    /// book the parts separately.
+    pub async fn compute_requirement_availability(
+        &self,
+        requirements: &[Requirement],
+        query_start: Ms,
+        query_end: Ms,
+        min_duration_ms: Option<Ms>,
+    ) -> Result<Vec<Span>, EngineError> {
+        self.validate_requirements(requirements)?;
+        if query_end - query_start > MAX_QUERY_WINDOW_MS {
+            return Err(EngineError::LimitExceeded("query window too wide"));
+        }
+        if query_end <= query_start {
+            return Ok(Vec::new());
+        }
+        let query = Span::new(query_start, query_end);
+        let pool = self.requirement_pool(requirements, &query).await?;
+
+        // Nothing changes within a segment, so one search decides each.
+        let mut free: Vec<Span> = Vec::new();
+        for segment in pool.segments(&query) {
+            match pool.assign(requirements, &segment) {
+                Ok(_) => match free.last_mut() {
+                    Some(last) if last.end == segment.start => last.end = segment.end,
+                    _ => free.push(segment),
+                },
+                Err(EngineError::RequirementNotMet(_)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        if let Some(min) = min_duration_ms {
+            free.retain(|span| span.duration_ms() >= min);
+        }
+        Ok(free)
+    }
+
+    /// Book `span` on concrete resources meeting every requirement, as one
+    /// atomic batch. Requirements are filled in order, each preferring its
+    /// resources in the order given, and an earlier choice is revisited when
+    /// a later requirement cannot otherwise be met: a composite and its
+    /// components are never both chosen, and an exclusion group never goes
+    /// over its limit. Returns `(booking id, resource id)` pairs,
+    /// requirement by requirement.
+    pub async fn book_requirements(
+        &self,
+        requirements: &[Requirement],
+        span: Span,
+        label: Option<String>,
+    ) -> Result<Vec<(Ulid, Ulid)>, EngineError> {
+        validate_span(&span)?;
+        self.validate_requirements(requirements)?;
+        let pool = self.requirement_pool(requirements, &span).await?;
+        let bookings: Vec<_> = pool
+            .assign(requirements, &span)?
+            .into_iter()
+            .map(|rid| (Ulid::new(), rid, span, label.clone()))
+            .collect();
+        let chosen = bookings.iter().map(|(id, rid, ..)| (*id, *rid)).collect();
+        self.batch_confirm_bookings(bookings).await?;
+        Ok(chosen)
+    }
+
+    /// Each requirement needs at least one resource; no resource may serve
+    /// two requirements.
+    fn validate_requirements(&self, requirements: &[Requirement]) -> Result<(), EngineError> {
+        if requirements.is_empty() || requirements.len() > MAX_REQUIREMENTS {
+            return Err(EngineError::LimitExceeded("number of requirements out of range"));
+        }
+        let total: usize = requirements.iter().map(|r| r.resources.len()).sum();
+        if total > MAX_IN_CLAUSE_IDS {
+            return Err(EngineError::LimitExceeded("too many resource IDs"));
+        }
+        let mut seen = HashSet::new();
+        for req in requirements {
+            if req.count == 0 {
+                return Err(EngineError::InvalidArgument("requirement count must be at least 1"));
+            }
+            for &rid in &req.resources {
+                if !seen.insert(rid) {
+                    return Err(EngineError::InvalidArgument("resource listed in more than one requirement"));
+                }
+                if !self.store.contains_resource(&rid) {
+                    return Err(EngineError::NotFound(rid));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Read every resource the requirements name, and the exclusion groups
+    /// they count against, for `query`.
+    async fn requirement_pool(&self, requirements: &[Requirement], query: &Span) -> Result<Pool, EngineError> {
+        let now = now_ms();
+        let alloc_window = Span::new((query.start - MAX_BUFFER_MS).max(0), query.end + MAX_BUFFER_MS);
+        let mut pool = Pool { candidates: HashMap::new(), groups: HashMap::new() };
+        for &rid in requirements.iter().flat_map(|req| &req.resources) {
+            let Some(rs) = self.get_resource(&rid) else { return Err(EngineError::NotFound(rid)) };
+            let components = rs.read().await.settings.components.clone();
+            let occupies = if components.is_empty() { vec![rid] } else { components };
+            let mut groups: Vec<(Ulid, u32)> = Vec::new();
+            for member in &occupies {
+                for group in self.store.groups_of(member) {
+                    match groups.iter_mut().find(|(id, _)| *id == group.id) {
+                        Some((_, n)) => *n += 1,
+                        None => groups.push((group.id, 1)),
+                    }
+                    if let Entry::Vacant(entry) = pool.groups.entry(group.id) {
+                        entry.insert((group.limit, self.group_footprints(&group, &alloc_window, now).await));
+                    }
+                }
+            }
+            let free = self.compute_availability(rid, query.start, query.end, None).await?;
+            pool.candidates.insert(rid, Candidate { occupies, groups, free });
+        }
+        Ok(pool)
+    }
+}
+
+impl Pool {
+    /// `query` cut wherever a resource's free time or a group's load changes.
+    fn segments(&self, query: &Span) -> Vec<Span> {
+        let spans = self
+            .candidates
+            .values()
+            .flat_map(|c| &c.free)
+            .chain(self.groups.values().flat_map(|(_, footprints)| footprints));
+        let mut cuts: Vec<Ms> = spans
+            .flat_map(|s| [s.start, s.end])
+            .filter(|t| query.start < *t && *t < query.end)
+            .chain([query.start, query.end])
+            .collect();
+        cuts.sort_unstable();
+        cuts.dedup();
+        cuts.windows(2).map(|w| Span::new(w[0], w[1])).collect()
+    }
+
+    /// Resources meeting every requirement throughout `span`, requirement by
+    /// requirement, or `RequirementNotMet` for the furthest requirement the
+    /// search could not fill.
+    fn assign(&self, requirements: &[Requirement], span: &Span) -> Result<Vec<Ulid>, EngineError> {
+        let free: HashSet<Ulid> = self
+            .candidates
+            .iter()
+            .filter(|(_, c)| c.free.iter().any(|s| s.start <= span.start && s.end >= span.end))
+            .map(|(rid, _)| *rid)
+            .collect();
+        let headroom = self
+            .groups
+            .iter()
+            .map(|(id, (limit, footprints))| (*id, limit.saturating_sub(peak_load(footprints, span))))
+            .collect();
+        let mut search = Search {
+            requirements,
+            candidates: &self.candidates,
+            free,
+            occupied: HashSet::new(),
+            headroom,
+            chosen: Vec::new(),
+            steps: 0,
+            furthest: 0,
+        };
+        if search.fill(0, 0, requirements[0].count)? {
+            Ok(search.chosen)
+        } else {
+            Err(EngineError::RequirementNotMet(search.furthest))
+        }
+    }
+}
+
+/// Depth-first search for resources meeting every requirement at once.
+struct Search<'a> {
+    requirements: &'a [Requirement],
+    candidates: &'a HashMap<Ulid, Candidate>,
+    free: HashSet<Ulid>,
+    occupied: HashSet<Ulid>,
+    headroom: HashMap<Ulid, u32>,
+    chosen: Vec<Ulid>,
+    steps: usize,
+    furthest: usize,
+}
+
+impl Search<'_> {
+    /// Choose `left` more resources for requirement `index` from its
+    /// resources at `from` onwards, then fill the requirements after it.
+    fn fill(&mut self, index: usize, from: usize, left: usize) -> Result<bool, EngineError> {
+        if left == 0 {
+            return match self.requirements.get(index + 1) {
+                Some(next) => self.fill(index + 1, 0, next.count),
+                None => Ok(true),
+            };
+        }
+        self.furthest = self.furthest.max(index);
+        let (requirements, candidates) = (self.requirements, self.candidates);
+        let resources = &requirements[index].resources;
+        for (i, rid) in resources.iter().enumerate().skip(from) {
+            if resources.len() - i < left {
+                break;
+            }
+            self.steps += 1;
+            if self.steps > MAX_REQUIREMENT_STEPS {
+                return Err(EngineError::LimitExceeded("requirement search too large"));
+            }
+            let candidate = &candidates[rid];
+            if !self.free.contains(rid)
+                || candidate.occupies.iter().any(|r| self.occupied.contains(r))
+                || candidate.groups.iter().any(|(g, n)| self.headroom[g] < *n)
+            {
+                continue;
+            }
+            self.take(candidate, true);
+            self.chosen.push(*rid);
+            if self.fill(index, i + 1, left - 1)? {
+                return Ok(true);
+            }
+            self.chosen.pop();
+            self.take(candidate, false);
+        }
+        Ok(false)
+    }
+
+    /// Mark what `candidate` takes up as used, or give it back.
+    fn take(&mut self, candidate: &Candidate, used: bool) {
+        for r in &candidate.occupies {
+            if used {
+                self.occupied.insert(*r);
+            } else {
+                self.occupied.remove(r);
+            }
+        }
+        for (g, n) in &candidate.groups {
+            let room = self.headroom.get_mut(g).expect("group read with its members");
+            if used {
+                *room -= n;
+            } else {
+                *room += n;
+            }
+        }
+    }
+}
+
+/// The most of `footprints` in force at once within `span`.
+fn peak_load(footprints: &[Span], span: &Span) -> u32 {
+    let mut events: Vec<(Ms, i32)> = footprints
+        .iter()
+        .filter(|f| f.overlaps(span))
+        .flat_map(|f| [(f.start.max(span.start), 1), (f.end.min(span.end), -1)])
+        .collect();
+    // Ends sort before starts at the same instant: half-open spans.
+    events.sort_unstable();
+    let (mut load, mut peak) = (0i32, 0i32);
+    for (_, delta) in events {
+        load += delta;
+        peak = peak.max(load);
+    }
+    peak as u32
+}
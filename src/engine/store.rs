@@ -1,14 +1,29 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
 use dashmap::DashMap;
 use ulid::Ulid;
 
 use crate::model::*;
 
-use super::SharedResourceState;
+use super::{SharedCalendarState, SharedResourceState};
 
 pub struct InMemoryStore {
     resources: DashMap<Ulid, SharedResourceState>,
     entity_to_resource: DashMap<Ulid, Ulid>,
     children: DashMap<Ulid, Vec<Ulid>>,
+    /// Parent → position → the child holding it.
+    positions: DashMap<Ulid, HashMap<i64, Ulid>>,
+    /// Component → composites over it.
+    composites: DashMap<Ulid, Vec<Ulid>>,
+    groups: DashMap<Ulid, Arc<ExclusionGroup>>,
+    /// Resource → exclusion groups it belongs to.
+    resource_groups: DashMap<Ulid, Vec<Ulid>>,
+    calendars: DashMap<Ulid, SharedCalendarState>,
+    calendar_rule_to_calendar: DashMap<Ulid, Ulid>,
+    calendar_resources: DashMap<Ulid, Vec<Ulid>>,
+    owner_index: DashMap<String, HashSet<Ulid>>,
 }
 
 impl Default for InMemoryStore {
@@ -23,6 +38,14 @@ impl InMemoryStore {
             resources: DashMap::new(),
             entity_to_resource: DashMap::new(),
             children: DashMap::new(),
+            positions: DashMap::new(),
+            composites: DashMap::new(),
+            groups: DashMap::new(),
+            resource_groups: DashMap::new(),
+            calendars: DashMap::new(),
+            calendar_rule_to_calendar: DashMap::new(),
+            calendar_resources: DashMap::new(),
+            owner_index: DashMap::new(),
         }
     }
 
@@ -66,6 +89,30 @@ impl InMemoryStore {
         self.entity_to_resource.remove(entity_id);
     }
 
+    // ── Owner index (owner → holds and bookings) ────────────
+
+    pub fn get_owner_entities(&self, owner: &str) -> Vec<Ulid> {
+        self.owner_index
+            .get(owner)
+            .map(|e| e.value().iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    fn index_owner(&self, interval: &Interval) {
+        if let Some(owner) = interval.kind.owner() {
+            self.owner_index.entry(owner.to_string()).or_default().insert(interval.id);
+        }
+    }
+
+    pub fn unindex_owner(&self, interval: &Interval) {
+        if let Some(owner) = interval.kind.owner() {
+            self.owner_index.remove_if_mut(owner, |_, ids| {
+                ids.remove(&interval.id);
+                ids.is_empty()
+            });
+        }
+    }
+
     // ── Children index ───────────────────────────────────────
 
     pub fn add_child(&self, parent_id: Ulid, child_id: Ulid) {
@@ -91,6 +138,172 @@ impl InMemoryStore {
             .is_some_and(|kids| !kids.is_empty())
     }
 
+    // ── Positions index ──────────────────────────────────────
+
+    /// Give `child_id` `position` among the children of `parent_id`, or
+    /// return the sibling already holding it.
+    pub fn claim_position(&self, parent_id: Ulid, position: i64, child_id: Ulid) -> Result<(), Ulid> {
+        let mut taken = self.positions.entry(parent_id).or_default();
+        match taken.entry(position) {
+            Entry::Occupied(holder) if *holder.get() != child_id => Err(*holder.get()),
+            Entry::Occupied(_) => Ok(()),
+            Entry::Vacant(free) => {
+                free.insert(child_id);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn release_position(&self, parent_id: &Ulid, position: i64, child_id: &Ulid) {
+        self.positions.remove_if_mut(parent_id, |_, taken| {
+            if taken.get(&position) == Some(child_id) {
+                taken.remove(&position);
+            }
+            taken.is_empty()
+        });
+    }
+
+    // ── Composites index ─────────────────────────────────────
+
+    pub fn link_composite(&self, composite_id: Ulid, components: &[Ulid]) {
+        for component in components {
+            self.composites.entry(*component).or_default().push(composite_id);
+        }
+    }
+
+    pub fn unlink_composite(&self, composite_id: &Ulid, components: &[Ulid]) {
+        for component in components {
+            if let Some(mut ids) = self.composites.get_mut(component) {
+                ids.retain(|c| c != composite_id);
+            }
+        }
+    }
+
+    pub fn get_composites(&self, component_id: &Ulid) -> Vec<Ulid> {
+        self.composites
+            .get(component_id)
+            .map(|e| e.value().clone())
+            .unwrap_or_default()
+    }
+
+    pub fn has_composites(&self, component_id: &Ulid) -> bool {
+        self.composites
+            .get(component_id)
+            .is_some_and(|ids| !ids.is_empty())
+    }
+
+    // ── Exclusion groups ─────────────────────────────────────
+
+    pub fn group_count(&self) -> usize {
+        self.groups.len()
+    }
+
+    pub fn get_group(&self, id: &Ulid) -> Option<Arc<ExclusionGroup>> {
+        self.groups.get(id).map(|e| e.value().clone())
+    }
+
+    pub fn groups(&self) -> Vec<Arc<ExclusionGroup>> {
+        self.groups.iter().map(|e| e.value().clone()).collect()
+    }
+
+    pub fn insert_group(&self, group: ExclusionGroup) {
+        for member in &group.members {
+            self.resource_groups.entry(*member).or_default().push(group.id);
+        }
+        self.groups.insert(group.id, Arc::new(group));
+    }
+
+    pub fn remove_group(&self, id: &Ulid) -> Option<Arc<ExclusionGroup>> {
+        let (_, group) = self.groups.remove(id)?;
+        for member in &group.members {
+            if let Some(mut ids) = self.resource_groups.get_mut(member) {
+                ids.retain(|g| g != id);
+            }
+        }
+        Some(group)
+    }
+
+    /// Groups `resource_id` belongs to.
+    pub fn groups_of(&self, resource_id: &Ulid) -> Vec<Arc<ExclusionGroup>> {
+        self.resource_groups
+            .get(resource_id)
+            .map(|ids| ids.iter().filter_map(|id| self.get_group(id)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Drop a deleted resource from every group it belonged to.
+    pub fn leave_groups(&self, resource_id: &Ulid) {
+        let Some((_, ids)) = self.resource_groups.remove(resource_id) else { return };
+        for id in ids {
+            if let Some(mut group) = self.groups.get_mut(&id) {
+                Arc::make_mut(&mut group).members.retain(|m| m != resource_id);
+            }
+        }
+    }
+
+    // ── Calendars ────────────────────────────────────────────
+
+    pub fn calendar_count(&self) -> usize {
+        self.calendars.len()
+    }
+
+    pub fn contains_calendar(&self, id: &Ulid) -> bool {
+        self.calendars.contains_key(id)
+    }
+
+    pub fn get_calendar(&self, id: &Ulid) -> Option<SharedCalendarState> {
+        self.calendars.get(id).map(|e| e.value().clone())
+    }
+
+    pub fn insert_calendar(&self, id: Ulid, state: SharedCalendarState) {
+        self.calendars.insert(id, state);
+    }
+
+    pub fn remove_calendar(&self, id: &Ulid) -> Option<(Ulid, SharedCalendarState)> {
+        self.calendar_resources.remove(id);
+        self.calendars.remove(id)
+    }
+
+    pub fn calendar_ids(&self) -> Vec<Ulid> {
+        self.calendars.iter().map(|e| *e.key()).collect()
+    }
+
+    pub fn get_calendar_for_rule(&self, rule_id: &Ulid) -> Option<Ulid> {
+        self.calendar_rule_to_calendar.get(rule_id).map(|e| *e.value())
+    }
+
+    pub fn unmap_calendar_rule(&self, rule_id: &Ulid) {
+        self.calendar_rule_to_calendar.remove(rule_id);
+    }
+
+    // ── Calendar attachment index (calendar → resources) ─────
+
+    pub fn attach_calendar(&self, calendar_id: Ulid, resource_id: Ulid) {
+        self.calendar_resources
+            .entry(calendar_id)
+            .or_default()
+            .push(resource_id);
+    }
+
+    pub fn detach_calendar(&self, calendar_id: &Ulid, resource_id: &Ulid) {
+        if let Some(mut rids) = self.calendar_resources.get_mut(calendar_id) {
+            rids.retain(|r| r != resource_id);
+        }
+    }
+
+    pub fn get_calendar_resources(&self, calendar_id: &Ulid) -> Vec<Ulid> {
+        self.calendar_resources
+            .get(calendar_id)
+            .map(|e| e.value().clone())
+            .unwrap_or_default()
+    }
+
+    pub fn calendar_in_use(&self, calendar_id: &Ulid) -> bool {
+        self.calendar_resources
+            .get(calendar_id)
+            .is_some_and(|rids| !rids.is_empty())
+    }
+
     // ── Event application ────────────────────────────────────
 
     pub fn apply_event(&self, rs: &mut ResourceState, event: &Event) {
@@ -100,16 +313,12 @@ impl InMemoryStore {
                 resource_id,
                 span,
                 blocking,
+                capacity,
             } => {
-                let kind = if *blocking {
-                    IntervalKind::Blocking
-                } else {
-                    IntervalKind::NonBlocking
-                };
                 rs.insert_interval(Interval {
                     id: *id,
                     span: *span,
-                    kind,
+                    kind: IntervalKind::rule(*blocking, *capacity),
                 });
                 self.map_entity(*id, *resource_id);
             }
@@ -118,17 +327,13 @@ impl InMemoryStore {
                 resource_id,
                 span,
                 blocking,
+                capacity,
             } => {
                 rs.remove_interval(*id);
-                let kind = if *blocking {
-                    IntervalKind::Blocking
-                } else {
-                    IntervalKind::NonBlocking
-                };
                 rs.insert_interval(Interval {
                     id: *id,
                     span: *span,
-                    kind,
+                    kind: IntervalKind::rule(*blocking, *capacity),
                 });
                 self.map_entity(*id, *resource_id);
             }
@@ -141,18 +346,29 @@ impl InMemoryStore {
                 resource_id,
                 span,
                 expires_at,
+                options,
             } => {
-                rs.insert_interval(Interval {
+                // An upsert replaces the hold with the same id.
+                if self.get_resource_for_entity(id).is_some()
+                    && let Some(old) = rs.remove_interval(*id) {
+                        self.unindex_owner(&old);
+                    }
+                let interval = Interval {
                     id: *id,
                     span: *span,
                     kind: IntervalKind::Hold {
                         expires_at: *expires_at,
+                        options: options.clone(),
                     },
-                });
+                };
+                self.index_owner(&interval);
+                rs.insert_interval(interval);
                 self.map_entity(*id, *resource_id);
             }
             Event::HoldReleased { id, .. } => {
-                rs.remove_interval(*id);
+                if let Some(removed) = rs.remove_interval(*id) {
+                    self.unindex_owner(&removed);
+                }
                 self.unmap_entity(id);
             }
             Event::BookingConfirmed {
@@ -160,29 +376,127 @@ impl InMemoryStore {
                 resource_id,
                 span,
                 label,
+                options,
             } => {
-                rs.insert_interval(Interval {
+                // An upsert replaces the booking with the same id.
+                if self.get_resource_for_entity(id).is_some()
+                    && let Some(old) = rs.remove_interval(*id) {
+                        self.unindex_owner(&old);
+                    }
+                let interval = Interval {
                     id: *id,
                     span: *span,
-                    kind: IntervalKind::Booking { label: label.clone() },
-                });
+                    kind: IntervalKind::Booking {
+                        label: label.clone(),
+                        options: options.clone(),
+                        status: BookingStatus::Confirmed,
+                    },
+                };
+                self.index_owner(&interval);
+                rs.insert_interval(interval);
                 self.map_entity(*id, *resource_id);
             }
-            Event::BookingCancelled { id, .. } => {
-                rs.remove_interval(*id);
-                self.unmap_entity(id);
+            Event::BookingCancelled { id, reason, at, .. } => {
+                // The booking stays indexed so it can still be looked up by id or owner.
+                if let Some(mut interval) = rs.remove_interval(*id) {
+                    if let IntervalKind::Booking { status, .. } = &mut interval.kind {
+                        *status = BookingStatus::Cancelled;
+                    }
+                    rs.cancelled.push(CancelledBooking {
+                        interval,
+                        reason: reason.clone(),
+                        cancelled_at: *at,
+                    });
+                }
+            }
+            Event::BookingStatusChanged { id, status: new_status, .. } => {
+                if let Some(interval) = rs.intervals.iter_mut().find(|i| i.id == *id)
+                    && let IntervalKind::Booking { status, .. } = &mut interval.kind {
+                        *status = *new_status;
+                    }
             }
             Event::ResourceUpdated {
                 name,
                 capacity,
                 buffer_after,
+                settings,
                 ..
             } => {
+                if let Some(pid) = rs.parent_id
+                    && rs.settings.position != settings.position {
+                        if let Some(old) = rs.settings.position {
+                            self.release_position(&pid, old, &rs.id);
+                        }
+                        if let Some(new) = settings.position {
+                            let _ = self.claim_position(pid, new, rs.id);
+                        }
+                    }
                 rs.name = name.clone();
                 rs.capacity = *capacity;
                 rs.buffer_after = *buffer_after;
+                rs.settings = settings.clone();
+            }
+            Event::CalendarAttached {
+                calendar_id,
+                resource_id,
+            } => {
+                rs.calendar_ids.push(*calendar_id);
+                self.attach_calendar(*calendar_id, *resource_id);
+            }
+            Event::CalendarDetached {
+                calendar_id,
+                resource_id,
+            } => {
+                rs.calendar_ids.retain(|c| c != calendar_id);
+                self.detach_calendar(calendar_id, resource_id);
+            }
+            Event::ResourceCreated { .. }
+            | Event::ResourceDeleted { .. }
+            | Event::GroupCreated { .. }
+            | Event::GroupDeleted { .. }
+            | Event::CalendarCreated { .. }
+            | Event::CalendarDeleted { .. }
+            | Event::CalendarRuleAdded { .. }
+            | Event::CalendarRuleUpdated { .. }
+            | Event::CalendarRuleRemoved { .. } => {}
+            // Batches span resources; the engine applies their events one by one.
+            Event::Batch { .. } => {}
+        }
+    }
+
+    /// Apply a calendar rule event to its calendar.
+    pub fn apply_calendar_event(&self, cal: &mut CalendarState, event: &Event) {
+        match event {
+            Event::CalendarRuleAdded {
+                id,
+                calendar_id,
+                span,
+                blocking,
+            }
+            | Event::CalendarRuleUpdated {
+                id,
+                calendar_id,
+                span,
+                blocking,
+            } => {
+                cal.remove_interval(*id);
+                let kind = if *blocking {
+                    IntervalKind::Blocking
+                } else {
+                    IntervalKind::NonBlocking
+                };
+                cal.insert_interval(Interval {
+                    id: *id,
+                    span: *span,
+                    kind,
+                });
+                self.calendar_rule_to_calendar.insert(*id, *calendar_id);
+            }
+            Event::CalendarRuleRemoved { id, .. } => {
+                cal.remove_interval(*id);
+                self.unmap_calendar_rule(id);
             }
-            Event::ResourceCreated { .. } | Event::ResourceDeleted { .. } => {}
+            _ => {}
         }
     }
 }
@@ -0,0 +1,188 @@
+use ulid::Ulid;
+
+use crate::limits::*;
+use crate::model::*;
+
+use super::availability::{inherit_rules, intersect_intervals, subtract_intervals};
+use super::conflict::{collect_capacity_rules, now_ms, validate_span};
+use super::{Engine, EngineError};
+
+/// Per-bucket totals, indexed like the buckets.
+#[derive(Default, Clone)]
+struct Totals {
+    open: Ms,
+    /// Open time times capacity.
+    slots: Ms,
+    booked: Ms,
+    held: Ms,
+}
+
+impl Engine {
+    /// Open, booked and held time over `[start, end)` in buckets of `bucket`
+    /// ms (the whole window if unset), aligned to `start`; the last bucket
+    /// ends at `end`. With `descendants`, totals cover every resource without
+    /// children in the subtree rooted at `resource_id`.
+    pub async fn compute_utilization(
+        &self,
+        resource_id: Ulid,
+        descendants: bool,
+        start: Ms,
+        end: Ms,
+        bucket: Option<Ms>,
+    ) -> Result<Vec<UtilizationBucket>, EngineError> {
+        let window = Span::new(start, end);
+        validate_span(&window)?;
+        if end - start > MAX_QUERY_WINDOW_MS {
+            return Err(EngineError::LimitExceeded("query window too wide"));
+        }
+        let bucket = bucket.unwrap_or(end - start);
+        if bucket <= 0 {
+            return Err(EngineError::InvalidArgument("bucket must be positive"));
+        }
+        let count = ((end - start) as u64).div_ceil(bucket as u64);
+        if count as usize > MAX_UTILIZATION_BUCKETS {
+            return Err(EngineError::LimitExceeded("too many buckets"));
+        }
+        if !self.store.contains_resource(&resource_id) {
+            return Err(EngineError::NotFound(resource_id));
+        }
+
+        let resource_ids = if descendants {
+            let mut ids = self.subtree(resource_id);
+            ids.retain(|id| !self.store.has_children(id));
+            ids
+        } else {
+            vec![resource_id]
+        };
+
+        let buckets = Buckets { window, size: bucket };
+        let mut totals = vec![Totals::default(); count as usize];
+        let now = now_ms();
+        for rid in resource_ids {
+            let Some(rs) = self.get_resource(&rid) else { continue };
+            // Bookings and holds on composites over this resource occupy it
+            // too. Read before taking its own lock, as availability does.
+            let mut allocations = Vec::new();
+            for cid in self.store.get_composites(&rid) {
+                if let Some(composite) = self.get_resource(&cid) {
+                    allocations.extend(active_spans(composite.read().await.overlapping(&window), now));
+                }
+            }
+            let guard = rs.read().await;
+
+            let calendar_rules = self.collect_calendar_rules(&guard.calendar_ids, &window).await;
+            let (inherited_non_blocking, inherited_blocking) = self.collect_inherited_rules(&guard, &window).await?;
+            let mut own_non_blocking = Vec::new();
+            let mut own_blocking = Vec::new();
+            for interval in guard.overlapping(&window).chain(&calendar_rules) {
+                let Some(span) = intersect_intervals(&[interval.span], &[window]).pop() else { continue };
+                match interval.kind {
+                    IntervalKind::NonBlocking => own_non_blocking.push(span),
+                    IntervalKind::Blocking => own_blocking.push(span),
+                    _ => {}
+                }
+            }
+            let (open, blocked) = inherit_rules(
+                guard.settings.inheritance,
+                own_non_blocking,
+                own_blocking,
+                &inherited_non_blocking,
+                &inherited_blocking,
+            );
+            let open = subtract_intervals(&open, &blocked);
+
+            let capacity_rules = collect_capacity_rules(&guard, &window);
+            for span in &open {
+                for (piece, capacity) in capacity_pieces(span, guard.capacity, &capacity_rules) {
+                    buckets.add(&mut totals, &piece, |t, ms| {
+                        t.open += ms;
+                        t.slots += ms * capacity as Ms;
+                    });
+                }
+            }
+            allocations.extend(active_spans(guard.overlapping(&window), now));
+            for (span, held) in allocations {
+                for span in intersect_intervals(&[span], &open) {
+                    buckets.add(&mut totals, &span, |t, ms| {
+                        if held {
+                            t.held += ms;
+                        } else {
+                            t.booked += ms;
+                        }
+                    });
+                }
+            }
+        }
+
+        Ok(totals
+            .into_iter()
+            .enumerate()
+            .map(|(i, t)| UtilizationBucket {
+                span: buckets.span(i),
+                open_ms: t.open,
+                booked_ms: t.booked,
+                held_ms: t.held,
+                utilization: (t.slots > 0).then(|| t.booked as f64 / t.slots as f64),
+            })
+            .collect())
+    }
+}
+
+/// Fixed-size buckets over a window, the last one cut short at its end.
+struct Buckets {
+    window: Span,
+    size: Ms,
+}
+
+impl Buckets {
+    fn span(&self, i: usize) -> Span {
+        let start = self.window.start + i as Ms * self.size;
+        Span::new(start, (start + self.size).min(self.window.end))
+    }
+
+    /// Call `add` with each bucket's share of `span`, which lies inside the window.
+    fn add(&self, totals: &mut [Totals], span: &Span, add: impl Fn(&mut Totals, Ms)) {
+        let first = ((span.start - self.window.start) / self.size) as usize;
+        let last = ((span.end - 1 - self.window.start) / self.size) as usize;
+        for (i, totals) in totals.iter_mut().enumerate().take(last + 1).skip(first) {
+            let bucket = self.span(i);
+            add(totals, span.end.min(bucket.end) - span.start.max(bucket.start));
+        }
+    }
+}
+
+/// The unexpired holds and bookings among `intervals`: each span, and
+/// whether it is held rather than booked.
+fn active_spans<'a>(intervals: impl Iterator<Item = &'a Interval>, now: Ms) -> Vec<(Span, bool)> {
+    intervals
+        .filter_map(|interval| match &interval.kind {
+            IntervalKind::Hold { expires_at, .. } if *expires_at <= now => None,
+            IntervalKind::Hold { .. } => Some((interval.span, true)),
+            IntervalKind::Booking { .. } => Some((interval.span, false)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// `span` cut where the capacity in force changes, with that capacity: the
+/// lowest overlapping rule, or `base` (0 counting as 1) where none applies.
+fn capacity_pieces(span: &Span, base: u32, rules: &[(Span, u32)]) -> Vec<(Span, u32)> {
+    let mut cuts = vec![span.start, span.end];
+    for (rule, _) in rules {
+        cuts.extend([rule.start, rule.end].into_iter().filter(|t| span.start < *t && *t < span.end));
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts.windows(2)
+        .map(|w| {
+            let piece = Span::new(w[0], w[1]);
+            let capacity = rules
+                .iter()
+                .filter(|(rule, _)| rule.overlaps(&piece))
+                .map(|(_, c)| *c)
+                .min()
+                .unwrap_or(base.max(1));
+            (piece, capacity)
+        })
+        .collect()
+}
@@ -1,5 +1,6 @@
 pub const MAX_QUERY_WINDOW_MS: i64 = 90 * 86_400_000; // 90 days
 pub const MAX_SPAN_DURATION_MS: i64 = 3650 * 86_400_000; // ~10 years
+pub const MAX_BUFFER_MS: i64 = 30 * 86_400_000; // 30 days
 pub const MIN_VALID_TIMESTAMP_MS: i64 = 0; // epoch
 pub const MAX_VALID_TIMESTAMP_MS: i64 = 32_503_680_000_000; // year 3000
 pub const MAX_BATCH_SIZE: usize = 1_000;
@@ -16,10 +17,28 @@ pub const MAX_INTERVALS_PER_RESOURCE: usize = 200;
 pub const MAX_RESOURCES_PER_TENANT: usize = 100_000;
 #[cfg(test)]
 pub const MAX_RESOURCES_PER_TENANT: usize = 200;
+#[cfg(not(test))]
+pub const MAX_CALENDARS_PER_TENANT: usize = 10_000;
+#[cfg(test)]
+pub const MAX_CALENDARS_PER_TENANT: usize = 50;
+pub const MAX_CALENDARS_PER_RESOURCE: usize = 32;
+#[cfg(not(test))]
+pub const MAX_GROUPS_PER_TENANT: usize = 10_000;
+#[cfg(test)]
+pub const MAX_GROUPS_PER_TENANT: usize = 50;
+pub const MAX_MEMBERS_PER_GROUP: usize = 1_000;
+pub const MAX_COMPONENTS_PER_RESOURCE: usize = 64;
+pub const MAX_REQUIREMENTS: usize = 32;
+pub const MAX_REQUIREMENT_STEPS: usize = 100_000;
+pub const MAX_ITINERARY_STEPS: usize = 32;
+pub const MAX_ALTERNATIVES: usize = 50;
+pub const MAX_UTILIZATION_BUCKETS: usize = 10_000;
 pub const MAX_TENANTS: usize = 1_000;
 pub const MAX_HIERARCHY_DEPTH: usize = 50;
 pub const MAX_NAME_LEN: usize = 1_000;
 pub const MAX_LABEL_LEN: usize = 10_000;
+pub const MAX_OWNER_LEN: usize = 256;
+pub const MAX_METADATA_LEN: usize = 10_000;
 pub const MAX_TENANT_NAME_LEN: usize = 256;
 pub const MAX_QUERY_LEN: usize = 1_048_576; // 1MB
 pub const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 100;
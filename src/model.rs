@@ -25,7 +25,6 @@ impl Span {
         self.start < other.end && other.start < self.end
     }
 
-    #[allow(dead_code)]
     pub fn contains_instant(&self, t: Ms) -> bool {
         self.start <= t && t < self.end
     }
@@ -44,10 +43,91 @@ pub enum IntervalKind {
     NonBlocking,
     /// Closes availability for this time range.
     Blocking,
+    /// Overrides the resource's capacity for this time range.
+    Capacity { capacity: u32 },
     /// Temporary reservation with expiration.
-    Hold { expires_at: Ms },
+    Hold { expires_at: Ms, options: AllocOptions },
     /// Permanent reservation with optional label.
-    Booking { label: Option<String> },
+    Booking { label: Option<String>, options: AllocOptions, status: BookingStatus },
+}
+
+/// Lifecycle state of a booking. Only cancellation frees its time; no-shows
+/// and completed bookings keep occupying the line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BookingStatus {
+    #[default]
+    Confirmed,
+    Cancelled,
+    NoShow,
+    Completed,
+}
+
+impl BookingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BookingStatus::Confirmed => "confirmed",
+            BookingStatus::Cancelled => "cancelled",
+            BookingStatus::NoShow => "no_show",
+            BookingStatus::Completed => "completed",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "confirmed" => Some(BookingStatus::Confirmed),
+            "cancelled" => Some(BookingStatus::Cancelled),
+            "no_show" => Some(BookingStatus::NoShow),
+            "completed" => Some(BookingStatus::Completed),
+            _ => None,
+        }
+    }
+}
+
+/// What an insert does when its id already exists with a different payload.
+/// Re-submitting an identical payload always succeeds without writing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Fail with `AlreadyExists`.
+    #[default]
+    Error,
+    /// `ON CONFLICT (id) DO NOTHING`: keep the existing row.
+    DoNothing,
+    /// `ON CONFLICT (id) DO UPDATE`: replace the existing row.
+    DoUpdate,
+}
+
+/// Optional per-allocation settings shared by holds and bookings.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllocOptions {
+    /// Overrides the resource's `buffer_before` for this allocation.
+    pub buffer_before: Option<Ms>,
+    /// Overrides the resource's `buffer_after` for this allocation.
+    pub buffer_after: Option<Ms>,
+    /// Who the allocation belongs to; indexed tenant-wide and subject to the
+    /// resource's quota.
+    pub owner: Option<String>,
+    /// Free-form JSON object, kept as compact text so the WAL stays bincode.
+    pub metadata: Option<String>,
+}
+
+impl IntervalKind {
+    /// Kind for a rule as stored in the WAL: a capacity rule if `capacity` is
+    /// set, otherwise blocking or non-blocking.
+    pub fn rule(blocking: bool, capacity: Option<u32>) -> Self {
+        match capacity {
+            Some(capacity) => IntervalKind::Capacity { capacity },
+            None if blocking => IntervalKind::Blocking,
+            None => IntervalKind::NonBlocking,
+        }
+    }
+
+    /// Owner of a hold or booking, if set.
+    pub fn owner(&self) -> Option<&str> {
+        match self {
+            IntervalKind::Hold { options, .. } | IntervalKind::Booking { options, .. } => options.owner.as_deref(),
+            _ => None,
+        }
+    }
 }
 
 /// A single interval on a resource — rules, holds, and bookings are all just intervals.
@@ -59,9 +139,11 @@ pub struct Interval {
 }
 
 impl Interval {
-    #[allow(dead_code)]
     pub fn is_rule(&self) -> bool {
-        matches!(self.kind, IntervalKind::NonBlocking | IntervalKind::Blocking)
+        matches!(
+            self.kind,
+            IntervalKind::NonBlocking | IntervalKind::Blocking | IntervalKind::Capacity { .. }
+        )
     }
 
     #[allow(dead_code)]
@@ -70,6 +152,205 @@ impl Interval {
     }
 }
 
+/// Which intervals a bulk operation applies to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IntervalClass {
+    Rules,
+    Holds,
+    Bookings,
+}
+
+impl IntervalClass {
+    pub fn matches(self, interval: &Interval) -> bool {
+        match self {
+            IntervalClass::Rules => interval.is_rule(),
+            IntervalClass::Holds => matches!(interval.kind, IntervalKind::Hold { .. }),
+            IntervalClass::Bookings => matches!(interval.kind, IntervalKind::Booking { .. }),
+        }
+    }
+}
+
+/// How a resource combines its own rules with those inherited from its parent.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InheritanceMode {
+    /// Own non-blocking rules replace the parent's; blocking rules accumulate.
+    #[default]
+    Override,
+    /// Open only when both the resource and its parent are open; blocking rules accumulate.
+    Intersect,
+    /// Open when either the resource or its parent is open; blocking rules accumulate.
+    Union,
+    /// Ancestors are ignored entirely, including their blocking rules.
+    Isolate,
+}
+
+impl InheritanceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InheritanceMode::Override => "override",
+            InheritanceMode::Intersect => "intersect",
+            InheritanceMode::Union => "union",
+            InheritanceMode::Isolate => "isolate",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "override" => Some(InheritanceMode::Override),
+            "intersect" => Some(InheritanceMode::Intersect),
+            "union" => Some(InheritanceMode::Union),
+            "isolate" => Some(InheritanceMode::Isolate),
+            _ => None,
+        }
+    }
+}
+
+/// Per-resource settings beyond capacity and `buffer_after`. Defaults reproduce
+/// the behaviour of a plain resource.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResourceSettings {
+    pub inheritance: InheritanceMode,
+    /// Buffer time in ms before each allocation starts (e.g. setup time).
+    pub buffer_before: Option<Ms>,
+    pub policy: BookingPolicy,
+    /// Allocations must start and end on multiples of this, counted from
+    /// `granularity_offset_ms` (e.g. 15-minute boundaries).
+    pub granularity_ms: Option<Ms>,
+    pub granularity_offset_ms: Ms,
+    pub quota: Option<OwnerQuota>,
+    /// Resources every allocation here also occupies, making this a composite
+    /// (e.g. "Hall A+B" over halls A and B). Fixed at creation; components
+    /// are plain resources, never composites themselves.
+    pub components: Vec<Ulid>,
+    /// Order among siblings (e.g. seat number in a row). Children whose
+    /// positions differ by one are adjacent for block booking.
+    pub position: Option<i64>,
+}
+
+/// Per-owner limit on active allocations of a resource within any rolling
+/// window of `window_ms`, counted by start time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OwnerQuota {
+    pub window_ms: Ms,
+    pub max_count: Option<u32>,
+    pub max_duration_ms: Option<Ms>,
+}
+
+impl ResourceSettings {
+    pub fn is_composite(&self) -> bool {
+        !self.components.is_empty()
+    }
+
+    pub fn is_aligned(&self, t: Ms) -> bool {
+        match self.granularity_ms {
+            Some(g) => (t - self.granularity_offset_ms).rem_euclid(g) == 0,
+            None => true,
+        }
+    }
+
+    /// Earliest grid point at or after `t`.
+    pub fn align_up(&self, t: Ms) -> Ms {
+        match self.granularity_ms {
+            Some(g) => {
+                let rem = (t - self.granularity_offset_ms).rem_euclid(g);
+                if rem == 0 { t } else { t + (g - rem) }
+            }
+            None => t,
+        }
+    }
+
+    /// Latest grid point at or before `t`.
+    pub fn align_down(&self, t: Ms) -> Ms {
+        match self.granularity_ms {
+            Some(g) => t - (t - self.granularity_offset_ms).rem_euclid(g),
+            None => t,
+        }
+    }
+}
+
+/// Constraints on the shape and timing of holds and bookings. Each unset field
+/// is inherited from the nearest ancestor that sets it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BookingPolicy {
+    pub min_duration: Option<Ms>,
+    pub max_duration: Option<Ms>,
+    /// How far ahead of `now` an allocation must start.
+    pub min_lead_time: Option<Ms>,
+    /// How far past `now` an allocation may end.
+    pub max_horizon: Option<Ms>,
+}
+
+impl BookingPolicy {
+    /// Fill unset fields from an ancestor's policy.
+    pub fn inherit(self, ancestor: &BookingPolicy) -> BookingPolicy {
+        BookingPolicy {
+            min_duration: self.min_duration.or(ancestor.min_duration),
+            max_duration: self.max_duration.or(ancestor.max_duration),
+            min_lead_time: self.min_lead_time.or(ancestor.min_lead_time),
+            max_horizon: self.max_horizon.or(ancestor.max_horizon),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == BookingPolicy::default()
+    }
+}
+
+/// The settings an update assigns. Unassigned (`None`) fields keep the
+/// resource's current value; components cannot be changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SettingsPatch {
+    pub inheritance: Option<InheritanceMode>,
+    pub buffer_before: Option<Option<Ms>>,
+    pub min_duration: Option<Option<Ms>>,
+    pub max_duration: Option<Option<Ms>>,
+    pub min_lead_time: Option<Option<Ms>>,
+    pub max_horizon: Option<Option<Ms>>,
+    pub granularity_ms: Option<Option<Ms>>,
+    pub granularity_offset_ms: Option<Ms>,
+    /// The quota as a whole; its fields are not patched one by one.
+    pub quota: Option<Option<OwnerQuota>>,
+    pub position: Option<Option<i64>>,
+}
+
+impl SettingsPatch {
+    pub fn apply(&self, settings: &mut ResourceSettings) {
+        fn set<T: Clone>(field: &mut T, value: &Option<T>) {
+            if let Some(value) = value {
+                field.clone_from(value);
+            }
+        }
+        set(&mut settings.inheritance, &self.inheritance);
+        set(&mut settings.buffer_before, &self.buffer_before);
+        set(&mut settings.policy.min_duration, &self.min_duration);
+        set(&mut settings.policy.max_duration, &self.max_duration);
+        set(&mut settings.policy.min_lead_time, &self.min_lead_time);
+        set(&mut settings.policy.max_horizon, &self.max_horizon);
+        set(&mut settings.granularity_ms, &self.granularity_ms);
+        set(&mut settings.granularity_offset_ms, &self.granularity_offset_ms);
+        set(&mut settings.quota, &self.quota);
+        set(&mut settings.position, &self.position);
+    }
+}
+
+/// Assigns every setting but the components.
+impl From<ResourceSettings> for SettingsPatch {
+    fn from(settings: ResourceSettings) -> Self {
+        SettingsPatch {
+            inheritance: Some(settings.inheritance),
+            buffer_before: Some(settings.buffer_before),
+            min_duration: Some(settings.policy.min_duration),
+            max_duration: Some(settings.policy.max_duration),
+            min_lead_time: Some(settings.policy.min_lead_time),
+            max_horizon: Some(settings.policy.max_horizon),
+            granularity_ms: Some(settings.granularity_ms),
+            granularity_offset_ms: Some(settings.granularity_offset_ms),
+            quota: Some(settings.quota),
+            position: Some(settings.position),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ResourceState {
     pub id: Ulid,
@@ -79,8 +360,21 @@ pub struct ResourceState {
     pub capacity: u32,
     /// Buffer time in ms after each allocation ends (e.g. cleaning time).
     pub buffer_after: Option<Ms>,
+    pub settings: ResourceSettings,
+    /// Shared calendars whose rules apply to this resource as if they were its own.
+    pub calendar_ids: Vec<Ulid>,
     /// All intervals (rules + allocations), sorted by `span.start`.
     pub intervals: Vec<Interval>,
+    /// Cancelled bookings, kept out of `intervals` so they never block time.
+    pub cancelled: Vec<CancelledBooking>,
+}
+
+/// A cancelled booking, retained for history until GC.
+#[derive(Debug, Clone)]
+pub struct CancelledBooking {
+    pub interval: Interval,
+    pub reason: Option<String>,
+    pub cancelled_at: Ms,
 }
 
 impl ResourceState {
@@ -91,7 +385,10 @@ impl ResourceState {
             name,
             capacity,
             buffer_after,
+            settings: ResourceSettings::default(),
+            calendar_ids: Vec::new(),
             intervals: Vec::new(),
+            cancelled: Vec::new(),
         }
     }
 
@@ -113,6 +410,14 @@ impl ResourceState {
         }
     }
 
+    /// The time an allocation occupies: its span widened by its buffers, each
+    /// taken from the allocation's overrides or else the resource's defaults.
+    pub fn footprint(&self, span: &Span, options: &AllocOptions) -> Span {
+        let before = options.buffer_before.or(self.settings.buffer_before).unwrap_or(0);
+        let after = options.buffer_after.or(self.buffer_after).unwrap_or(0);
+        Span::new(span.start - before, span.end + after)
+    }
+
     /// Return only intervals whose span overlaps the query window.
     /// Uses binary search to skip intervals starting at or after `query.end`.
     pub fn overlapping(&self, query: &Span) -> impl Iterator<Item = &Interval> {
@@ -124,9 +429,105 @@ impl ResourceState {
             .iter()
             .filter(move |i| i.span.end > query.start)
     }
+
+    /// This composite's holds and bookings near `query` as they weigh on a
+    /// component: widened to their footprint here, with no buffers left for
+    /// the component to add.
+    pub fn shared_allocations(&self, query: &Span) -> Vec<Interval> {
+        self.overlapping(query)
+            .filter_map(|interval| {
+                let mut kind = interval.kind.clone();
+                let (IntervalKind::Hold { options, .. } | IntervalKind::Booking { options, .. }) = &mut kind else {
+                    return None;
+                };
+                let span = self.footprint(&interval.span, options);
+                options.buffer_before = Some(0);
+                options.buffer_after = Some(0);
+                Some(Interval { id: interval.id, span, kind })
+            })
+            .collect()
+    }
+}
+
+/// Resources of which at most `limit` may be allocated at any instant, e.g.
+/// treatment rooms sharing one machine. Members are fixed at creation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExclusionGroup {
+    pub id: Ulid,
+    pub name: Option<String>,
+    pub limit: u32,
+    pub members: Vec<Ulid>,
 }
 
-/// The event types — flat, no nesting. This is the WAL record format.
+/// `count` distinct resources out of `resources`, e.g. 2 of the ward's
+/// nurses, as one part of a requirements query or booking.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Requirement {
+    pub resources: Vec<Ulid>,
+    pub count: usize,
+}
+
+/// One step of an itinerary: `duration` on any one of `resources`, starting
+/// `min_gap..=max_gap` after the previous step ends. The first step's gaps
+/// are ignored.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItineraryStep {
+    pub resources: Vec<Ulid>,
+    pub duration: Ms,
+    #[serde(default)]
+    pub min_gap: Ms,
+    #[serde(default)]
+    pub max_gap: Ms,
+}
+
+/// A named set of rules shared by many resources (holidays, closures).
+/// Holds rules only — allocations always live on resources.
+#[derive(Debug, Clone)]
+pub struct CalendarState {
+    pub id: Ulid,
+    pub name: Option<String>,
+    /// Rule intervals, sorted by `span.start`.
+    pub intervals: Vec<Interval>,
+}
+
+impl CalendarState {
+    pub fn new(id: Ulid, name: Option<String>) -> Self {
+        Self {
+            id,
+            name,
+            intervals: Vec::new(),
+        }
+    }
+
+    /// Insert rule maintaining sort order by span.start.
+    pub fn insert_interval(&mut self, interval: Interval) {
+        let pos = self
+            .intervals
+            .binary_search_by_key(&interval.span.start, |i| i.span.start)
+            .unwrap_or_else(|e| e);
+        self.intervals.insert(pos, interval);
+    }
+
+    /// Remove rule by id.
+    pub fn remove_interval(&mut self, id: Ulid) -> Option<Interval> {
+        let pos = self.intervals.iter().position(|i| i.id == id)?;
+        Some(self.intervals.remove(pos))
+    }
+
+    /// Return only rules whose span overlaps the query window.
+    pub fn overlapping(&self, query: &Span) -> impl Iterator<Item = &Interval> {
+        let right_bound = self
+            .intervals
+            .partition_point(|i| i.span.start < query.end);
+        self.intervals[..right_bound]
+            .iter()
+            .filter(move |i| i.span.end > query.start)
+    }
+}
+
+/// The event types — flat apart from `Batch`. This is the WAL record format.
+/// Changing how any variant encodes needs a new WAL version and a migration
+/// in `wal.rs`.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Event {
     ResourceCreated {
@@ -135,12 +536,14 @@ pub enum Event {
         name: Option<String>,
         capacity: u32,
         buffer_after: Option<Ms>,
+        settings: ResourceSettings,
     },
     ResourceUpdated {
         id: Ulid,
         name: Option<String>,
         capacity: u32,
         buffer_after: Option<Ms>,
+        settings: ResourceSettings,
     },
     ResourceDeleted {
         id: Ulid,
@@ -150,12 +553,15 @@ pub enum Event {
         resource_id: Ulid,
         span: Span,
         blocking: bool,
+        /// Set for capacity rules, which neither open nor close time.
+        capacity: Option<u32>,
     },
     RuleUpdated {
         id: Ulid,
         resource_id: Ulid,
         span: Span,
         blocking: bool,
+        capacity: Option<u32>,
     },
     RuleRemoved {
         id: Ulid,
@@ -166,6 +572,7 @@ pub enum Event {
         resource_id: Ulid,
         span: Span,
         expires_at: Ms,
+        options: AllocOptions,
     },
     HoldReleased {
         id: Ulid,
@@ -176,10 +583,63 @@ pub enum Event {
         resource_id: Ulid,
         span: Span,
         label: Option<String>,
+        options: AllocOptions,
     },
     BookingCancelled {
         id: Ulid,
         resource_id: Ulid,
+        reason: Option<String>,
+        at: Ms,
+    },
+    BookingStatusChanged {
+        id: Ulid,
+        resource_id: Ulid,
+        status: BookingStatus,
+    },
+    CalendarCreated {
+        id: Ulid,
+        name: Option<String>,
+    },
+    CalendarDeleted {
+        id: Ulid,
+    },
+    CalendarRuleAdded {
+        id: Ulid,
+        calendar_id: Ulid,
+        span: Span,
+        blocking: bool,
+    },
+    CalendarRuleUpdated {
+        id: Ulid,
+        calendar_id: Ulid,
+        span: Span,
+        blocking: bool,
+    },
+    CalendarRuleRemoved {
+        id: Ulid,
+        calendar_id: Ulid,
+    },
+    CalendarAttached {
+        calendar_id: Ulid,
+        resource_id: Ulid,
+    },
+    CalendarDetached {
+        calendar_id: Ulid,
+        resource_id: Ulid,
+    },
+    GroupCreated {
+        id: Ulid,
+        name: Option<String>,
+        limit: u32,
+        members: Vec<Ulid>,
+    },
+    GroupDeleted {
+        id: Ulid,
+    },
+    /// Events written as one WAL record, so they replay all or not at all.
+    /// Never nested.
+    Batch {
+        events: Vec<Event>,
     },
 }
 
@@ -192,6 +652,7 @@ pub struct ResourceInfo {
     pub name: Option<String>,
     pub capacity: u32,
     pub buffer_after: Option<Ms>,
+    pub settings: ResourceSettings,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -201,6 +662,22 @@ pub struct RuleInfo {
     pub start: Ms,
     pub end: Ms,
     pub blocking: bool,
+    pub capacity: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarInfo {
+    pub id: Ulid,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CalendarRuleInfo {
+    pub id: Ulid,
+    pub calendar_id: Ulid,
+    pub start: Ms,
+    pub end: Ms,
+    pub blocking: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -210,6 +687,13 @@ pub struct BookingInfo {
     pub start: Ms,
     pub end: Ms,
     pub label: Option<String>,
+    pub buffer_before: Option<Ms>,
+    pub buffer_after: Option<Ms>,
+    pub owner: Option<String>,
+    pub metadata: Option<String>,
+    pub status: BookingStatus,
+    pub cancel_reason: Option<String>,
+    pub cancelled_at: Option<Ms>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -219,6 +703,135 @@ pub struct HoldInfo {
     pub start: Ms,
     pub end: Ms,
     pub expires_at: Ms,
+    pub buffer_before: Option<Ms>,
+    pub buffer_after: Option<Ms>,
+    pub owner: Option<String>,
+    pub metadata: Option<String>,
+}
+
+/// Why part of a resource's timeline is unavailable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BusyCause {
+    /// A blocking rule of the resource or of an attached calendar.
+    BlockingRule,
+    /// A blocking rule of an ancestor.
+    InheritedRule,
+    /// No non-blocking rule opens the time.
+    NoOpenRule,
+    Booking,
+    Hold,
+    /// The buffer before or after a booking or hold.
+    Buffer,
+    /// Allocations fill the resource's (possibly overridden) capacity.
+    Capacity,
+    /// An exclusion group the resource belongs to is at its limit.
+    GroupLimit,
+    /// None of the above: the booking policy or slot grid rules it out.
+    Policy,
+}
+
+impl BusyCause {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BusyCause::BlockingRule => "blocking_rule",
+            BusyCause::InheritedRule => "inherited_rule",
+            BusyCause::NoOpenRule => "no_open_rule",
+            BusyCause::Booking => "booking",
+            BusyCause::Hold => "hold",
+            BusyCause::Buffer => "buffer",
+            BusyCause::Capacity => "capacity",
+            BusyCause::GroupLimit => "group_limit",
+            BusyCause::Policy => "policy",
+        }
+    }
+}
+
+/// A blocked stretch of a resource's timeline and what blocks it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BusySegment {
+    pub span: Span,
+    pub cause: BusyCause,
+    /// The resource, ancestor, composite or exclusion group the cause belongs to.
+    pub source_id: Ulid,
+    /// Rules and allocations responsible.
+    pub interval_ids: Vec<Ulid>,
+}
+
+/// Open, booked and held time of a resource or subtree over one bucket.
+/// Booked and held time count only within open time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtilizationBucket {
+    pub span: Span,
+    pub open_ms: Ms,
+    pub booked_ms: Ms,
+    pub held_ms: Ms,
+    /// Booked time over open time weighted by capacity; `None` with no open time.
+    pub utilization: Option<f64>,
+}
+
+/// Predicate on an allocation's JSON metadata.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetadataFilter {
+    /// `metadata->>'key' = value`: a string value compares as text, any other
+    /// value as JSON.
+    KeyEquals { key: String, value: String },
+    /// `metadata @> '{...}'`: the metadata contains the given JSON.
+    Contains(serde_json::Value),
+}
+
+impl MetadataFilter {
+    fn matches(&self, metadata: &serde_json::Value) -> bool {
+        match self {
+            MetadataFilter::KeyEquals { key, value } => match metadata.get(key) {
+                None | Some(serde_json::Value::Null) => false,
+                Some(serde_json::Value::String(s)) => s == value,
+                Some(other) => serde_json::from_str::<serde_json::Value>(value).is_ok_and(|v| v == *other),
+            },
+            MetadataFilter::Contains(needle) => json_contains(metadata, needle),
+        }
+    }
+}
+
+/// Postgres `@>` semantics: objects match key-wise, every element of an
+/// array needle must be contained in some element of the haystack, and
+/// scalars compare equal.
+fn json_contains(haystack: &serde_json::Value, needle: &serde_json::Value) -> bool {
+    use serde_json::Value;
+    match (haystack, needle) {
+        (Value::Object(h), Value::Object(n)) => n
+            .iter()
+            .all(|(k, nv)| h.get(k).is_some_and(|hv| json_contains(hv, nv))),
+        (Value::Array(h), Value::Array(n)) => n
+            .iter()
+            .all(|nv| h.iter().any(|hv| json_contains(hv, nv))),
+        _ => haystack == needle,
+    }
+}
+
+/// Row filters for `SELECT * FROM bookings` beyond the lookup key.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookingFilter {
+    pub status: Option<BookingStatus>,
+    pub metadata: Vec<MetadataFilter>,
+}
+
+impl BookingFilter {
+    pub fn matches(&self, booking: &BookingInfo) -> bool {
+        if self.status.is_some_and(|s| s != booking.status) {
+            return false;
+        }
+        if self.metadata.is_empty() {
+            return true;
+        }
+        let Some(value) = booking
+            .metadata
+            .as_deref()
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+        else {
+            return false;
+        };
+        self.metadata.iter().all(|f| f.matches(&value))
+    }
 }
 
 #[cfg(test)]
@@ -259,7 +872,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(300, 400),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         rs.insert_interval(Interval {
             id: Ulid::new(),
@@ -269,7 +882,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(200, 300),
-            kind: IntervalKind::Hold { expires_at: 9999 },
+            kind: IntervalKind::Hold { expires_at: 9999, options: AllocOptions::default() },
         });
         assert_eq!(rs.intervals[0].span.start, 100);
         assert_eq!(rs.intervals[1].span.start, 200);
@@ -283,7 +896,7 @@ mod tests {
         rs.insert_interval(Interval {
             id,
             span: Span::new(100, 200),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         assert_eq!(rs.intervals.len(), 1);
         rs.remove_interval(id);
@@ -297,7 +910,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(100, 200),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         // Overlapping interval
         rs.insert_interval(Interval {
@@ -309,7 +922,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(1000, 1100),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
 
         let query = Span::new(500, 800);
@@ -325,7 +938,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(100, 200),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         let query = Span::new(200, 300);
         let hits: Vec<_> = rs.overlapping(&query).collect();
@@ -339,7 +952,7 @@ mod tests {
             rs.insert_interval(Interval {
                 id: Ulid::new(),
                 span: Span::new(i * 100, i * 100 + 50),
-                kind: IntervalKind::Booking { label: None },
+                kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
             });
         }
         // All intervals end before 1000
@@ -355,7 +968,7 @@ mod tests {
             rs.insert_interval(Interval {
                 id: Ulid::new(),
                 span: Span::new(i * 100, i * 100 + 50),
-                kind: IntervalKind::Booking { label: None },
+                kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
             });
         }
         // All intervals start at 1000+, query ends at 500
@@ -393,7 +1006,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(100, 201),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         let query = Span::new(200, 300);
         let hits: Vec<_> = rs.overlapping(&query).collect();
@@ -406,7 +1019,7 @@ mod tests {
         rs.insert_interval(Interval {
             id: Ulid::new(),
             span: Span::new(100, 200),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         });
         let result = rs.remove_interval(Ulid::new());
         assert!(result.is_none());
@@ -421,7 +1034,7 @@ mod tests {
             rs.insert_interval(Interval {
                 id,
                 span: Span::new((i as Ms) * 100, (i as Ms) * 100 + 50),
-                kind: IntervalKind::Booking { label: None },
+                kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
             });
         }
         rs.remove_interval(ids[1]); // remove middle
@@ -450,7 +1063,7 @@ mod tests {
         let h = Interval {
             id: Ulid::new(),
             span: Span::new(0, 100),
-            kind: IntervalKind::Hold { expires_at: 999 },
+            kind: IntervalKind::Hold { expires_at: 999, options: AllocOptions::default() },
         };
         assert!(h.is_allocation());
         assert!(!h.is_rule());
@@ -458,7 +1071,7 @@ mod tests {
         let bk = Interval {
             id: Ulid::new(),
             span: Span::new(0, 100),
-            kind: IntervalKind::Booking { label: None },
+            kind: IntervalKind::Booking { label: None, options: AllocOptions::default(), status: BookingStatus::Confirmed },
         };
         assert!(bk.is_allocation());
     }
@@ -477,9 +1090,45 @@ mod tests {
             name: Some("Test".into()),
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         };
         let bytes = bincode::serialize(&event).unwrap();
         let decoded: Event = bincode::deserialize(&bytes).unwrap();
         assert_eq!(event, decoded);
     }
+
+    #[test]
+    fn metadata_filter_matching() {
+        let booking = BookingInfo {
+            id: Ulid::new(),
+            resource_id: Ulid::new(),
+            start: 0,
+            end: 100,
+            label: None,
+            buffer_before: None,
+            buffer_after: None,
+            owner: None,
+            status: BookingStatus::Confirmed,
+            cancel_reason: None,
+            cancelled_at: None,
+            metadata: Some(r#"{"channel":"web","party":4,"tags":["vip","late"],"ref":{"pnr":"X1"}}"#.into()),
+        };
+        let matches = |filters: Vec<MetadataFilter>| BookingFilter { metadata: filters, ..Default::default() }.matches(&booking);
+        let eq = |key: &str, value: &str| MetadataFilter::KeyEquals { key: key.into(), value: value.into() };
+        let contains = |json: &str| MetadataFilter::Contains(serde_json::from_str(json).unwrap());
+
+        assert!(matches(vec![]));
+        assert!(matches(vec![eq("channel", "web")]));
+        assert!(matches(vec![eq("party", "4")]));
+        assert!(!matches(vec![eq("channel", "phone")]));
+        assert!(!matches(vec![eq("missing", "web")]));
+        assert!(matches(vec![contains(r#"{"party":4,"ref":{"pnr":"X1"}}"#)]));
+        assert!(matches(vec![contains(r#"{"tags":["late"]}"#)]));
+        assert!(!matches(vec![contains(r#"{"party":"4"}"#)]));
+        assert!(!matches(vec![eq("channel", "web"), contains(r#"{"tags":["walk-in"]}"#)]));
+
+        let plain = BookingInfo { metadata: None, ..booking.clone() };
+        assert!(BookingFilter::default().matches(&plain));
+        assert!(!BookingFilter { metadata: vec![contains("{}")], ..Default::default() }.matches(&plain));
+    }
 }
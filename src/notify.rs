@@ -52,6 +52,7 @@ impl NotifyHub {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::ResourceSettings;
 
     #[tokio::test]
     async fn subscribe_and_receive() {
@@ -65,6 +66,7 @@ mod tests {
             name: None,
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         };
         hub.send(rid, &event);
 
@@ -96,6 +98,7 @@ mod tests {
             name: None,
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         };
         hub.send(rid, &event);
 
@@ -53,19 +53,50 @@ pub fn command_label(cmd: &Command) -> &'static str {
         Command::InsertRule { .. } => "insert_rule",
         Command::UpdateRule { .. } => "update_rule",
         Command::DeleteRule { .. } => "delete_rule",
+        Command::ReplaceRules { .. } => "replace_rules",
+        Command::CloneSubtree { .. } => "clone_subtree",
+        Command::BookBlock { .. } => "book_block",
+        Command::BookRequirements { .. } => "book_requirements",
+        Command::BookItinerary { .. } => "book_itinerary",
+        Command::Explain { .. } => "explain",
         Command::InsertHold { .. } => "insert_hold",
         Command::DeleteHold { .. } => "delete_hold",
         Command::InsertBooking { .. } => "insert_booking",
         Command::BatchInsertBookings { .. } => "batch_insert_bookings",
         Command::DeleteBooking { .. } => "delete_booking",
+        Command::DeleteInRange { .. } => "delete_in_range",
+        Command::UpdateBookingStatus { .. } => "update_booking_status",
         Command::SelectResources { .. } => "select_resources",
         Command::SelectRules { .. } => "select_rules",
+        Command::SelectRulesById { .. } => "select_rules_by_id",
         Command::SelectBookings { .. } => "select_bookings",
+        Command::SelectBookingsById { .. } => "select_bookings_by_id",
+        Command::SelectBookingsByStatus { .. } => "select_bookings_by_status",
+        Command::SelectBookingsByOwner { .. } => "select_bookings_by_owner",
         Command::SelectHolds { .. } => "select_holds",
+        Command::SelectHoldsById { .. } => "select_holds_by_id",
+        Command::SelectHoldsByOwner { .. } => "select_holds_by_owner",
         Command::SelectAvailability { .. } => "select_availability",
+        Command::SelectBusy { .. } => "select_busy",
+        Command::SelectUtilization { .. } => "select_utilization",
         Command::SelectMultiAvailability { .. } => "select_multi_availability",
+        Command::SelectRequirementAvailability { .. } => "select_requirement_availability",
+        Command::InsertCalendar { .. } => "insert_calendar",
+        Command::DeleteCalendar { .. } => "delete_calendar",
+        Command::InsertCalendarRule { .. } => "insert_calendar_rule",
+        Command::UpdateCalendarRule { .. } => "update_calendar_rule",
+        Command::DeleteCalendarRule { .. } => "delete_calendar_rule",
+        Command::AttachCalendar { .. } => "attach_calendar",
+        Command::DetachCalendar { .. } => "detach_calendar",
+        Command::SelectCalendars => "select_calendars",
+        Command::SelectCalendarRules { .. } => "select_calendar_rules",
+        Command::SelectCalendarAttachments { .. } => "select_calendar_attachments",
+        Command::InsertGroup { .. } => "insert_group",
+        Command::DeleteGroup { .. } => "delete_group",
+        Command::SelectGroups => "select_groups",
         Command::Listen { .. } => "listen",
         Command::Unlisten { .. } => "unlisten",
         Command::UnlistenAll => "unlisten_all",
+        Command::Returning { command, .. } => command_label(command),
     }
 }
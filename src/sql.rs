@@ -3,9 +3,12 @@ use sqlparser::dialect::PostgreSqlDialect;
 use sqlparser::parser::Parser;
 use ulid::Ulid;
 
-use crate::limits::MAX_IN_CLAUSE_IDS;
+use crate::limits::{MAX_ALTERNATIVES, MAX_IN_CLAUSE_IDS};
 use crate::model::*;
 
+/// (id, start, end, blocking, capacity)
+pub type RuleRow = (Ulid, Ms, Ms, bool, Option<u32>);
+
 /// Parsed command from SQL input.
 #[derive(Debug, PartialEq)]
 pub enum Command {
@@ -15,12 +18,15 @@ pub enum Command {
         name: Option<String>,
         capacity: u32,
         buffer_after: Option<Ms>,
+        settings: ResourceSettings,
+        on_conflict: OnConflict,
     },
     UpdateResource {
         id: Ulid,
         name: Option<String>,
         capacity: u32,
         buffer_after: Option<Ms>,
+        settings: SettingsPatch,
     },
     DeleteResource {
         id: Ulid,
@@ -31,22 +37,67 @@ pub enum Command {
         start: Ms,
         end: Ms,
         blocking: bool,
+        capacity: Option<u32>,
+        on_conflict: OnConflict,
     },
     UpdateRule {
         id: Ulid,
         start: Ms,
         end: Ms,
         blocking: bool,
+        capacity: Option<u32>,
     },
     DeleteRule {
         id: Ulid,
     },
+    /// `INSERT INTO rule_sets (resource_id, start, "end", rules)`: replace the
+    /// resource's rules within the span with the JSON array in `rules`.
+    ReplaceRules {
+        resource_id: Ulid,
+        start: Ms,
+        end: Ms,
+        rules: Vec<RuleRow>,
+    },
+    /// `INSERT INTO resource_clones (source_id, parent_id, include_rules)`:
+    /// copy the source's subtree under `parent_id` with fresh ids.
+    CloneSubtree {
+        source_id: Ulid,
+        parent_id: Option<Ulid>,
+        include_rules: bool,
+    },
+    /// `INSERT INTO requirement_bookings (requirements, start, "end", label)`:
+    /// book resources meeting every requirement.
+    BookRequirements {
+        requirements: Vec<Requirement>,
+        start: Ms,
+        end: Ms,
+        label: Option<String>,
+    },
+    /// `INSERT INTO itinerary_bookings (steps, start, "end", label)`: book
+    /// the earliest chain of steps within `[start, end)`.
+    BookItinerary {
+        steps: Vec<ItineraryStep>,
+        start: Ms,
+        end: Ms,
+        label: Option<String>,
+    },
+    /// `INSERT INTO block_bookings (parent_id, count, start, "end", label)`:
+    /// book `count` adjacent free children of `parent_id`.
+    BookBlock {
+        parent_id: Ulid,
+        count: usize,
+        start: Ms,
+        end: Ms,
+        label: Option<String>,
+    },
     InsertHold {
         id: Ulid,
         resource_id: Ulid,
         start: Ms,
         end: Ms,
         expires_at: Ms,
+        options: AllocOptions,
+        on_conflict: OnConflict,
     },
     DeleteHold {
         id: Ulid,
@@ -57,25 +108,73 @@ pub enum Command {
         start: Ms,
         end: Ms,
         label: Option<String>,
+        options: AllocOptions,
+        on_conflict: OnConflict,
+        /// On a conflict, how many nearby alternatives to report (`suggest`).
+        suggest: usize,
     },
     BatchInsertBookings {
-        bookings: Vec<(Ulid, Ulid, Ms, Ms, Option<String>)>, // (id, resource_id, start, end, label)
+        bookings: Vec<(Ulid, Ulid, Ms, Ms, Option<String>, AllocOptions)>, // (id, resource_id, start, end, label, options)
+        on_conflict: OnConflict,
     },
     DeleteBooking {
         id: Ulid,
     },
+    /// `DELETE FROM rules|holds|bookings WHERE resource_id = ... AND start >= ...
+    /// AND "end" <= ...`; `descendants_of = ...` instead of `resource_id`
+    /// covers that resource and everything below it.
+    DeleteInRange {
+        class: IntervalClass,
+        resource_id: Ulid,
+        descendants: bool,
+        start: Ms,
+        end: Ms,
+    },
+    /// `UPDATE bookings SET status = ... [, cancel_reason = ...] WHERE id = ...`
+    UpdateBookingStatus {
+        id: Ulid,
+        status: BookingStatus,
+        reason: Option<String>,
+    },
     SelectResources {
         parent_id: Option<Option<Ulid>>, // None = no filter, Some(None) = root only, Some(Some(id)) = children of id
     },
     SelectRules {
         resource_id: Ulid,
     },
+    SelectRulesById {
+        ids: Vec<Ulid>,
+    },
     SelectBookings {
         resource_id: Ulid,
+        filter: BookingFilter,
+    },
+    /// `WHERE id = ...` or `WHERE id IN (...)`, resolved through the entity index.
+    SelectBookingsById {
+        ids: Vec<Ulid>,
+        filter: BookingFilter,
+    },
+    /// Tenant-wide, optionally narrowed to one resource.
+    SelectBookingsByOwner {
+        owner: String,
+        resource_id: Option<Ulid>,
+        filter: BookingFilter,
+    },
+    /// `WHERE status = ...` without a resource: every resource of the tenant.
+    SelectBookingsByStatus {
+        status: BookingStatus,
+        filter: BookingFilter,
     },
     SelectHolds {
         resource_id: Ulid,
     },
+    SelectHoldsById {
+        ids: Vec<Ulid>,
+    },
+    SelectHoldsByOwner {
+        owner: String,
+        resource_id: Option<Ulid>,
+    },
     SelectAvailability {
         resource_id: Ulid,
         start: Ms,
@@ -89,6 +188,80 @@ pub enum Command {
         min_available: usize,
         min_duration: Option<Ms>,
     },
+    /// `SELECT * FROM availability WHERE requirements = '<json>' AND ...`.
+    SelectRequirementAvailability {
+        requirements: Vec<Requirement>,
+        start: Ms,
+        end: Ms,
+        min_duration: Option<Ms>,
+    },
+    /// `SELECT * FROM busy WHERE resource_id = ... AND start >= ... AND "end" <= ...`.
+    SelectBusy {
+        resource_id: Ulid,
+        start: Ms,
+        end: Ms,
+    },
+    /// `SELECT * FROM utilization WHERE resource_id = ... AND start >= ...
+    /// AND "end" <= ... [AND bucket = ...]`; `descendants_of = ...` instead
+    /// of `resource_id` totals the subtree below that resource.
+    SelectUtilization {
+        resource_id: Ulid,
+        descendants: bool,
+        start: Ms,
+        end: Ms,
+        bucket: Option<Ms>,
+    },
+    InsertCalendar {
+        id: Ulid,
+        name: Option<String>,
+    },
+    DeleteCalendar {
+        id: Ulid,
+    },
+    InsertCalendarRule {
+        id: Ulid,
+        calendar_id: Ulid,
+        start: Ms,
+        end: Ms,
+        blocking: bool,
+    },
+    UpdateCalendarRule {
+        id: Ulid,
+        start: Ms,
+        end: Ms,
+        blocking: bool,
+    },
+    DeleteCalendarRule {
+        id: Ulid,
+    },
+    AttachCalendar {
+        calendar_id: Ulid,
+        resource_id: Ulid,
+    },
+    DetachCalendar {
+        calendar_id: Ulid,
+        resource_id: Ulid,
+    },
+    SelectCalendars,
+    SelectCalendarRules {
+        calendar_id: Ulid,
+    },
+    SelectCalendarAttachments {
+        calendar_id: Option<Ulid>,
+        resource_id: Option<Ulid>,
+    },
+    /// `INSERT INTO exclusion_groups (id, name, max_concurrent, members)`,
+    /// with members as a JSON array of resource ids.
+    InsertGroup {
+        id: Ulid,
+        name: Option<String>,
+        limit: u32,
+        members: Vec<Ulid>,
+    },
+    DeleteGroup {
+        id: Ulid,
+    },
+    SelectGroups,
     Listen {
         channel: String,
     },
@@ -96,6 +269,17 @@ pub enum Command {
         channel: String,
     },
     UnlistenAll,
+    /// `EXPLAIN` of an INSERT, UPDATE or DELETE: run its checks as a dry
+    /// run, without writing anything.
+    Explain {
+        command: Box<Command>,
+    },
+    /// An INSERT, UPDATE or DELETE on resources, rules, holds or bookings
+    /// followed by `RETURNING`. `columns` is `None` for `RETURNING *`.
+    Returning {
+        command: Box<Command>,
+        columns: Option<Vec<String>>,
+    },
 }
 
 pub fn parse_sql(sql: &str) -> Result<Command, SqlError> {
@@ -118,82 +302,178 @@ pub fn parse_sql(sql: &str) -> Result<Command, SqlError> {
         return Err(SqlError::Empty);
     }
 
-    match &stmts[0] {
-        Statement::Insert(insert) => parse_insert(insert),
-        Statement::Delete(delete) => parse_delete(delete),
+    parse_statement(&stmts[0])
+}
+
+fn parse_statement(statement: &Statement) -> Result<Command, SqlError> {
+    match statement {
+        Statement::Explain { statement, analyze: false, .. } => match statement.as_ref() {
+            Statement::Insert(_) | Statement::Update { .. } | Statement::Delete(_) => {
+                Ok(Command::Explain { command: Box::new(parse_statement(statement)?) })
+            }
+            _ => Err(SqlError::Unsupported("EXPLAIN of anything but INSERT, UPDATE or DELETE".into())),
+        },
+        Statement::Insert(insert) => with_returning(parse_insert(insert)?, &insert.returning),
+        Statement::Delete(delete) => with_returning(parse_delete(delete)?, &delete.returning),
         Statement::Query(query) => parse_select(query),
-        Statement::Update { table, assignments, selection, .. } => parse_update(table, assignments, selection),
+        Statement::Update { table, assignments, selection, returning, .. } => {
+            with_returning(parse_update(table, assignments, selection)?, returning)
+        }
         other => Err(SqlError::Unsupported(format!("{other}"))),
     }
 }
 
+/// Table and `RETURNING` columns of a write statement, without evaluating
+/// its values. Used to describe the result of a prepared statement.
+pub fn returning_clause(sql: &str) -> Option<(String, Option<Vec<String>>)> {
+    let stmts = Parser::parse_sql(&PostgreSqlDialect {}, sql).ok()?;
+    let (table, returning) = match stmts.first()? {
+        Statement::Insert(insert) => (insert_table_name(insert).ok()?, insert.returning.as_ref()?),
+        Statement::Delete(delete) => (delete_table_name(delete).ok()?, delete.returning.as_ref()?),
+        Statement::Update { table, returning, .. } => {
+            (table_factor_name(&table.relation).ok()?, returning.as_ref()?)
+        }
+        _ => return None,
+    };
+    Some((table, parse_returning_items(returning).ok()?))
+}
+
+fn with_returning(command: Command, returning: &Option<Vec<ast::SelectItem>>) -> Result<Command, SqlError> {
+    let Some(items) = returning else { return Ok(command) };
+    let supported = matches!(
+        command,
+        Command::InsertResource { .. }
+            | Command::UpdateResource { .. }
+            | Command::DeleteResource { .. }
+            | Command::InsertRule { .. }
+            | Command::UpdateRule { .. }
+            | Command::DeleteRule { .. }
+            | Command::InsertHold { .. }
+            | Command::DeleteHold { .. }
+            | Command::InsertBooking { .. }
+            | Command::BatchInsertBookings { .. }
+            | Command::DeleteBooking { .. }
+            | Command::UpdateBookingStatus { .. }
+    );
+    if !supported {
+        return Err(SqlError::Unsupported("RETURNING on this table".into()));
+    }
+    Ok(Command::Returning { command: Box::new(command), columns: parse_returning_items(items)? })
+}
+
+/// `RETURNING *` is `None`; otherwise plain column names, in order.
+fn parse_returning_items(items: &[ast::SelectItem]) -> Result<Option<Vec<String>>, SqlError> {
+    if let [ast::SelectItem::Wildcard(_)] = items {
+        return Ok(None);
+    }
+    items
+        .iter()
+        .map(|item| match item {
+            ast::SelectItem::UnnamedExpr(expr) => expr_column_name(expr)
+                .ok_or_else(|| SqlError::Unsupported(format!("RETURNING {expr}"))),
+            other => Err(SqlError::Unsupported(format!("RETURNING {other}"))),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Some)
+}
+
 fn parse_insert(insert: &ast::Insert) -> Result<Command, SqlError> {
     let table = insert_table_name(insert)?;
     let values = extract_insert_values(insert)?;
     let columns = extract_column_names(insert);
+    let on_conflict = parse_on_conflict(insert)?;
+    if !columns.is_empty() && columns.len() != values.len() {
+        return Err(SqlError::Parse(format!("{table}: {} columns but {} values", columns.len(), values.len())));
+    }
+    if on_conflict != OnConflict::Error && !matches!(table.as_str(), "resources" | "rules" | "holds" | "bookings") {
+        return Err(SqlError::Unsupported(format!("ON CONFLICT for {table}")));
+    }
 
     match table.as_str() {
         "resources" => {
             if values.is_empty() {
                 return Err(SqlError::WrongArity("resources", 1, 0));
             }
-            // Column-aware parsing: map column name → value index
-            let col_idx = |name: &str| -> Option<usize> {
-                if columns.is_empty() { None } else { columns.iter().position(|c| c == name) }
-            };
+            // Without a column list the order is (id, parent_id, capacity,
+            // buffer_after); everything else must be named.
+            let col = |name: &str| columns.iter().position(|c| c == name).and_then(|i| values.get(i));
 
-            let id = parse_ulid(&values[col_idx("id").unwrap_or(0)])?;
-            let parent_id = col_idx("parent_id").or(if columns.is_empty() && values.len() >= 2 { Some(1) } else { None })
-                .map(|i| parse_ulid_or_null(&values[i]))
+            let id = parse_insert_id(&columns, &values)?;
+            let parent_id = insert_value(&columns, &values, "parent_id", 1)
+                .map(parse_ulid_or_null)
                 .transpose()?
                 .flatten();
-            let name = col_idx("name")
-                .map(|i| parse_string_or_null(&values[i]))
+            let name = col("name").map(parse_string_or_null).transpose()?.flatten();
+            let capacity = insert_value(&columns, &values, "capacity", 2).map(parse_u32).transpose()?.unwrap_or(1);
+            let buffer_after = insert_value(&columns, &values, "buffer_after", 3)
+                .map(parse_i64_or_null)
                 .transpose()?
                 .flatten();
 
-            // For capacity/buffer_after, use column names if present; otherwise use positional
-            // (skipping over name if it wasn't in columns)
-            let capacity = if let Some(i) = col_idx("capacity") {
-                parse_u32(&values[i])?
-            } else if columns.is_empty() {
-                // No columns specified: old positional order (id, parent_id, capacity, buffer_after)
-                if values.len() >= 3 { parse_u32(&values[2])? } else { 1 }
-            } else {
-                1
-            };
-            let buffer_after = if let Some(i) = col_idx("buffer_after") {
-                parse_i64_or_null(&values[i])?
-            } else if columns.is_empty() {
-                if values.len() >= 4 { parse_i64_or_null(&values[3])? } else { None }
-            } else {
-                None
-            };
+            let mut settings = ResourceSettings::default();
+            if let Some(v) = col("inheritance") {
+                settings.inheritance = parse_inheritance(v)?;
+            }
+            if let Some(v) = col("buffer_before") {
+                settings.buffer_before = parse_i64_or_null(v)?;
+            }
+            if let Some(v) = col("granularity_ms") {
+                settings.granularity_ms = parse_i64_or_null(v)?;
+            }
+            if let Some(v) = col("granularity_offset_ms") {
+                settings.granularity_offset_ms = parse_i64(v)?;
+            }
+            for (col, value) in columns.iter().zip(&values) {
+                if let Some(field) = policy_field(&mut settings.policy, col) {
+                    *field = parse_i64_or_null(value)?;
+                }
+            }
+            settings.quota = parse_quota(col)?;
+            if let Some(v) = col("components") {
+                settings.components = parse_component_list(v)?;
+            }
+            if let Some(v) = col("position") {
+                settings.position = parse_i64_or_null(v)?;
+            }
 
-            Ok(Command::InsertResource { id, parent_id, name, capacity, buffer_after })
+            Ok(Command::InsertResource { id, parent_id, name, capacity, buffer_after, settings, on_conflict })
         }
         "rules" => {
-            if values.len() < 5 {
+            if columns.is_empty() && values.len() < 5 {
                 return Err(SqlError::WrongArity("rules", 5, values.len()));
             }
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            let blocking = parse_bool(value("blocking", 4)?)?;
+            let capacity = insert_value(&columns, &values, "capacity", 5)
+                .map(parse_u32_or_null)
+                .transpose()?
+                .flatten();
+            if blocking && capacity.is_some() {
+                return Err(SqlError::Parse("capacity rules cannot be blocking".into()));
+            }
             Ok(Command::InsertRule {
-                id: parse_ulid(&values[0])?,
-                resource_id: parse_ulid(&values[1])?,
-                start: parse_i64(&values[2])?,
-                end: parse_i64(&values[3])?,
-                blocking: parse_bool(&values[4])?,
+                id: parse_insert_id(&columns, &values)?,
+                resource_id: parse_ulid(value("resource_id", 1)?)?,
+                start: parse_i64(value("start", 2)?)?,
+                end: parse_i64(value("end", 3)?)?,
+                blocking,
+                capacity,
+                on_conflict,
             })
         }
         "holds" => {
-            if values.len() < 5 {
+            if columns.is_empty() && values.len() < 5 {
                 return Err(SqlError::WrongArity("holds", 5, values.len()));
             }
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
             Ok(Command::InsertHold {
-                id: parse_ulid(&values[0])?,
-                resource_id: parse_ulid(&values[1])?,
-                start: parse_i64(&values[2])?,
-                end: parse_i64(&values[3])?,
-                expires_at: parse_i64(&values[4])?,
+                id: parse_insert_id(&columns, &values)?,
+                resource_id: parse_ulid(value("resource_id", 1)?)?,
+                start: parse_i64(value("start", 2)?)?,
+                end: parse_i64(value("end", 3)?)?,
+                expires_at: parse_i64(value("expires_at", 4)?)?,
+                options: parse_alloc_options(&columns, &values)?,
+                on_conflict,
             })
         }
         "bookings" => {
@@ -202,40 +482,180 @@ fn parse_insert(insert: &ast::Insert) -> Result<Command, SqlError> {
 
             if all_rows.len() == 1 {
                 let values = &all_rows[0];
-                if values.len() < 4 {
+                if columns.is_empty() && values.len() < 4 {
                     return Err(SqlError::WrongArity("bookings", 4, values.len()));
                 }
+                let value = |name: &'static str, pos: usize| required_insert_value(&columns, values, name, pos);
                 let label = label_idx
                     .map(|i| parse_string_or_null(&values[i]))
                     .transpose()?
                     .flatten();
+                let suggest = columns
+                    .iter()
+                    .position(|c| c == "suggest")
+                    .map(|i| parse_u32_or_null(&values[i]))
+                    .transpose()?
+                    .flatten()
+                    .unwrap_or(0) as usize;
+                if suggest > MAX_ALTERNATIVES {
+                    return Err(SqlError::Parse(format!("suggest too large: {suggest} (max {MAX_ALTERNATIVES})")));
+                }
                 Ok(Command::InsertBooking {
-                    id: parse_ulid(&values[0])?,
-                    resource_id: parse_ulid(&values[1])?,
-                    start: parse_i64(&values[2])?,
-                    end: parse_i64(&values[3])?,
+                    id: parse_insert_id(&columns, values)?,
+                    resource_id: parse_ulid(value("resource_id", 1)?)?,
+                    start: parse_i64(value("start", 2)?)?,
+                    end: parse_i64(value("end", 3)?)?,
                     label,
+                    options: parse_alloc_options(&columns, values)?,
+                    on_conflict,
+                    suggest,
                 })
             } else {
+                if columns.iter().any(|c| c == "suggest") {
+                    return Err(SqlError::Unsupported("suggest on a multi-row booking insert".into()));
+                }
                 let mut bookings = Vec::with_capacity(all_rows.len());
                 for (i, row) in all_rows.iter().enumerate() {
-                    if row.len() < 4 {
+                    if columns.is_empty() && row.len() < 4 {
                         return Err(SqlError::WrongArity("bookings row", 4, row.len()));
                     }
+                    if !columns.is_empty() && columns.len() != row.len() {
+                        return Err(SqlError::Parse(format!(
+                            "row {i}: {} columns but {} values",
+                            columns.len(),
+                            row.len()
+                        )));
+                    }
+                    let in_row = |e: SqlError| SqlError::Parse(format!("row {i}: {e}"));
+                    let value = |name: &'static str, pos: usize| {
+                        required_insert_value(&columns, row, name, pos).map_err(in_row)
+                    };
                     let label = label_idx
-                        .map(|j| parse_string_or_null(&row[j]).map_err(|e| SqlError::Parse(format!("row {i}: {e}"))))
+                        .map(|j| parse_string_or_null(&row[j]).map_err(in_row))
                         .transpose()?
                         .flatten();
                     bookings.push((
-                        parse_ulid(&row[0]).map_err(|e| SqlError::Parse(format!("row {i}: {e}")))?,
-                        parse_ulid(&row[1]).map_err(|e| SqlError::Parse(format!("row {i}: {e}")))?,
-                        parse_i64(&row[2]).map_err(|e| SqlError::Parse(format!("row {i}: {e}")))?,
-                        parse_i64(&row[3]).map_err(|e| SqlError::Parse(format!("row {i}: {e}")))?,
+                        parse_insert_id(&columns, row).map_err(in_row)?,
+                        parse_ulid(value("resource_id", 1)?).map_err(in_row)?,
+                        parse_i64(value("start", 2)?).map_err(in_row)?,
+                        parse_i64(value("end", 3)?).map_err(in_row)?,
                         label,
+                        parse_alloc_options(&columns, row).map_err(in_row)?,
                     ));
                 }
-                Ok(Command::BatchInsertBookings { bookings })
+                Ok(Command::BatchInsertBookings { bookings, on_conflict })
+            }
+        }
+        "rule_sets" => {
+            if columns.is_empty() && values.len() < 4 {
+                return Err(SqlError::WrongArity("rule_sets", 4, values.len()));
             }
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            let rules = parse_string_or_null(value("rules", 3)?)?
+                .ok_or_else(|| SqlError::Parse("rules must not be NULL".into()))?;
+            Ok(Command::ReplaceRules {
+                resource_id: parse_ulid(value("resource_id", 0)?)?,
+                start: parse_i64(value("start", 1)?)?,
+                end: parse_i64(value("end", 2)?)?,
+                rules: parse_rule_list(&rules)?,
+            })
+        }
+        "resource_clones" => {
+            let source_id = parse_ulid(required_insert_value(&columns, &values, "source_id", 0)?)?;
+            let parent_id = match insert_value(&columns, &values, "parent_id", 1) {
+                Some(expr) => parse_ulid_or_null(expr)?,
+                None => None,
+            };
+            let include_rules = match insert_value(&columns, &values, "include_rules", 2) {
+                Some(expr) => parse_bool(expr)?,
+                None => false,
+            };
+            Ok(Command::CloneSubtree { source_id, parent_id, include_rules })
+        }
+        "requirement_bookings" => {
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            let label = match insert_value(&columns, &values, "label", 3) {
+                Some(expr) => parse_string_or_null(expr)?,
+                None => None,
+            };
+            Ok(Command::BookRequirements {
+                requirements: parse_requirements(value("requirements", 0)?)?,
+                start: parse_i64(value("start", 1)?)?,
+                end: parse_i64(value("end", 2)?)?,
+                label,
+            })
+        }
+        "itinerary_bookings" => {
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            let label = match insert_value(&columns, &values, "label", 3) {
+                Some(expr) => parse_string_or_null(expr)?,
+                None => None,
+            };
+            let steps = parse_string_or_null(value("steps", 0)?)?.ok_or(SqlError::MissingColumn("steps"))?;
+            Ok(Command::BookItinerary {
+                steps: serde_json::from_str(&steps).map_err(|e| SqlError::Parse(format!("bad steps JSON: {e}")))?,
+                start: parse_i64(value("start", 1)?)?,
+                end: parse_i64(value("end", 2)?)?,
+                label,
+            })
+        }
+        "block_bookings" => {
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            let label = match insert_value(&columns, &values, "label", 4) {
+                Some(expr) => parse_string_or_null(expr)?,
+                None => None,
+            };
+            Ok(Command::BookBlock {
+                parent_id: parse_ulid(value("parent_id", 0)?)?,
+                count: parse_u32(value("count", 1)?)? as usize,
+                start: parse_i64(value("start", 2)?)?,
+                end: parse_i64(value("end", 3)?)?,
+                label,
+            })
+        }
+        "exclusion_groups" => {
+            let id = parse_insert_id(&columns, &values)?;
+            let name = match insert_value(&columns, &values, "name", 1) {
+                Some(expr) => parse_string_or_null(expr)?,
+                None => None,
+            };
+            let limit = parse_u32(required_insert_value(&columns, &values, "max_concurrent", 2)?)?;
+            let members = parse_component_list(required_insert_value(&columns, &values, "members", 3)?)?;
+            Ok(Command::InsertGroup { id, name, limit, members })
+        }
+        "calendars" => {
+            if values.is_empty() {
+                return Err(SqlError::WrongArity("calendars", 1, 0));
+            }
+            let id = parse_insert_id(&columns, &values)?;
+            let name = insert_value(&columns, &values, "name", 1)
+                .map(parse_string_or_null)
+                .transpose()?
+                .flatten();
+            Ok(Command::InsertCalendar { id, name })
+        }
+        "calendar_rules" => {
+            if columns.is_empty() && values.len() < 5 {
+                return Err(SqlError::WrongArity("calendar_rules", 5, values.len()));
+            }
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            Ok(Command::InsertCalendarRule {
+                id: parse_insert_id(&columns, &values)?,
+                calendar_id: parse_ulid(value("calendar_id", 1)?)?,
+                start: parse_i64(value("start", 2)?)?,
+                end: parse_i64(value("end", 3)?)?,
+                blocking: parse_bool(value("blocking", 4)?)?,
+            })
+        }
+        "calendar_attachments" => {
+            if values.len() < 2 {
+                return Err(SqlError::WrongArity("calendar_attachments", 2, values.len()));
+            }
+            let value = |name: &'static str, pos: usize| required_insert_value(&columns, &values, name, pos);
+            Ok(Command::AttachCalendar {
+                calendar_id: parse_ulid(value("calendar_id", 0)?)?,
+                resource_id: parse_ulid(value("resource_id", 1)?)?,
+            })
         }
         _ => Err(SqlError::UnknownTable(table)),
     }
@@ -243,6 +663,23 @@ fn parse_insert(insert: &ast::Insert) -> Result<Command, SqlError> {
 
 fn parse_delete(delete: &ast::Delete) -> Result<Command, SqlError> {
     let table = delete_table_name(delete)?;
+    if table == "calendar_attachments" {
+        return Ok(Command::DetachCalendar {
+            calendar_id: extract_ulid_filter(&delete.selection, "calendar_id")?,
+            resource_id: extract_ulid_filter(&delete.selection, "resource_id")?,
+        });
+    }
+    let class = match table.as_str() {
+        "rules" => Some(IntervalClass::Rules),
+        "holds" => Some(IntervalClass::Holds),
+        "bookings" => Some(IntervalClass::Bookings),
+        _ => None,
+    };
+    if let Some(class) = class
+        && let Some(selection) = &delete.selection
+        && extract_where_id(&delete.selection).is_err() {
+            return parse_range_delete(class, selection);
+        }
     let id = extract_where_id(&delete.selection)?;
 
     match table.as_str() {
@@ -250,10 +687,95 @@ fn parse_delete(delete: &ast::Delete) -> Result<Command, SqlError> {
         "rules" => Ok(Command::DeleteRule { id }),
         "holds" => Ok(Command::DeleteHold { id }),
         "bookings" => Ok(Command::DeleteBooking { id }),
+        "calendars" => Ok(Command::DeleteCalendar { id }),
+        "calendar_rules" => Ok(Command::DeleteCalendarRule { id }),
+        "exclusion_groups" => Ok(Command::DeleteGroup { id }),
         _ => Err(SqlError::UnknownTable(table)),
     }
 }
 
+/// A bulk delete must name its resource and both bounds; any other
+/// predicate is rejected rather than ignored.
+fn parse_range_delete(class: IntervalClass, selection: &Expr) -> Result<Command, SqlError> {
+    let mut preds = Vec::new();
+    flatten_and(selection, &mut preds);
+    let (mut resource_id, mut subtree_root, mut start, mut end) = (None, None, None, None);
+    for pred in preds {
+        let Expr::BinaryOp { left, op, right } = pred else {
+            return Err(SqlError::Unsupported(format!("DELETE ... WHERE {pred}")));
+        };
+        match (expr_column_name(left).as_deref(), op) {
+            (Some("resource_id"), ast::BinaryOperator::Eq) => resource_id = Some(parse_ulid_expr(right)?),
+            (Some("descendants_of"), ast::BinaryOperator::Eq) => subtree_root = Some(parse_ulid_expr(right)?),
+            (Some("start"), ast::BinaryOperator::GtEq) => start = Some(parse_i64_expr(right)?),
+            (Some("end"), ast::BinaryOperator::LtEq) => end = Some(parse_i64_expr(right)?),
+            _ => return Err(SqlError::Unsupported(format!("DELETE ... WHERE {pred}"))),
+        }
+    }
+    let (resource_id, descendants) = match (resource_id, subtree_root) {
+        (Some(id), None) => (id, false),
+        (None, Some(id)) => (id, true),
+        (Some(_), Some(_)) => {
+            return Err(SqlError::Parse("use resource_id or descendants_of, not both".into()));
+        }
+        (None, None) => return Err(SqlError::MissingFilter("resource_id")),
+    };
+    Ok(Command::DeleteInRange {
+        class,
+        resource_id,
+        descendants,
+        start: start.ok_or(SqlError::MissingFilter("start"))?,
+        end: end.ok_or(SqlError::MissingFilter("end"))?,
+    })
+}
+
+/// Like a bulk delete, a utilization query rejects predicates it does not
+/// understand rather than ignoring them.
+fn parse_utilization(selection: &Expr) -> Result<Command, SqlError> {
+    let mut preds = Vec::new();
+    flatten_and(selection, &mut preds);
+    let (mut resource_id, mut subtree_root, mut start, mut end, mut bucket) = (None, None, None, None, None);
+    for pred in preds {
+        let Expr::BinaryOp { left, op, right } = pred else {
+            return Err(SqlError::Unsupported(format!("utilization WHERE {pred}")));
+        };
+        match (expr_column_name(left).as_deref(), op) {
+            (Some("resource_id"), ast::BinaryOperator::Eq) => resource_id = Some(parse_ulid_expr(right)?),
+            (Some("descendants_of"), ast::BinaryOperator::Eq) => subtree_root = Some(parse_ulid_expr(right)?),
+            (Some("start"), ast::BinaryOperator::GtEq) => start = Some(parse_i64_expr(right)?),
+            (Some("end"), ast::BinaryOperator::LtEq) => end = Some(parse_i64_expr(right)?),
+            (Some("bucket"), ast::BinaryOperator::Eq) => bucket = Some(parse_i64_expr(right)?),
+            _ => return Err(SqlError::Unsupported(format!("utilization WHERE {pred}"))),
+        }
+    }
+    let (resource_id, descendants) = match (resource_id, subtree_root) {
+        (Some(id), None) => (id, false),
+        (None, Some(id)) => (id, true),
+        (Some(_), Some(_)) => {
+            return Err(SqlError::Parse("use resource_id or descendants_of, not both".into()));
+        }
+        (None, None) => return Err(SqlError::MissingFilter("resource_id")),
+    };
+    Ok(Command::SelectUtilization {
+        resource_id,
+        descendants,
+        start: start.ok_or(SqlError::MissingFilter("start"))?,
+        end: end.ok_or(SqlError::MissingFilter("end"))?,
+        bucket,
+    })
+}
+
+fn flatten_and<'a>(expr: &'a Expr, out: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::BinaryOp { left, op: ast::BinaryOperator::And, right } => {
+            flatten_and(left, out);
+            flatten_and(right, out);
+        }
+        Expr::Nested(inner) => flatten_and(inner, out),
+        other => out.push(other),
+    }
+}
+
 fn parse_select(query: &ast::Query) -> Result<Command, SqlError> {
     let select = match query.body.as_ref() {
         SetExpr::Select(s) => s,
@@ -275,6 +797,14 @@ fn parse_select(query: &ast::Query) -> Result<Command, SqlError> {
             let start = filters.start.ok_or(SqlError::MissingFilter("start"))?;
             let end = filters.end.ok_or(SqlError::MissingFilter("end"))?;
 
+            if let Some(requirements) = filters.requirements {
+                return Ok(Command::SelectRequirementAvailability {
+                    requirements,
+                    start,
+                    end,
+                    min_duration: filters.min_duration,
+                });
+            }
             if !filters.resource_ids.is_empty() {
                 let count = filters.resource_ids.len();
                 Ok(Command::SelectMultiAvailability {
@@ -293,6 +823,21 @@ fn parse_select(query: &ast::Query) -> Result<Command, SqlError> {
                 })
             }
         }
+        "busy" => {
+            let mut filters = AvailabilityFilters::default();
+            if let Some(selection) = &select.selection {
+                extract_availability_filters(selection, &mut filters)?;
+            }
+            Ok(Command::SelectBusy {
+                resource_id: filters.resource_id.ok_or(SqlError::MissingFilter("resource_id"))?,
+                start: filters.start.ok_or(SqlError::MissingFilter("start"))?,
+                end: filters.end.ok_or(SqlError::MissingFilter("end"))?,
+            })
+        }
+        "utilization" => {
+            let selection = select.selection.as_ref().ok_or(SqlError::MissingFilter("resource_id"))?;
+            parse_utilization(selection)
+        }
         "resources" => {
             // Optional: WHERE parent_id = 'X' or WHERE parent_id IS NULL
             let parent_id = if let Some(selection) = &select.selection {
@@ -303,17 +848,60 @@ fn parse_select(query: &ast::Query) -> Result<Command, SqlError> {
             Ok(Command::SelectResources { parent_id })
         }
         "rules" => {
+            if let Some(ids) = extract_id_list_filter(&select.selection)? {
+                return Ok(Command::SelectRulesById { ids });
+            }
             let resource_id = extract_resource_id_filter(&select.selection)?;
             Ok(Command::SelectRules { resource_id })
         }
         "bookings" => {
+            let status = extract_string_filter(&select.selection, "status")?
+                .map(|s| parse_booking_status(&s))
+                .transpose()?;
+            let filter = BookingFilter {
+                status,
+                metadata: extract_metadata_filters(&select.selection)?,
+            };
+            if let Some(ids) = extract_id_list_filter(&select.selection)? {
+                return Ok(Command::SelectBookingsById { ids, filter });
+            }
+            if let Some(owner) = extract_string_filter(&select.selection, "owner")? {
+                let resource_id = extract_resource_id_filter(&select.selection).ok();
+                return Ok(Command::SelectBookingsByOwner { owner, resource_id, filter });
+            }
+            if let Some(status) = status
+                && extract_resource_id_filter(&select.selection).is_err()
+            {
+                return Ok(Command::SelectBookingsByStatus { status, filter });
+            }
             let resource_id = extract_resource_id_filter(&select.selection)?;
-            Ok(Command::SelectBookings { resource_id })
+            Ok(Command::SelectBookings { resource_id, filter })
         }
         "holds" => {
+            if let Some(ids) = extract_id_list_filter(&select.selection)? {
+                return Ok(Command::SelectHoldsById { ids });
+            }
+            if let Some(owner) = extract_string_filter(&select.selection, "owner")? {
+                let resource_id = extract_resource_id_filter(&select.selection).ok();
+                return Ok(Command::SelectHoldsByOwner { owner, resource_id });
+            }
             let resource_id = extract_resource_id_filter(&select.selection)?;
             Ok(Command::SelectHolds { resource_id })
         }
+        "calendars" => Ok(Command::SelectCalendars),
+        "exclusion_groups" => Ok(Command::SelectGroups),
+        "calendar_rules" => {
+            let calendar_id = extract_ulid_filter(&select.selection, "calendar_id")?;
+            Ok(Command::SelectCalendarRules { calendar_id })
+        }
+        "calendar_attachments" => {
+            let calendar_id = extract_ulid_filter(&select.selection, "calendar_id").ok();
+            let resource_id = extract_ulid_filter(&select.selection, "resource_id").ok();
+            if calendar_id.is_none() && resource_id.is_none() {
+                return Err(SqlError::MissingFilter("calendar_id or resource_id"));
+            }
+            Ok(Command::SelectCalendarAttachments { calendar_id, resource_id })
+        }
         _ => Err(SqlError::UnknownTable(table)),
     }
 }
@@ -326,6 +914,7 @@ struct AvailabilityFilters {
     end: Option<Ms>,
     min_duration: Option<Ms>,
     min_available: Option<usize>,
+    requirements: Option<Vec<Requirement>>,
 }
 
 fn extract_availability_filters(
@@ -347,33 +936,31 @@ fn extract_availability_filters(
                 } else if col.as_deref() == Some("min_available") {
                     let v = parse_i64_expr(right)?;
                     f.min_available = Some(v as usize);
+                } else if col.as_deref() == Some("requirements") {
+                    f.requirements = Some(parse_requirements(right)?);
                 }
             }
-            ast::BinaryOperator::GtEq => {
-                if expr_column_name(left).as_deref() == Some("start") {
-                    f.start = Some(parse_i64_expr(right)?);
-                }
+            ast::BinaryOperator::GtEq if expr_column_name(left).as_deref() == Some("start") => {
+                f.start = Some(parse_i64_expr(right)?);
             }
-            ast::BinaryOperator::LtEq => {
-                if expr_column_name(left).as_deref() == Some("end") {
-                    f.end = Some(parse_i64_expr(right)?);
-                }
+            ast::BinaryOperator::LtEq if expr_column_name(left).as_deref() == Some("end") => {
+                f.end = Some(parse_i64_expr(right)?);
             }
             _ => {}
         },
         // resource_id IN ('id1', 'id2', ...)
-        Expr::InList { expr: col_expr, list, negated } if !negated => {
-            if expr_column_name(col_expr).as_deref() == Some("resource_id") {
-                if list.len() > MAX_IN_CLAUSE_IDS {
-                    return Err(SqlError::Parse(format!(
-                        "IN clause too large: {} IDs (max {})",
-                        list.len(),
-                        MAX_IN_CLAUSE_IDS
-                    )));
-                }
-                for item in list {
-                    f.resource_ids.push(parse_ulid_expr(item)?);
-                }
+        Expr::InList { expr: col_expr, list, negated }
+            if !negated && expr_column_name(col_expr).as_deref() == Some("resource_id") =>
+        {
+            if list.len() > MAX_IN_CLAUSE_IDS {
+                return Err(SqlError::Parse(format!(
+                    "IN clause too large: {} IDs (max {})",
+                    list.len(),
+                    MAX_IN_CLAUSE_IDS
+                )));
+            }
+            for item in list {
+                f.resource_ids.push(parse_ulid_expr(item)?);
             }
         }
         _ => {}
@@ -394,28 +981,49 @@ fn parse_update(
             let mut name: Option<String> = None;
             let mut capacity: Option<u32> = None;
             let mut buffer_after: Option<Option<Ms>> = None;
+            let mut settings = SettingsPatch::default();
 
+            let mut assigned = Vec::new();
             for a in assignments {
                 let col = assignment_column_name(a)?;
+                assigned.push((col.clone(), &a.value));
                 match col.as_str() {
                     "name" => name = parse_string_or_null(&a.value)?,
                     "capacity" => capacity = Some(parse_u32(&a.value)?),
                     "buffer_after" => buffer_after = Some(parse_i64_or_null(&a.value)?),
-                    _ => {}
+                    "inheritance" => settings.inheritance = Some(parse_inheritance(&a.value)?),
+                    "buffer_before" => settings.buffer_before = Some(parse_i64_or_null(&a.value)?),
+                    "granularity_ms" => settings.granularity_ms = Some(parse_i64_or_null(&a.value)?),
+                    "granularity_offset_ms" => settings.granularity_offset_ms = Some(parse_i64(&a.value)?),
+                    "position" => settings.position = Some(parse_i64_or_null(&a.value)?),
+                    other => {
+                        if let Some(field) = policy_patch_field(&mut settings, other) {
+                            *field = Some(parse_i64_or_null(&a.value)?);
+                        }
+                    }
                 }
             }
 
+            // Any quota column replaces the whole quota.
+            if assigned.iter().any(|(c, _)| c.starts_with("quota_")) {
+                settings.quota = Some(parse_quota(|name| {
+                    assigned.iter().find(|(c, _)| c == name).map(|(_, v)| *v)
+                })?);
+            }
+
             Ok(Command::UpdateResource {
                 id,
                 name,
                 capacity: capacity.unwrap_or(1),
                 buffer_after: buffer_after.unwrap_or(None),
+                settings,
             })
         }
-        "rules" => {
+        "rules" | "calendar_rules" => {
             let mut start: Option<Ms> = None;
             let mut end: Option<Ms> = None;
             let mut blocking: Option<bool> = None;
+            let mut capacity: Option<u32> = None;
 
             for a in assignments {
                 let col = assignment_column_name(a)?;
@@ -423,16 +1031,52 @@ fn parse_update(
                     "start" => start = Some(parse_i64_expr(&a.value)?),
                     "end" => end = Some(parse_i64_expr(&a.value)?),
                     "blocking" => blocking = Some(parse_bool(&a.value)?),
+                    "capacity" => capacity = parse_u32_or_null(&a.value)?,
                     _ => {}
                 }
             }
 
-            Ok(Command::UpdateRule {
-                id,
-                start: start.ok_or(SqlError::MissingFilter("start"))?,
-                end: end.ok_or(SqlError::MissingFilter("end"))?,
-                blocking: blocking.ok_or(SqlError::MissingFilter("blocking"))?,
-            })
+            let start = start.ok_or(SqlError::MissingFilter("start"))?;
+            let end = end.ok_or(SqlError::MissingFilter("end"))?;
+            // Capacity rules neither open nor close time, so blocking is optional for them.
+            let blocking = match (blocking, capacity) {
+                (Some(true), Some(_)) => {
+                    return Err(SqlError::Parse("capacity rules cannot be blocking".into()));
+                }
+                (Some(b), _) => b,
+                (None, Some(_)) => false,
+                (None, None) => return Err(SqlError::MissingFilter("blocking")),
+            };
+            if table_name == "rules" {
+                Ok(Command::UpdateRule { id, start, end, blocking, capacity })
+            } else if capacity.is_some() {
+                Err(SqlError::Unsupported("capacity on calendar rules".into()))
+            } else {
+                Ok(Command::UpdateCalendarRule { id, start, end, blocking })
+            }
+        }
+        "bookings" => {
+            let mut status: Option<BookingStatus> = None;
+            let mut reason: Option<String> = None;
+
+            for a in assignments {
+                let col = assignment_column_name(a)?;
+                match col.as_str() {
+                    "status" => {
+                        let s = parse_string_or_null(&a.value)?
+                            .ok_or_else(|| SqlError::Parse("status must not be NULL".into()))?;
+                        status = Some(parse_booking_status(&s)?);
+                    }
+                    "cancel_reason" => reason = parse_string_or_null(&a.value)?,
+                    other => return Err(SqlError::Unsupported(format!("UPDATE bookings SET {other}"))),
+                }
+            }
+
+            let status = status.ok_or(SqlError::MissingFilter("status"))?;
+            if reason.is_some() && status != BookingStatus::Cancelled {
+                return Err(SqlError::Parse("cancel_reason requires status = 'cancelled'".into()));
+            }
+            Ok(Command::UpdateBookingStatus { id, status, reason })
         }
         _ => Err(SqlError::Unsupported(format!("UPDATE {table_name}"))),
     }
@@ -448,30 +1092,140 @@ fn assignment_column_name(a: &ast::Assignment) -> Result<String, SqlError> {
 }
 
 fn extract_resource_id_filter(selection: &Option<Expr>) -> Result<Ulid, SqlError> {
-    let sel = selection.as_ref().ok_or(SqlError::MissingFilter("resource_id"))?;
+    extract_ulid_filter(selection, "resource_id")
+}
+
+/// Find `column = 'ULID'` in the WHERE clause, descending through ANDs.
+fn extract_ulid_filter(selection: &Option<Expr>, column: &'static str) -> Result<Ulid, SqlError> {
+    let sel = selection.as_ref().ok_or(SqlError::MissingFilter(column))?;
     match sel {
         Expr::BinaryOp {
             left,
             op: ast::BinaryOperator::Eq,
             right,
         } => {
-            if expr_column_name(left).as_deref() == Some("resource_id") {
+            if expr_column_name(left).as_deref() == Some(column) {
                 parse_ulid_expr(right)
             } else {
-                Err(SqlError::MissingFilter("resource_id"))
+                Err(SqlError::MissingFilter(column))
             }
         }
-        // Handle AND expressions — find resource_id = X within ANDs
+        // Handle AND expressions — find column = X within ANDs
         Expr::BinaryOp {
             left,
             op: ast::BinaryOperator::And,
             right,
         } => {
-            extract_resource_id_filter(&Some(*left.clone()))
-                .or_else(|_| extract_resource_id_filter(&Some(*right.clone())))
+            extract_ulid_filter(&Some(*left.clone()), column)
+                .or_else(|_| extract_ulid_filter(&Some(*right.clone()), column))
+        }
+        _ => Err(SqlError::MissingFilter(column)),
+    }
+}
+
+/// Find `id = 'ULID'` or `id IN ('ULID', ...)` in the WHERE clause,
+/// descending through ANDs.
+fn extract_id_list_filter(selection: &Option<Expr>) -> Result<Option<Vec<Ulid>>, SqlError> {
+    match selection {
+        Some(Expr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::Eq,
+            right,
+        }) if expr_column_name(left).as_deref() == Some("id") => Ok(Some(vec![parse_ulid_expr(right)?])),
+        Some(Expr::InList { expr, list, negated })
+            if !negated && expr_column_name(expr).as_deref() == Some("id") =>
+        {
+            if list.len() > MAX_IN_CLAUSE_IDS {
+                return Err(SqlError::Parse(format!(
+                    "IN clause too large: {} IDs (max {})",
+                    list.len(),
+                    MAX_IN_CLAUSE_IDS
+                )));
+            }
+            list.iter().map(parse_ulid_expr).collect::<Result<_, _>>().map(Some)
+        }
+        Some(Expr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::And,
+            right,
+        }) => match extract_id_list_filter(&Some(*left.clone()))? {
+            Some(ids) => Ok(Some(ids)),
+            None => extract_id_list_filter(&Some(*right.clone())),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Find `column = 'text'` in the WHERE clause, descending through ANDs.
+fn extract_string_filter(selection: &Option<Expr>, column: &str) -> Result<Option<String>, SqlError> {
+    match selection {
+        Some(Expr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::Eq,
+            right,
+        }) if expr_column_name(left).as_deref() == Some(column) => {
+            parse_string_or_null(right)?
+                .map(Some)
+                .ok_or_else(|| SqlError::Parse(format!("{column} must not be NULL")))
         }
-        _ => Err(SqlError::MissingFilter("resource_id")),
+        Some(Expr::BinaryOp {
+            left,
+            op: ast::BinaryOperator::And,
+            right,
+        }) => match extract_string_filter(&Some(*left.clone()), column)? {
+            Some(v) => Ok(Some(v)),
+            None => extract_string_filter(&Some(*right.clone()), column),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// Collect `metadata->>'key' = value` and `metadata @> '{...}'` predicates
+/// from the WHERE clause, descending through ANDs.
+fn extract_metadata_filters(selection: &Option<Expr>) -> Result<Vec<MetadataFilter>, SqlError> {
+    let mut filters = Vec::new();
+    if let Some(expr) = selection {
+        collect_metadata_filters(expr, &mut filters)?;
     }
+    Ok(filters)
+}
+
+fn collect_metadata_filters(expr: &Expr, filters: &mut Vec<MetadataFilter>) -> Result<(), SqlError> {
+    let Expr::BinaryOp { left, op, right } = expr else { return Ok(()) };
+    match op {
+        ast::BinaryOperator::And => {
+            collect_metadata_filters(left, filters)?;
+            collect_metadata_filters(right, filters)?;
+        }
+        ast::BinaryOperator::Eq => {
+            if let Expr::BinaryOp {
+                left: column,
+                op: ast::BinaryOperator::LongArrow,
+                right: key,
+            } = left.as_ref()
+                && expr_column_name(column).as_deref() == Some("metadata")
+            {
+                let key = parse_string_or_null(key)?
+                    .ok_or_else(|| SqlError::Parse("metadata key must not be NULL".into()))?;
+                let value = match extract_value(right) {
+                    Some(Value::SingleQuotedString(s)) => s.clone(),
+                    Some(Value::Number(n, _)) => n.clone(),
+                    Some(Value::Boolean(b)) => b.to_string(),
+                    _ => return Err(SqlError::Parse(format!("expected value, got {right:?}"))),
+                };
+                filters.push(MetadataFilter::KeyEquals { key, value });
+            }
+        }
+        ast::BinaryOperator::AtArrow if expr_column_name(left).as_deref() == Some("metadata") => {
+            let text = parse_string_or_null(right)?
+                .ok_or_else(|| SqlError::Parse("metadata must not be NULL".into()))?;
+            let needle = serde_json::from_str(&text)
+                .map_err(|e| SqlError::Parse(format!("bad metadata JSON: {e}")))?;
+            filters.push(MetadataFilter::Contains(needle));
+        }
+        _ => {}
+    }
+    Ok(())
 }
 
 fn extract_parent_id_filter(selection: &Expr) -> Result<Option<Ulid>, SqlError> {
@@ -536,6 +1290,31 @@ fn table_factor_name(tf: &TableFactor) -> Result<String, SqlError> {
     }
 }
 
+/// The value for `name`: by column name when the INSERT lists its columns,
+/// by position otherwise.
+fn insert_value<'a>(columns: &[String], values: &'a [Expr], name: &str, pos: usize) -> Option<&'a Expr> {
+    if columns.is_empty() {
+        values.get(pos)
+    } else {
+        columns.iter().position(|c| c == name).and_then(|i| values.get(i))
+    }
+}
+
+fn required_insert_value<'a>(
+    columns: &[String],
+    values: &'a [Expr],
+    name: &'static str,
+    pos: usize,
+) -> Result<&'a Expr, SqlError> {
+    insert_value(columns, values, name, pos).ok_or(SqlError::MissingColumn(name))
+}
+
+/// The row id, minted here when the `id` column is left out or NULL.
+fn parse_insert_id(columns: &[String], values: &[Expr]) -> Result<Ulid, SqlError> {
+    let id = insert_value(columns, values, "id", 0).map(parse_ulid_or_null).transpose()?.flatten();
+    Ok(id.unwrap_or_else(Ulid::new))
+}
+
 fn extract_column_names(insert: &ast::Insert) -> Vec<String> {
     insert.columns.iter().map(|c| c.value.to_lowercase()).collect()
 }
@@ -671,6 +1450,13 @@ fn parse_u32(expr: &Expr) -> Result<u32, SqlError> {
     u32::try_from(v).map_err(|_| SqlError::Parse(format!("{v} out of u32 range")))
 }
 
+fn parse_u32_or_null(expr: &Expr) -> Result<Option<u32>, SqlError> {
+    match extract_value(expr) {
+        Some(Value::Null) => Ok(None),
+        _ => Ok(Some(parse_u32(expr)?)),
+    }
+}
+
 fn parse_string_or_null(expr: &Expr) -> Result<Option<String>, SqlError> {
     if let Some(value) = extract_value(expr) {
         match value {
@@ -698,6 +1484,154 @@ fn parse_i64(expr: &Expr) -> Result<i64, SqlError> {
     parse_i64_expr(expr)
 }
 
+/// The booking policy field stored in resource column `col`, if any.
+fn policy_field<'a>(policy: &'a mut BookingPolicy, col: &str) -> Option<&'a mut Option<Ms>> {
+    match col {
+        "min_duration" => Some(&mut policy.min_duration),
+        "max_duration" => Some(&mut policy.max_duration),
+        "min_lead_time" => Some(&mut policy.min_lead_time),
+        "max_horizon" => Some(&mut policy.max_horizon),
+        _ => None,
+    }
+}
+
+/// Like `policy_field`, for the policy fields of an update.
+fn policy_patch_field<'a>(patch: &'a mut SettingsPatch, col: &str) -> Option<&'a mut Option<Option<Ms>>> {
+    match col {
+        "min_duration" => Some(&mut patch.min_duration),
+        "max_duration" => Some(&mut patch.max_duration),
+        "min_lead_time" => Some(&mut patch.min_lead_time),
+        "max_horizon" => Some(&mut patch.max_horizon),
+        _ => None,
+    }
+}
+
+/// Owner quota from the `quota_window_ms`, `quota_max_count` and
+/// `quota_max_duration_ms` resource columns; no window means no quota.
+fn parse_quota<'a>(col: impl Fn(&str) -> Option<&'a Expr>) -> Result<Option<OwnerQuota>, SqlError> {
+    let window_ms = col("quota_window_ms").map(parse_i64_or_null).transpose()?.flatten();
+    let max_count = col("quota_max_count").map(parse_u32_or_null).transpose()?.flatten();
+    let max_duration_ms = col("quota_max_duration_ms").map(parse_i64_or_null).transpose()?.flatten();
+    match window_ms {
+        Some(window_ms) => Ok(Some(OwnerQuota { window_ms, max_count, max_duration_ms })),
+        None if max_count.is_some() || max_duration_ms.is_some() => {
+            Err(SqlError::Parse("quota limits require quota_window_ms".into()))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Optional per-allocation `buffer_before`/`buffer_after`/`owner` columns.
+/// `ON CONFLICT (id) DO NOTHING` or `DO UPDATE SET col = EXCLUDED.col, ...`.
+/// DO UPDATE replaces the existing row with the inserted one, so every
+/// assignment must take the EXCLUDED value.
+fn parse_on_conflict(insert: &ast::Insert) -> Result<OnConflict, SqlError> {
+    let conflict = match &insert.on {
+        None => return Ok(OnConflict::Error),
+        Some(ast::OnInsert::OnConflict(conflict)) => conflict,
+        Some(other) => return Err(SqlError::Unsupported(format!("{other}"))),
+    };
+    match &conflict.conflict_target {
+        None => {}
+        Some(ast::ConflictTarget::Columns(cols)) if cols.len() == 1 && cols[0].value.eq_ignore_ascii_case("id") => {}
+        Some(target) => return Err(SqlError::Unsupported(format!("ON CONFLICT {target}"))),
+    }
+    match &conflict.action {
+        ast::OnConflictAction::DoNothing => Ok(OnConflict::DoNothing),
+        ast::OnConflictAction::DoUpdate(update) => {
+            if update.selection.is_some() {
+                return Err(SqlError::Unsupported("ON CONFLICT DO UPDATE ... WHERE".into()));
+            }
+            for a in &update.assignments {
+                let col = assignment_column_name(a)?;
+                let from_excluded = matches!(&a.value, Expr::CompoundIdentifier(parts)
+                    if parts.len() == 2
+                        && parts[0].value.eq_ignore_ascii_case("excluded")
+                        && parts[1].value == col);
+                if !from_excluded {
+                    return Err(SqlError::Unsupported(format!(
+                        "ON CONFLICT DO UPDATE must set {col} = EXCLUDED.{col}"
+                    )));
+                }
+            }
+            Ok(OnConflict::DoUpdate)
+        }
+    }
+}
+
+/// One entry of a `rule_sets` array. Rules without an id get a fresh one.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleSpec {
+    id: Option<Ulid>,
+    start: Ms,
+    end: Ms,
+    #[serde(default)]
+    blocking: bool,
+    capacity: Option<u32>,
+}
+
+fn parse_rule_list(text: &str) -> Result<Vec<RuleRow>, SqlError> {
+    let specs: Vec<RuleSpec> =
+        serde_json::from_str(text).map_err(|e| SqlError::Parse(format!("bad rules JSON: {e}")))?;
+    specs
+        .into_iter()
+        .map(|r| {
+            if r.blocking && r.capacity.is_some() {
+                return Err(SqlError::Parse("capacity rules cannot be blocking".into()));
+            }
+            Ok((r.id.unwrap_or_else(Ulid::new), r.start, r.end, r.blocking, r.capacity))
+        })
+        .collect()
+}
+
+/// A composite's `components` or a group's `members`: a JSON array of
+/// resource ids, or NULL.
+fn parse_component_list(expr: &Expr) -> Result<Vec<Ulid>, SqlError> {
+    match parse_string_or_null(expr)? {
+        Some(text) => serde_json::from_str(&text).map_err(|e| SqlError::Parse(format!("bad components JSON: {e}"))),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Requirements as a JSON array of `{"resources": [ids], "count": n}`.
+fn parse_requirements(expr: &Expr) -> Result<Vec<Requirement>, SqlError> {
+    let text = parse_string_or_null(expr)?.ok_or(SqlError::MissingColumn("requirements"))?;
+    serde_json::from_str(&text).map_err(|e| SqlError::Parse(format!("bad requirements JSON: {e}")))
+}
+
+fn parse_alloc_options(columns: &[String], values: &[Expr]) -> Result<AllocOptions, SqlError> {
+    let col = |name: &str| columns.iter().position(|c| c == name).and_then(|i| values.get(i));
+    Ok(AllocOptions {
+        buffer_before: col("buffer_before").map(parse_i64_or_null).transpose()?.flatten(),
+        buffer_after: col("buffer_after").map(parse_i64_or_null).transpose()?.flatten(),
+        owner: col("owner").map(parse_string_or_null).transpose()?.flatten(),
+        metadata: col("metadata").map(parse_metadata).transpose()?.flatten(),
+    })
+}
+
+/// Parse a JSON object literal, normalised to compact text.
+fn parse_metadata(expr: &Expr) -> Result<Option<String>, SqlError> {
+    let Some(text) = parse_string_or_null(expr)? else { return Ok(None) };
+    match serde_json::from_str::<serde_json::Value>(&text) {
+        Ok(value @ serde_json::Value::Object(_)) => Ok(Some(value.to_string())),
+        Ok(_) => Err(SqlError::Parse("metadata must be a JSON object".into())),
+        Err(e) => Err(SqlError::Parse(format!("bad metadata JSON: {e}"))),
+    }
+}
+
+fn parse_booking_status(s: &str) -> Result<BookingStatus, SqlError> {
+    BookingStatus::parse(s).ok_or_else(|| SqlError::Parse(format!("bad booking status: {s}")))
+}
+
+fn parse_inheritance(expr: &Expr) -> Result<InheritanceMode, SqlError> {
+    match parse_string_or_null(expr)? {
+        None => Ok(InheritanceMode::default()),
+        Some(s) => InheritanceMode::parse(&s)
+            .ok_or_else(|| SqlError::Parse(format!("bad inheritance mode: {s}"))),
+    }
+}
+
 fn parse_bool(expr: &Expr) -> Result<bool, SqlError> {
     if let Some(value) = extract_value(expr) {
         match value {
@@ -725,6 +1659,7 @@ pub enum SqlError {
     UnknownTable(String),
     WrongArity(&'static str, usize, usize),
     MissingFilter(&'static str),
+    MissingColumn(&'static str),
 }
 
 impl std::fmt::Display for SqlError {
@@ -738,6 +1673,7 @@ impl std::fmt::Display for SqlError {
                 write!(f, "{t}: expected {expected} values, got {got}")
             }
             SqlError::MissingFilter(col) => write!(f, "missing filter: {col}"),
+            SqlError::MissingColumn(col) => write!(f, "missing column: {col}"),
         }
     }
 }
@@ -753,7 +1689,7 @@ mod tests {
         let sql = "INSERT INTO resources (id) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV')";
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::InsertResource { id, parent_id, name: _, capacity, buffer_after } => {
+            Command::InsertResource { id, parent_id, name: _, capacity, buffer_after, .. } => {
                 assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
                 assert_eq!(parent_id, None);
                 assert_eq!(capacity, 1);
@@ -768,7 +1704,7 @@ mod tests {
         let sql = "INSERT INTO resources (id, parent_id) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV')";
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::InsertResource { id, parent_id, name: _, capacity, buffer_after } => {
+            Command::InsertResource { id, parent_id, name: _, capacity, buffer_after, .. } => {
                 assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
                 assert_eq!(parent_id, Some(id));
                 assert_eq!(capacity, 1);
@@ -816,6 +1752,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_resource_inheritance() {
+        let sql = "INSERT INTO resources (id, parent_id, inheritance) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', NULL, 'intersect')";
+        match parse_sql(sql).unwrap() {
+            Command::InsertResource { settings, .. } => {
+                assert_eq!(settings.inheritance, InheritanceMode::Intersect);
+            }
+            cmd => panic!("expected InsertResource, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET inheritance = 'ISOLATE' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::UpdateResource { settings, .. } => {
+                assert_eq!(settings.inheritance, Some(InheritanceMode::Isolate));
+            }
+            cmd => panic!("expected UpdateResource, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET inheritance = 'sideways' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(parse_sql(sql).is_err());
+    }
+
     #[test]
     fn parse_delete_resource() {
         let sql = "DELETE FROM resources WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
@@ -847,6 +1805,33 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_insert_capacity_rule() {
+        let sql = r#"INSERT INTO rules (id, resource_id, start, "end", blocking, capacity) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, false, 10)"#;
+        match parse_sql(sql).unwrap() {
+            Command::InsertRule { blocking, capacity, .. } => {
+                assert!(!blocking);
+                assert_eq!(capacity, Some(10));
+            }
+            cmd => panic!("expected InsertRule, got {cmd:?}"),
+        }
+
+        let sql = r#"INSERT INTO rules (id, resource_id, start, "end", blocking, capacity) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, true, 10)"#;
+        assert!(parse_sql(sql).is_err());
+    }
+
+    #[test]
+    fn parse_update_capacity_rule_without_blocking() {
+        let sql = r#"UPDATE rules SET start = 5000, "end" = 10000, capacity = 3 WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'"#;
+        match parse_sql(sql).unwrap() {
+            Command::UpdateRule { blocking, capacity, .. } => {
+                assert!(!blocking);
+                assert_eq!(capacity, Some(3));
+            }
+            cmd => panic!("expected UpdateRule, got {cmd:?}"),
+        }
+    }
+
     #[test]
     fn parse_insert_hold() {
         let sql = r#"INSERT INTO holds (id, resource_id, start, "end", expires_at) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 3000)"#;
@@ -935,7 +1920,7 @@ mod tests {
         let sql = r#"INSERT INTO bookings (id, resource_id, start, "end") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000), ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 3000, 4000)"#;
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::BatchInsertBookings { bookings } => {
+            Command::BatchInsertBookings { bookings, .. } => {
                 assert_eq!(bookings.len(), 2);
                 assert_eq!(bookings[0].2, 1000);
                 assert_eq!(bookings[0].3, 2000);
@@ -1138,7 +2123,7 @@ mod tests {
         let sql = "SELECT * FROM bookings WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::SelectBookings { resource_id } => {
+            Command::SelectBookings { resource_id, .. } => {
                 assert_eq!(resource_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
             }
             _ => panic!("expected SelectBookings, got {cmd:?}"),
@@ -1176,7 +2161,7 @@ mod tests {
         let sql = "UPDATE resources SET name = 'Meeting Room A', capacity = 5 WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::UpdateResource { id, name, capacity, buffer_after } => {
+            Command::UpdateResource { id, name, capacity, buffer_after, .. } => {
                 assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
                 assert_eq!(name, Some("Meeting Room A".to_string()));
                 assert_eq!(capacity, 5);
@@ -1218,7 +2203,7 @@ mod tests {
         let sql = r#"UPDATE rules SET start = 5000, "end" = 10000, blocking = true WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'"#;
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::UpdateRule { id, start, end, blocking } => {
+            Command::UpdateRule { id, start, end, blocking, .. } => {
                 assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
                 assert_eq!(start, 5000);
                 assert_eq!(end, 10000);
@@ -1248,7 +2233,7 @@ mod tests {
         let sql = "INSERT INTO resources (id, parent_id, name, capacity, buffer_after) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', NULL, 'Room 101', 3, NULL)";
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::InsertResource { id, parent_id, name, capacity, buffer_after } => {
+            Command::InsertResource { id, parent_id, name, capacity, buffer_after, .. } => {
                 assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
                 assert_eq!(parent_id, None);
                 assert_eq!(name, Some("Room 101".to_string()));
@@ -1300,6 +2285,509 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_insert_booking_with_buffers() {
+        let sql = r#"INSERT INTO bookings (id, resource_id, start, "end", buffer_before, buffer_after) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 300, NULL)"#;
+        match parse_sql(sql).unwrap() {
+            Command::InsertBooking { label, options, .. } => {
+                assert_eq!(label, None);
+                assert_eq!(options, AllocOptions { buffer_before: Some(300), buffer_after: None, ..Default::default() });
+            }
+            cmd => panic!("expected InsertBooking, got {cmd:?}"),
+        }
+
+        let sql = r#"INSERT INTO holds (id, resource_id, start, "end", expires_at, buffer_after) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 5000, 600)"#;
+        match parse_sql(sql).unwrap() {
+            Command::InsertHold { options, .. } => assert_eq!(options.buffer_after, Some(600)),
+            cmd => panic!("expected InsertHold, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET buffer_before = 900 WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::UpdateResource { settings, .. } => assert_eq!(settings.buffer_before, Some(Some(900))),
+            cmd => panic!("expected UpdateResource, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_resource_booking_policy() {
+        let sql = "INSERT INTO resources (id, min_duration, max_horizon) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1800000, 5184000000)";
+        match parse_sql(sql).unwrap() {
+            Command::InsertResource { settings, .. } => {
+                assert_eq!(settings.policy.min_duration, Some(1_800_000));
+                assert_eq!(settings.policy.max_duration, None);
+                assert_eq!(settings.policy.max_horizon, Some(5_184_000_000));
+            }
+            cmd => panic!("expected InsertResource, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET min_lead_time = 7200000, max_duration = NULL WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::UpdateResource { settings, .. } => {
+                assert_eq!(settings.min_lead_time, Some(Some(7_200_000)));
+                assert_eq!(settings.max_duration, Some(None));
+                assert_eq!(settings.min_duration, None);
+            }
+            cmd => panic!("expected UpdateResource, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_resource_granularity() {
+        let sql = "INSERT INTO resources (id, granularity_ms, granularity_offset_ms) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 900000, 300000)";
+        match parse_sql(sql).unwrap() {
+            Command::InsertResource { settings, .. } => {
+                assert_eq!(settings.granularity_ms, Some(900_000));
+                assert_eq!(settings.granularity_offset_ms, 300_000);
+            }
+            cmd => panic!("expected InsertResource, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET granularity_ms = NULL WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::UpdateResource { settings, .. } => assert_eq!(settings.granularity_ms, Some(None)),
+            cmd => panic!("expected UpdateResource, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_booking_owner() {
+        let sql = r#"INSERT INTO bookings (id, resource_id, start, "end", owner) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 'cust-42')"#;
+        match parse_sql(sql).unwrap() {
+            Command::InsertBooking { options, .. } => assert_eq!(options.owner.as_deref(), Some("cust-42")),
+            cmd => panic!("expected InsertBooking, got {cmd:?}"),
+        }
+
+        let sql = "SELECT * FROM bookings WHERE owner = 'cust-42'";
+        match parse_sql(sql).unwrap() {
+            Command::SelectBookingsByOwner { owner, resource_id, .. } => {
+                assert_eq!(owner, "cust-42");
+                assert_eq!(resource_id, None);
+            }
+            cmd => panic!("expected SelectBookingsByOwner, got {cmd:?}"),
+        }
+
+        let sql = "SELECT * FROM holds WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND owner = 'cust-42'";
+        match parse_sql(sql).unwrap() {
+            Command::SelectHoldsByOwner { owner, resource_id } => {
+                assert_eq!(owner, "cust-42");
+                assert!(resource_id.is_some());
+            }
+            cmd => panic!("expected SelectHoldsByOwner, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_resource_quota() {
+        let sql = "INSERT INTO resources (id, quota_window_ms, quota_max_count) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 604800000, 2)";
+        match parse_sql(sql).unwrap() {
+            Command::InsertResource { settings, .. } => assert_eq!(
+                settings.quota,
+                Some(OwnerQuota { window_ms: 604_800_000, max_count: Some(2), max_duration_ms: None })
+            ),
+            cmd => panic!("expected InsertResource, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE resources SET quota_max_count = 2 WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(parse_sql(sql).is_err());
+    }
+
+    #[test]
+    fn parse_select_by_id() {
+        let sql = "SELECT * FROM bookings WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::SelectBookingsById { ids, .. } => {
+                assert_eq!(ids, vec![Ulid::from_string("01ARZ3NDEKTSV4RRFFQ69G5FAV").unwrap()]);
+            }
+            cmd => panic!("expected SelectBookingsById, got {cmd:?}"),
+        }
+
+        let sql = "SELECT * FROM holds WHERE id IN ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01BX5ZZKBKACTAV9WEVGEMMVRZ')";
+        match parse_sql(sql).unwrap() {
+            Command::SelectHoldsById { ids } => assert_eq!(ids.len(), 2),
+            cmd => panic!("expected SelectHoldsById, got {cmd:?}"),
+        }
+
+        let sql = "SELECT * FROM rules WHERE id IN ('01ARZ3NDEKTSV4RRFFQ69G5FAV')";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::SelectRulesById { .. }));
+
+        let sql = "SELECT * FROM bookings WHERE id = 'not-a-ulid'";
+        assert!(parse_sql(sql).is_err());
+    }
+
+    #[test]
+    fn parse_booking_metadata() {
+        let sql = r#"INSERT INTO bookings (id, resource_id, start, "end", metadata) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, '{ "channel": "web", "party": 4 }')"#;
+        match parse_sql(sql).unwrap() {
+            Command::InsertBooking { options, .. } => {
+                assert_eq!(options.metadata.as_deref(), Some(r#"{"channel":"web","party":4}"#));
+            }
+            cmd => panic!("expected InsertBooking, got {cmd:?}"),
+        }
+
+        let sql = r#"INSERT INTO holds (id, resource_id, start, "end", expires_at, metadata) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 3000, '[1, 2]')"#;
+        assert!(parse_sql(sql).is_err());
+
+        let sql = r#"SELECT * FROM bookings WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND metadata->>'channel' = 'web' AND metadata @> '{"party": 4}'"#;
+        match parse_sql(sql).unwrap() {
+            Command::SelectBookings { filter, .. } => {
+                assert_eq!(
+                    filter.metadata,
+                    vec![
+                        MetadataFilter::KeyEquals { key: "channel".into(), value: "web".into() },
+                        MetadataFilter::Contains(serde_json::json!({ "party": 4 })),
+                    ]
+                );
+            }
+            cmd => panic!("expected SelectBookings, got {cmd:?}"),
+        }
+
+        let sql = "SELECT * FROM bookings WHERE owner = 'cust-42' AND metadata->>'party' = 4";
+        match parse_sql(sql).unwrap() {
+            Command::SelectBookingsByOwner { filter, .. } => {
+                assert_eq!(filter.metadata, vec![MetadataFilter::KeyEquals { key: "party".into(), value: "4".into() }]);
+            }
+            cmd => panic!("expected SelectBookingsByOwner, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_booking_status() {
+        let sql = "UPDATE bookings SET status = 'cancelled', cancel_reason = 'weather' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::UpdateBookingStatus { status, reason, .. } => {
+                assert_eq!(status, BookingStatus::Cancelled);
+                assert_eq!(reason.as_deref(), Some("weather"));
+            }
+            cmd => panic!("expected UpdateBookingStatus, got {cmd:?}"),
+        }
+
+        let sql = "UPDATE bookings SET status = 'no_show' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::UpdateBookingStatus { status: BookingStatus::NoShow, reason: None, .. }
+        ));
+
+        let sql = "UPDATE bookings SET status = 'completed', cancel_reason = 'x' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(parse_sql(sql).is_err());
+        let sql = "UPDATE bookings SET status = 'lost' WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(parse_sql(sql).is_err());
+
+        let sql = "SELECT * FROM bookings WHERE status = 'cancelled'";
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::SelectBookingsByStatus { status: BookingStatus::Cancelled, .. }
+        ));
+
+        let sql = "SELECT * FROM bookings WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND status = 'no_show'";
+        match parse_sql(sql).unwrap() {
+            Command::SelectBookings { filter, .. } => assert_eq!(filter.status, Some(BookingStatus::NoShow)),
+            cmd => panic!("expected SelectBookings, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_insert_on_conflict() {
+        let base = r#"INSERT INTO bookings (id, resource_id, start, "end", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 'x')"#;
+        let cmd = parse_sql(base).unwrap();
+        assert!(matches!(cmd, Command::InsertBooking { on_conflict: OnConflict::Error, .. }));
+
+        let cmd = parse_sql(&format!("{base} ON CONFLICT (id) DO NOTHING")).unwrap();
+        assert!(matches!(cmd, Command::InsertBooking { on_conflict: OnConflict::DoNothing, .. }));
+
+        let cmd = parse_sql(&format!(
+            "{base} ON CONFLICT (id) DO UPDATE SET label = EXCLUDED.label, start = EXCLUDED.start"
+        ))
+        .unwrap();
+        assert!(matches!(cmd, Command::InsertBooking { on_conflict: OnConflict::DoUpdate, .. }));
+
+        let sql = "INSERT INTO resources (id, capacity) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 2) ON CONFLICT DO NOTHING";
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::InsertResource { on_conflict: OnConflict::DoNothing, .. }
+        ));
+
+        assert!(parse_sql(&format!("{base} ON CONFLICT (id) DO UPDATE SET label = 'y'")).is_err());
+        assert!(parse_sql(&format!("{base} ON CONFLICT (resource_id) DO NOTHING")).is_err());
+        assert!(parse_sql(&format!(
+            "{base} ON CONFLICT (id) DO UPDATE SET label = EXCLUDED.label WHERE bookings.start > 0"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn parse_insert_without_id() {
+        let sql = r#"INSERT INTO bookings (resource_id, start, "end", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 'x')"#;
+        let Command::InsertBooking { id, resource_id, start, end, label, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected InsertBooking");
+        };
+        assert_ne!(id, resource_id);
+        assert_eq!((start, end, label.as_deref()), (1000, 2000, Some("x")));
+
+        let sql = "INSERT INTO rules (resource_id, start, \"end\", blocking) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 10, true)";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::InsertRule { start: 0, end: 10, blocking: true, .. }));
+
+        let sql = "INSERT INTO resources (id, name) VALUES (NULL, 'Room')";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::InsertResource { .. }));
+
+        let sql = r#"INSERT INTO holds (resource_id, start, "end") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000)"#;
+        assert!(matches!(parse_sql(sql), Err(SqlError::MissingColumn("expires_at"))));
+
+        let sql = r#"INSERT INTO bookings (resource_id, start, "end") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000), ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 3000, 4000)"#;
+        let Command::BatchInsertBookings { bookings, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected BatchInsertBookings");
+        };
+        assert_ne!(bookings[0].0, bookings[1].0);
+    }
+
+    #[test]
+    fn parse_returning() {
+        let sql = r#"INSERT INTO bookings (resource_id, start, "end") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000) RETURNING id, resource_id, start, "end""#;
+        let Command::Returning { command, columns } = parse_sql(sql).unwrap() else {
+            panic!("expected Returning");
+        };
+        assert!(matches!(*command, Command::InsertBooking { .. }));
+        assert_eq!(columns.unwrap(), ["id", "resource_id", "start", "end"]);
+
+        let sql = "DELETE FROM holds WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' RETURNING *";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::Returning { columns: None, .. }));
+
+        let sql = "UPDATE rules SET start = 0, \"end\" = 10, blocking = false WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' RETURNING id";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::Returning { .. }));
+
+        assert!(parse_sql("DELETE FROM calendars WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' RETURNING id").is_err());
+        assert!(parse_sql("DELETE FROM holds WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' RETURNING id AS x").is_err());
+
+        let (table, columns) = returning_clause("DELETE FROM holds WHERE id = $1 RETURNING id").unwrap();
+        assert_eq!((table.as_str(), columns.unwrap()), ("holds", vec!["id".to_string()]));
+        assert!(returning_clause("DELETE FROM holds WHERE id = $1").is_none());
+    }
+
+    #[test]
+    fn parse_range_delete() {
+        let sql = r#"DELETE FROM bookings WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 1000 AND "end" <= 2000"#;
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::DeleteInRange { class: IntervalClass::Bookings, descendants: false, start: 1000, end: 2000, .. }
+        ));
+
+        let sql = r#"DELETE FROM rules WHERE descendants_of = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND (start >= 0 AND "end" <= 10)"#;
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::DeleteInRange { class: IntervalClass::Rules, descendants: true, .. }
+        ));
+
+        // Single-id deletes are unchanged.
+        let sql = "DELETE FROM holds WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::DeleteHold { .. }));
+
+        let missing_end = "DELETE FROM holds WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 0";
+        assert!(matches!(parse_sql(missing_end), Err(SqlError::MissingFilter("end"))));
+        let unknown = r#"DELETE FROM holds WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 0 AND "end" <= 10 AND owner = 'x'"#;
+        assert!(matches!(parse_sql(unknown), Err(SqlError::Unsupported(_))));
+        let no_resource = r#"DELETE FROM holds WHERE start >= 0 AND "end" <= 10"#;
+        assert!(parse_sql(no_resource).is_err());
+    }
+
+    #[test]
+    fn parse_rule_sets() {
+        let sql = r#"INSERT INTO rule_sets (resource_id, start, "end", rules) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 100, '[{"start": 0, "end": 10}, {"id": "01ARZ3NDEKTSV4RRFFQ69G5FAW", "start": 20, "end": 30, "blocking": true}, {"start": 40, "end": 50, "capacity": 3}]')"#;
+        let Command::ReplaceRules { start, end, rules, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected ReplaceRules");
+        };
+        assert_eq!((start, end), (0, 100));
+        assert_eq!(rules.len(), 3);
+        assert_eq!((rules[0].1, rules[0].2, rules[0].3, rules[0].4), (0, 10, false, None));
+        assert_eq!(rules[1].0.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAW");
+        assert!(rules[1].3);
+        assert_eq!(rules[2].4, Some(3));
+
+        let empty = r#"INSERT INTO rule_sets (resource_id, start, "end", rules) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 100, '[]')"#;
+        assert!(matches!(parse_sql(empty).unwrap(), Command::ReplaceRules { rules, .. } if rules.is_empty()));
+
+        let bad = r#"INSERT INTO rule_sets (resource_id, start, "end", rules) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 100, '[{"start": 0, "end": 10, "blocking": true, "capacity": 2}]')"#;
+        assert!(parse_sql(bad).is_err());
+        let typo = r#"INSERT INTO rule_sets (resource_id, start, "end", rules) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 100, '[{"start": 0, "end": 10, "blokcing": true}]')"#;
+        assert!(parse_sql(typo).is_err());
+    }
+
+    #[test]
+    fn parse_resource_clones() {
+        let sql = "INSERT INTO resource_clones (source_id, parent_id, include_rules) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAW', true)";
+        let Command::CloneSubtree { source_id, parent_id, include_rules } = parse_sql(sql).unwrap() else {
+            panic!("expected CloneSubtree");
+        };
+        assert_eq!(source_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+        assert_eq!(parent_id.unwrap().to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAW");
+        assert!(include_rules);
+
+        let bare = "INSERT INTO resource_clones (source_id) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV')";
+        assert!(matches!(
+            parse_sql(bare).unwrap(),
+            Command::CloneSubtree { parent_id: None, include_rules: false, .. }
+        ));
+        let no_source = "INSERT INTO resource_clones (parent_id) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV')";
+        assert!(matches!(parse_sql(no_source), Err(SqlError::MissingColumn(_))));
+    }
+
+    #[test]
+    fn parse_insert_composite_resource() {
+        let sql = r#"INSERT INTO resources (id, name, components) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 'A+B', '["01ARZ3NDEKTSV4RRFFQ69G5FAW", "01ARZ3NDEKTSV4RRFFQ69G5FAX"]')"#;
+        let Command::InsertResource { settings, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected InsertResource");
+        };
+        assert_eq!(settings.components.len(), 2);
+        assert_eq!(settings.components[1].to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAX");
+
+        let bad = "INSERT INTO resources (id, components) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 'A,B')";
+        assert!(parse_sql(bad).is_err());
+    }
+
+    #[test]
+    fn parse_exclusion_groups() {
+        let sql = "INSERT INTO exclusion_groups (id, name, max_concurrent, members) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 'rooms', 2, '[\"01ARZ3NDEKTSV4RRFFQ69G5FAW\", \"01ARZ3NDEKTSV4RRFFQ69G5FAX\"]')";
+        let Command::InsertGroup { id, name, limit, members } = parse_sql(sql).unwrap() else {
+            panic!("expected InsertGroup");
+        };
+        assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+        assert_eq!(name.as_deref(), Some("rooms"));
+        assert_eq!(limit, 2);
+        assert_eq!(members.len(), 2);
+        let no_limit = "INSERT INTO exclusion_groups (id, members) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '[]')";
+        assert!(matches!(parse_sql(no_limit), Err(SqlError::MissingColumn("max_concurrent"))));
+        let delete = "DELETE FROM exclusion_groups WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(matches!(parse_sql(delete).unwrap(), Command::DeleteGroup { .. }));
+        assert!(matches!(parse_sql("SELECT * FROM exclusion_groups").unwrap(), Command::SelectGroups));
+    }
+
+    #[test]
+    fn parse_explain() {
+        let sql = "EXPLAIN INSERT INTO bookings (id, resource_id, start, \"end\") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAW', 1000, 2000)";
+        let Command::Explain { command } = parse_sql(sql).unwrap() else {
+            panic!("expected Explain");
+        };
+        assert!(matches!(*command, Command::InsertBooking { .. }));
+        let delete = "EXPLAIN DELETE FROM bookings WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        assert!(matches!(parse_sql(delete).unwrap(), Command::Explain { .. }));
+        assert!(matches!(parse_sql("EXPLAIN SELECT * FROM resources"), Err(SqlError::Unsupported(_))));
+        assert!(parse_sql("EXPLAIN ANALYZE DELETE FROM bookings WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'").is_err());
+    }
+
+    #[test]
+    fn parse_requirements_query_and_booking() {
+        let reqs = r#"[{"resources": ["01ARZ3NDEKTSV4RRFFQ69G5FAV"], "count": 1}, {"resources": ["01ARZ3NDEKTSV4RRFFQ69G5FAW", "01ARZ3NDEKTSV4RRFFQ69G5FAX"], "count": 2}]"#;
+        let sql = format!("SELECT * FROM availability WHERE requirements = '{reqs}' AND start >= 0 AND \"end\" <= 1000");
+        let Command::SelectRequirementAvailability { requirements, start, end, min_duration } = parse_sql(&sql).unwrap()
+        else {
+            panic!("expected SelectRequirementAvailability");
+        };
+        assert_eq!(requirements.len(), 2);
+        assert_eq!(requirements[1].count, 2);
+        assert_eq!((start, end, min_duration), (0, 1000, None));
+
+        let sql = format!("INSERT INTO requirement_bookings (requirements, start, \"end\", label) VALUES ('{reqs}', 0, 500, 'surgery')");
+        let Command::BookRequirements { requirements, label, .. } = parse_sql(&sql).unwrap() else {
+            panic!("expected BookRequirements");
+        };
+        assert_eq!(requirements[0].resources.len(), 1);
+        assert_eq!(label.as_deref(), Some("surgery"));
+        let bad = "SELECT * FROM availability WHERE requirements = '[{\"count\": 1}]' AND start >= 0 AND \"end\" <= 1000";
+        assert!(parse_sql(bad).is_err());
+    }
+
+    #[test]
+    fn insert_column_value_count_mismatch() {
+        let short = "INSERT INTO resources (id, inheritance) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV')";
+        assert!(matches!(parse_sql(short), Err(SqlError::Parse(_))));
+        let long = "INSERT INTO rules (id, resource_id, start, \"end\") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 10, false)";
+        assert!(matches!(parse_sql(long), Err(SqlError::Parse(_))));
+        let ragged = "INSERT INTO bookings (id, resource_id, start, \"end\", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 0, 10, 'a'), ('01ARZ3NDEKTSV4RRFFQ69G5FAX', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 20, 30)";
+        assert!(matches!(parse_sql(ragged), Err(SqlError::Parse(_))));
+        // Without a column list, positional values are still accepted.
+        let positional = "INSERT INTO resources VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', NULL, 3)";
+        assert!(matches!(parse_sql(positional).unwrap(), Command::InsertResource { capacity: 3, .. }));
+    }
+
+    #[test]
+    fn parse_utilization_query() {
+        let sql = "SELECT * FROM utilization WHERE descendants_of = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 0 AND \"end\" <= 172800000 AND bucket = 86400000";
+        let Command::SelectUtilization { resource_id, descendants, start, end, bucket } = parse_sql(sql).unwrap() else {
+            panic!("expected SelectUtilization");
+        };
+        assert_eq!(resource_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+        assert!(descendants);
+        assert_eq!((start, end, bucket), (0, 172_800_000, Some(86_400_000)));
+        let sql = "SELECT * FROM utilization WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 0 AND \"end\" <= 1000";
+        assert!(matches!(
+            parse_sql(sql).unwrap(),
+            Command::SelectUtilization { descendants: false, bucket: None, .. }
+        ));
+        let unknown = "SELECT * FROM utilization WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 0 AND \"end\" <= 1000 AND label = 'x'";
+        assert!(matches!(parse_sql(unknown), Err(SqlError::Unsupported(_))));
+        assert!(matches!(parse_sql("SELECT * FROM utilization"), Err(SqlError::MissingFilter("resource_id"))));
+    }
+
+    #[test]
+    fn parse_busy() {
+        let sql = "SELECT * FROM busy WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV' AND start >= 1000 AND \"end\" <= 5000";
+        let Command::SelectBusy { resource_id, start, end } = parse_sql(sql).unwrap() else {
+            panic!("expected SelectBusy");
+        };
+        assert_eq!(resource_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+        assert_eq!((start, end), (1000, 5000));
+        let no_resource = "SELECT * FROM busy WHERE start >= 1000 AND \"end\" <= 5000";
+        assert!(matches!(parse_sql(no_resource), Err(SqlError::MissingFilter("resource_id"))));
+    }
+
+    #[test]
+    fn parse_booking_suggest() {
+        let sql = "INSERT INTO bookings (id, resource_id, start, \"end\", suggest) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 3)";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::InsertBooking { suggest: 3, .. }));
+        let sql = "INSERT INTO bookings (id, resource_id, start, \"end\") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000)";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::InsertBooking { suggest: 0, .. }));
+        let batch = "INSERT INTO bookings (id, resource_id, start, \"end\", suggest) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 3), ('01ARZ3NDEKTSV4RRFFQ69G5FAX', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 3000, 4000, 3)";
+        assert!(matches!(parse_sql(batch), Err(SqlError::Unsupported(_))));
+    }
+
+    #[test]
+    fn parse_itinerary_bookings() {
+        let steps = r#"[{"resources": ["01ARZ3NDEKTSV4RRFFQ69G5FAV"], "duration": 1200000}, {"resources": ["01ARZ3NDEKTSV4RRFFQ69G5FAW"], "duration": 1800000, "max_gap": 600000}]"#;
+        let sql = format!("INSERT INTO itinerary_bookings (steps, start, \"end\", label) VALUES ('{steps}', 0, 86400000, 'visit')");
+        let Command::BookItinerary { steps, start, end, label } = parse_sql(&sql).unwrap() else {
+            panic!("expected BookItinerary");
+        };
+        assert_eq!(steps.len(), 2);
+        assert_eq!((steps[1].min_gap, steps[1].max_gap), (0, 600_000));
+        assert_eq!((start, end), (0, 86_400_000));
+        assert_eq!(label.as_deref(), Some("visit"));
+        let bad = "INSERT INTO itinerary_bookings (steps, start, \"end\") VALUES ('[{\"duration\": 1}]', 0, 1000)";
+        assert!(parse_sql(bad).is_err());
+    }
+
+    #[test]
+    fn parse_block_bookings_and_positions() {
+        let sql = "INSERT INTO block_bookings (parent_id, count, start, \"end\", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 4, 1000, 2000, 'family')";
+        let Command::BookBlock { parent_id, count, start, end, label } = parse_sql(sql).unwrap() else {
+            panic!("expected BookBlock");
+        };
+        assert_eq!(parent_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+        assert_eq!((count, start, end), (4, 1000, 2000));
+        assert_eq!(label.as_deref(), Some("family"));
+        let no_count = "INSERT INTO block_bookings (parent_id, start, \"end\") VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000)";
+        assert!(matches!(parse_sql(no_count), Err(SqlError::MissingColumn("count"))));
+
+        let sql = "INSERT INTO resources (id, parent_id, position) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAW', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 7)";
+        let Command::InsertResource { settings, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected InsertResource");
+        };
+        assert_eq!(settings.position, Some(7));
+        let sql = "UPDATE resources SET position = 8 WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAW'";
+        let Command::UpdateResource { settings, .. } = parse_sql(sql).unwrap() else {
+            panic!("expected UpdateResource");
+        };
+        assert_eq!(settings.position, Some(Some(8)));
+    }
+
     #[test]
     fn parse_insert_booking_with_null_label() {
         let sql = r#"INSERT INTO bookings (id, resource_id, start, "end", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, NULL)"#;
@@ -1330,7 +2818,7 @@ mod tests {
         let sql = r#"INSERT INTO bookings (id, resource_id, start, "end", label) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 1000, 2000, 'Morning'), ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01ARZ3NDEKTSV4RRFFQ69G5FAV', 3000, 4000, NULL)"#;
         let cmd = parse_sql(sql).unwrap();
         match cmd {
-            Command::BatchInsertBookings { bookings } => {
+            Command::BatchInsertBookings { bookings, .. } => {
                 assert_eq!(bookings.len(), 2);
                 assert_eq!(bookings[0].4, Some("Morning".to_string()));
                 assert_eq!(bookings[1].4, None);
@@ -1467,4 +2955,90 @@ mod tests {
             _ => panic!("expected Unlisten, got {cmd:?}"),
         }
     }
+
+    // ── Calendars ────────────────────────────────────────────────
+
+    #[test]
+    fn parse_insert_calendar() {
+        let sql = "INSERT INTO calendars (id, name) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', 'holidays')";
+        let cmd = parse_sql(sql).unwrap();
+        match cmd {
+            Command::InsertCalendar { id, name } => {
+                assert_eq!(id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+                assert_eq!(name.as_deref(), Some("holidays"));
+            }
+            _ => panic!("expected InsertCalendar, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_insert_calendar_rule() {
+        let sql = r#"INSERT INTO calendar_rules (id, calendar_id, start, "end", blocking) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01BX5ZZKBKACTAV9WEVGEMMVRZ', 1000, 2000, true)"#;
+        let cmd = parse_sql(sql).unwrap();
+        match cmd {
+            Command::InsertCalendarRule { calendar_id, start, end, blocking, .. } => {
+                assert_eq!(calendar_id.to_string(), "01BX5ZZKBKACTAV9WEVGEMMVRZ");
+                assert_eq!(start, 1000);
+                assert_eq!(end, 2000);
+                assert!(blocking);
+            }
+            _ => panic!("expected InsertCalendarRule, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_update_calendar_rule() {
+        let sql = r#"UPDATE calendar_rules SET start = 5000, "end" = 10000, blocking = false WHERE id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'"#;
+        let cmd = parse_sql(sql).unwrap();
+        match cmd {
+            Command::UpdateCalendarRule { start, end, blocking, .. } => {
+                assert_eq!(start, 5000);
+                assert_eq!(end, 10000);
+                assert!(!blocking);
+            }
+            _ => panic!("expected UpdateCalendarRule, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_attach_and_detach_calendar() {
+        let sql = "INSERT INTO calendar_attachments (resource_id, calendar_id) VALUES ('01ARZ3NDEKTSV4RRFFQ69G5FAV', '01BX5ZZKBKACTAV9WEVGEMMVRZ')";
+        let cmd = parse_sql(sql).unwrap();
+        match cmd {
+            Command::AttachCalendar { calendar_id, resource_id } => {
+                assert_eq!(calendar_id.to_string(), "01BX5ZZKBKACTAV9WEVGEMMVRZ");
+                assert_eq!(resource_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+            }
+            _ => panic!("expected AttachCalendar, got {cmd:?}"),
+        }
+
+        let sql = "DELETE FROM calendar_attachments WHERE calendar_id = '01BX5ZZKBKACTAV9WEVGEMMVRZ' AND resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        let cmd = parse_sql(sql).unwrap();
+        match cmd {
+            Command::DetachCalendar { calendar_id, resource_id } => {
+                assert_eq!(calendar_id.to_string(), "01BX5ZZKBKACTAV9WEVGEMMVRZ");
+                assert_eq!(resource_id.to_string(), "01ARZ3NDEKTSV4RRFFQ69G5FAV");
+            }
+            _ => panic!("expected DetachCalendar, got {cmd:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_select_calendar_tables() {
+        assert!(matches!(parse_sql("SELECT * FROM calendars").unwrap(), Command::SelectCalendars));
+
+        let sql = "SELECT * FROM calendar_rules WHERE calendar_id = '01BX5ZZKBKACTAV9WEVGEMMVRZ'";
+        assert!(matches!(parse_sql(sql).unwrap(), Command::SelectCalendarRules { .. }));
+
+        let sql = "SELECT * FROM calendar_attachments WHERE resource_id = '01ARZ3NDEKTSV4RRFFQ69G5FAV'";
+        match parse_sql(sql).unwrap() {
+            Command::SelectCalendarAttachments { calendar_id, resource_id } => {
+                assert!(calendar_id.is_none());
+                assert!(resource_id.is_some());
+            }
+            cmd => panic!("expected SelectCalendarAttachments, got {cmd:?}"),
+        }
+
+        assert!(parse_sql("SELECT * FROM calendar_attachments").is_err());
+    }
 }
@@ -1,9 +1,22 @@
 use std::fs::{self, File, OpenOptions};
-use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::io::{self, BufReader, BufWriter, Read, Seek, Write};
 use std::path::{Path, PathBuf};
 
 use crate::model::Event;
 
+/// Marks a WAL file with a format version. Files written before versioning
+/// start directly with their first entry; see `v0`.
+const MAGIC: [u8; 4] = *b"DTWL";
+/// Bump on any change to how an `Event` encodes, and migrate the old format
+/// on replay.
+const VERSION: u32 = 1;
+const HEADER_LEN: u64 = 8;
+
+fn write_header(writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&VERSION.to_le_bytes())
+}
+
 /// Encode a single event to [len][bincode][crc32] format.
 fn encode_event(writer: &mut impl Write, event: &Event) -> io::Result<()> {
     let payload =
@@ -18,9 +31,12 @@ fn encode_event(writer: &mut impl Write, event: &Event) -> io::Result<()> {
 
 /// Append-only Write-Ahead Log.
 ///
-/// Format per entry: `[u32: len][bincode: Event][u32: crc32]`
+/// Header: `[b"DTWL"][u32: version]`, then per entry:
+/// `[u32: len][bincode: Event][u32: crc32]`
 /// - `len` is the byte length of the bincode payload (not including the CRC).
 /// - Truncated last entry (crash) is safely discarded via length-prefix + CRC check.
+/// - A file without the header is in the unversioned format and is
+///   rewritten in the current one when opened.
 pub struct Wal {
     writer: BufWriter<File>,
     path: PathBuf,
@@ -28,12 +44,25 @@ pub struct Wal {
 }
 
 impl Wal {
-    /// Open (or create) the WAL file at `path`.
+    /// Open (or create) the WAL file at `path`, migrating it to the current
+    /// format first if needed.
     pub fn open(path: &Path) -> io::Result<Self> {
-        let file = OpenOptions::new()
+        let len = fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        let current =
+            len >= HEADER_LEN && read_version(&mut File::open(path)?)? == Some(VERSION);
+        if len > 0 && !current {
+            let events = Self::replay(path)?;
+            Self::write_compact_file(path, &events)?;
+            fs::rename(path.with_extension("wal.tmp"), path)?;
+        }
+        let mut file = OpenOptions::new()
             .create(true)
             .append(true)
             .open(path)?;
+        if file.metadata()?.len() == 0 {
+            write_header(&mut file)?;
+            file.sync_all()?;
+        }
         Ok(Self {
             writer: BufWriter::new(file),
             path: path.to_path_buf(),
@@ -74,6 +103,7 @@ impl Wal {
         let tmp_path = path.with_extension("wal.tmp");
         let file = File::create(&tmp_path)?;
         let mut writer = BufWriter::new(file);
+        write_header(&mut writer)?;
         for event in events {
             encode_event(&mut writer, event)?;
         }
@@ -111,60 +141,219 @@ impl Wal {
     /// Replay the WAL from disk, returning all valid events.
     /// Truncated/corrupt trailing entries are silently discarded.
     pub fn replay(path: &Path) -> io::Result<Vec<Event>> {
-        let file = match File::open(path) {
+        let mut file = match File::open(path) {
             Ok(f) => f,
             Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
             Err(e) => return Err(e),
         };
-        let mut reader = BufReader::new(file);
-        let mut events = Vec::new();
-
-        loop {
-            // Read length prefix
-            let mut len_buf = [0u8; 4];
-            match reader.read_exact(&mut len_buf) {
-                Ok(()) => {}
-                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
-                Err(e) => return Err(e),
-            }
-            let len = u32::from_le_bytes(len_buf) as usize;
-
-            // Read payload
-            let mut payload = vec![0u8; len];
-            match reader.read_exact(&mut payload) {
-                Ok(()) => {}
-                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break, // truncated
-                Err(e) => return Err(e),
+        // Shorter than a header means nothing was ever committed to it.
+        if file.metadata()?.len() < HEADER_LEN {
+            return Ok(Vec::new());
+        }
+        match read_version(&mut file)? {
+            Some(VERSION) => read_entries(BufReader::new(file), |payload| {
+                bincode::deserialize::<Event>(payload).ok()
+            }),
+            Some(v) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported WAL version {v}"),
+            )),
+            None => {
+                file.rewind()?;
+                read_entries(BufReader::new(file), |payload| {
+                    bincode::deserialize::<v0::Event>(payload).ok().map(Event::from)
+                })
             }
+        }
+    }
+}
 
-            // Read CRC
-            let mut crc_buf = [0u8; 4];
-            match reader.read_exact(&mut crc_buf) {
-                Ok(()) => {}
-                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break, // truncated
-                Err(e) => return Err(e),
-            }
-            let stored_crc = u32::from_le_bytes(crc_buf);
-            let computed_crc = crc32fast::hash(&payload);
+/// Read the header at the start of `file`, returning its version, or `None`
+/// for an unversioned file.
+fn read_version(file: &mut File) -> io::Result<Option<u32>> {
+    let mut header = [0u8; HEADER_LEN as usize];
+    file.read_exact(&mut header)?;
+    if header[..4] != MAGIC {
+        return Ok(None);
+    }
+    Ok(Some(u32::from_le_bytes(header[4..].try_into().unwrap())))
+}
 
-            if stored_crc != computed_crc {
-                // Corrupt entry — stop replaying
-                break;
-            }
+/// Read entries up to the first truncated or corrupt one, decoding each
+/// payload with `decode`.
+fn read_entries(
+    mut reader: impl Read,
+    decode: impl Fn(&[u8]) -> Option<Event>,
+) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+
+    loop {
+        // Read length prefix
+        let mut len_buf = [0u8; 4];
+        match reader.read_exact(&mut len_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(len_buf) as usize;
 
-            match bincode::deserialize::<Event>(&payload) {
-                Ok(event) => events.push(event),
-                Err(_) => break, // corrupt payload
-            }
+        // Read payload
+        let mut payload = vec![0u8; len];
+        match reader.read_exact(&mut payload) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break, // truncated
+            Err(e) => return Err(e),
         }
 
-        Ok(events)
+        // Read CRC
+        let mut crc_buf = [0u8; 4];
+        match reader.read_exact(&mut crc_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break, // truncated
+            Err(e) => return Err(e),
+        }
+        let stored_crc = u32::from_le_bytes(crc_buf);
+        let computed_crc = crc32fast::hash(&payload);
+
+        if stored_crc != computed_crc {
+            // Corrupt entry — stop replaying
+            break;
+        }
+
+        match decode(&payload) {
+            Some(event) => events.push(event),
+            None => break, // corrupt payload
+        }
+    }
+
+    Ok(events)
+}
+
+/// The unversioned format: `Event` as it stood before the WAL had a header.
+/// Frozen — these layouts must match what old files hold byte for byte.
+mod v0 {
+    use serde::Deserialize;
+    use ulid::Ulid;
+
+    use crate::model::{AllocOptions, Ms, ResourceSettings, Span};
+
+    #[derive(Deserialize)]
+    pub(super) enum Event {
+        ResourceCreated {
+            id: Ulid,
+            parent_id: Option<Ulid>,
+            name: Option<String>,
+            capacity: u32,
+            buffer_after: Option<Ms>,
+        },
+        ResourceUpdated {
+            id: Ulid,
+            name: Option<String>,
+            capacity: u32,
+            buffer_after: Option<Ms>,
+        },
+        ResourceDeleted {
+            id: Ulid,
+        },
+        RuleAdded {
+            id: Ulid,
+            resource_id: Ulid,
+            span: Span,
+            blocking: bool,
+        },
+        RuleUpdated {
+            id: Ulid,
+            resource_id: Ulid,
+            span: Span,
+            blocking: bool,
+        },
+        RuleRemoved {
+            id: Ulid,
+            resource_id: Ulid,
+        },
+        HoldPlaced {
+            id: Ulid,
+            resource_id: Ulid,
+            span: Span,
+            expires_at: Ms,
+        },
+        HoldReleased {
+            id: Ulid,
+            resource_id: Ulid,
+        },
+        BookingConfirmed {
+            id: Ulid,
+            resource_id: Ulid,
+            span: Span,
+            label: Option<String>,
+        },
+        BookingCancelled {
+            id: Ulid,
+            resource_id: Ulid,
+        },
+    }
+
+    /// Fields added since take their defaults, which reproduce the old
+    /// behaviour. A cancellation's time was never recorded, so it reads as 0.
+    impl From<Event> for super::Event {
+        fn from(event: Event) -> Self {
+            match event {
+                Event::ResourceCreated { id, parent_id, name, capacity, buffer_after } => {
+                    Self::ResourceCreated {
+                        id,
+                        parent_id,
+                        name,
+                        capacity,
+                        buffer_after,
+                        settings: ResourceSettings::default(),
+                    }
+                }
+                Event::ResourceUpdated { id, name, capacity, buffer_after } => {
+                    Self::ResourceUpdated {
+                        id,
+                        name,
+                        capacity,
+                        buffer_after,
+                        settings: ResourceSettings::default(),
+                    }
+                }
+                Event::ResourceDeleted { id } => Self::ResourceDeleted { id },
+                Event::RuleAdded { id, resource_id, span, blocking } => {
+                    Self::RuleAdded { id, resource_id, span, blocking, capacity: None }
+                }
+                Event::RuleUpdated { id, resource_id, span, blocking } => {
+                    Self::RuleUpdated { id, resource_id, span, blocking, capacity: None }
+                }
+                Event::RuleRemoved { id, resource_id } => Self::RuleRemoved { id, resource_id },
+                Event::HoldPlaced { id, resource_id, span, expires_at } => Self::HoldPlaced {
+                    id,
+                    resource_id,
+                    span,
+                    expires_at,
+                    options: AllocOptions::default(),
+                },
+                Event::HoldReleased { id, resource_id } => Self::HoldReleased { id, resource_id },
+                Event::BookingConfirmed { id, resource_id, span, label } => {
+                    Self::BookingConfirmed {
+                        id,
+                        resource_id,
+                        span,
+                        label,
+                        options: AllocOptions::default(),
+                    }
+                }
+                Event::BookingCancelled { id, resource_id } => {
+                    Self::BookingCancelled { id, resource_id, reason: None, at: 0 }
+                }
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::model::ResourceSettings;
     use ulid::Ulid;
 
     fn tmp_path(name: &str) -> std::path::PathBuf {
@@ -185,12 +374,14 @@ mod tests {
                 name: None,
                 capacity: 1,
                 buffer_after: None,
+                settings: ResourceSettings::default(),
             },
             Event::RuleAdded {
                 id: Ulid::new(),
                 resource_id: Ulid::new(),
                 span: crate::model::Span::new(1000, 2000),
                 blocking: false,
+                capacity: None,
             },
         ];
 
@@ -219,6 +410,7 @@ mod tests {
             name: None,
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         };
 
         {
@@ -289,12 +481,14 @@ mod tests {
                 name: Some("Room".into()),
                 capacity: 1,
                 buffer_after: None,
+                settings: ResourceSettings::default(),
             }).unwrap();
             wal.append(&Event::RuleAdded {
                 id: rule_id,
                 resource_id: rid,
                 span: crate::model::Span::new(0, 1000),
                 blocking: false,
+                capacity: None,
             }).unwrap();
             wal.append(&Event::RuleRemoved { id: rule_id, resource_id: rid }).unwrap();
             // 10 more churn events
@@ -305,6 +499,7 @@ mod tests {
                     resource_id: rid,
                     span: crate::model::Span::new(0, 500),
                     blocking: false,
+                    capacity: None,
                 }).unwrap();
                 wal.append(&Event::RuleRemoved { id: tmp_id, resource_id: rid }).unwrap();
             }
@@ -320,6 +515,7 @@ mod tests {
             name: Some("Room".into()),
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         }];
 
         {
@@ -350,6 +546,7 @@ mod tests {
             name: None,
             capacity: 1,
             buffer_after: None,
+            settings: ResourceSettings::default(),
         }];
 
         let new_event = Event::RuleAdded {
@@ -357,6 +554,7 @@ mod tests {
             resource_id: rid,
             span: crate::model::Span::new(1000, 2000),
             blocking: false,
+            capacity: None,
         };
 
         {
@@ -389,6 +587,7 @@ mod tests {
                 name: None,
                 capacity: 1,
                 buffer_after: None,
+                settings: ResourceSettings::default(),
             })
             .collect();
 
@@ -406,4 +605,41 @@ mod tests {
 
         let _ = fs::remove_file(&path);
     }
+
+    /// `tests/fixtures/baseline.wal` was written by the unversioned WAL.
+    #[test]
+    fn replays_and_migrates_unversioned_wal() {
+        let path = tmp_path("unversioned.wal");
+        fs::write(&path, include_bytes!("../tests/fixtures/baseline.wal")).unwrap();
+
+        let events = Wal::replay(&path).unwrap();
+        assert_eq!(events.len(), 17);
+        assert_eq!(
+            events[2],
+            Event::ResourceUpdated {
+                id: Ulid(2),
+                name: Some("Room 1".into()),
+                capacity: 2,
+                buffer_after: Some(900_000),
+                settings: ResourceSettings::default(),
+            }
+        );
+        assert_eq!(
+            events[14],
+            Event::BookingCancelled { id: Ulid(31), resource_id: Ulid(2), reason: None, at: 0 }
+        );
+
+        // Opening rewrites it in the current format, and appends follow on.
+        let extra = Event::ResourceDeleted { id: Ulid(1) };
+        {
+            let mut wal = Wal::open(&path).unwrap();
+            wal.append(&extra).unwrap();
+        }
+        assert_eq!(fs::read(&path).unwrap()[..4], MAGIC);
+        let mut expected = events;
+        expected.push(extra);
+        assert_eq!(Wal::replay(&path).unwrap(), expected);
+
+        let _ = fs::remove_file(&path);
+    }
 }
@@ -27,7 +27,7 @@ use tokio_util::codec::Framed;
 use ulid::Ulid;
 
 use crate::auth::DeltaTAuthSource;
-use crate::engine::Engine;
+use crate::engine::{ConflictDetail, Engine, EngineError};
 use crate::limits::{MAX_QUERY_LEN, MAX_SUBSCRIPTIONS_PER_CONNECTION};
 use crate::model::*;
 use crate::sql::{self, Command};
@@ -122,85 +122,81 @@ impl DeltaTHandler {
         cmd: Command,
     ) -> PgWireResult<Vec<Response>> {
         match cmd {
-            Command::InsertResource {
-                id,
-                parent_id,
-                name,
-                capacity,
-                buffer_after,
-            } => {
-                engine
-                    .create_resource(id, parent_id, name, capacity, buffer_after)
-                    .await
-                    .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
+            cmd @ (Command::InsertResource { .. }
+            | Command::UpdateResource { .. }
+            | Command::DeleteResource { .. }
+            | Command::InsertRule { .. }
+            | Command::UpdateRule { .. }
+            | Command::DeleteRule { .. }
+            | Command::ReplaceRules { .. }
+            | Command::InsertHold { .. }
+            | Command::DeleteHold { .. }
+            | Command::InsertBooking { .. }
+            | Command::BatchInsertBookings { .. }
+            | Command::DeleteBooking { .. }
+            | Command::DeleteInRange { .. }
+            | Command::UpdateBookingStatus { .. }) => {
+                let (tag, ids) = execute_write(engine, cmd).await?;
+                Ok(vec![Response::Execution(Tag::new(tag).with_rows(ids.len()))])
             }
-            Command::DeleteResource { id } => {
-                engine.delete_resource(id).await.map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+            Command::Returning { command, columns } => execute_returning(engine, *command, columns).await,
+            Command::Explain { command } => {
+                let (reached_write, result) = engine.dry_run(Box::pin(self.execute_command_inner(engine, *command))).await;
+                let row = match result {
+                    Err(PgWireError::UserError(info)) if !reached_write => {
+                        vec![Cell::Bool(false), Cell::Text(Some(info.message)), Cell::Text(info.detail)]
+                    }
+                    Err(e) if !reached_write => return Err(e),
+                    _ => vec![Cell::Bool(true), Cell::Text(None), Cell::Text(None)],
+                };
+                Ok(vec![Response::Query(query_response(explain_schema(), vec![row]))])
             }
-            Command::InsertRule {
-                id,
-                resource_id,
-                start,
-                end,
-                blocking,
-            } => {
-                engine
-                    .add_rule(id, resource_id, Span::new(start, end), blocking)
+            Command::CloneSubtree { source_id, parent_id, include_rules } => {
+                let mapping = engine
+                    .clone_subtree(source_id, parent_id, include_rules)
                     .await
                     .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
-            }
-            Command::DeleteRule { id } => {
-                engine.remove_rule(id).await.map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+                let rows = mapping
+                    .into_iter()
+                    .map(|(source, id)| vec![Cell::Text(Some(source.to_string())), Cell::Text(Some(id.to_string()))])
+                    .collect();
+                let mut response = query_response(resource_clones_schema(), rows);
+                response.set_command_tag("INSERT");
+                Ok(vec![Response::Query(response)])
             }
-            Command::InsertHold {
-                id,
-                resource_id,
-                start,
-                end,
-                expires_at,
-            } => {
-                engine
-                    .place_hold(id, resource_id, Span::new(start, end), expires_at)
+            Command::BookBlock { parent_id, count, start, end, label } => {
+                let chosen = engine
+                    .book_adjacent_children(parent_id, count, Span::new(start, end), label)
                     .await
                     .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
-            }
-            Command::DeleteHold { id } => {
-                engine.release_hold(id).await.map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+                Ok(chosen_bookings_response(chosen))
             }
-            Command::InsertBooking {
-                id,
-                resource_id,
-                start,
-                end,
-                label,
-            } => {
-                engine
-                    .confirm_booking(id, resource_id, Span::new(start, end), label)
+            Command::BookItinerary { steps, start, end, label } => {
+                let chosen = engine
+                    .book_itinerary(&steps, Span::new(start, end), label)
                     .await
                     .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
-            }
-            Command::BatchInsertBookings { bookings } => {
-                let count = bookings.len();
-                let batch: Vec<_> = bookings
+                let rows = chosen
                     .into_iter()
-                    .map(|(id, resource_id, start, end, label)| (id, resource_id, Span::new(start, end), label))
+                    .map(|(id, rid, span)| {
+                        vec![
+                            Cell::Text(Some(id.to_string())),
+                            Cell::Text(Some(rid.to_string())),
+                            Cell::Int(Some(span.start)),
+                            Cell::Int(Some(span.end)),
+                        ]
+                    })
                     .collect();
-                engine
-                    .batch_confirm_bookings(batch)
+                let mut response = query_response(itinerary_bookings_schema(), rows);
+                response.set_command_tag("INSERT");
+                Ok(vec![Response::Query(response)])
+            }
+            Command::BookRequirements { requirements, start, end, label } => {
+                let chosen = engine
+                    .book_requirements(&requirements, Span::new(start, end), label)
                     .await
                     .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(count))])
-            }
-            Command::DeleteBooking { id } => {
-                engine.cancel_booking(id).await.map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+                Ok(chosen_bookings_response(chosen))
             }
             Command::SelectAvailability {
                 resource_id,
@@ -232,6 +228,46 @@ impl DeltaTHandler {
                     stream::iter(rows),
                 ))])
             }
+            Command::SelectBusy { resource_id, start, end } => {
+                let rows = engine
+                    .compute_busy(resource_id, start, end)
+                    .await
+                    .map_err(engine_err)?
+                    .into_iter()
+                    .map(|segment| {
+                        let ids: Vec<String> = segment.interval_ids.iter().map(|id| id.to_string()).collect();
+                        vec![
+                            Cell::Int(Some(segment.span.start)),
+                            Cell::Int(Some(segment.span.end)),
+                            Cell::Text(Some(segment.cause.as_str().into())),
+                            Cell::Text(Some(segment.source_id.to_string())),
+                            Cell::Text(Some(serde_json::to_string(&ids).unwrap_or_default())),
+                        ]
+                    })
+                    .collect();
+                Ok(vec![Response::Query(query_response(busy_schema(), rows))])
+            }
+            Command::SelectUtilization { resource_id, descendants, start, end, bucket } => {
+                let rid = resource_id.to_string();
+                let rows = engine
+                    .compute_utilization(resource_id, descendants, start, end, bucket)
+                    .await
+                    .map_err(engine_err)?
+                    .into_iter()
+                    .map(|b| {
+                        vec![
+                            Cell::Text(Some(rid.clone())),
+                            Cell::Int(Some(b.span.start)),
+                            Cell::Int(Some(b.span.end)),
+                            Cell::Int(Some(b.open_ms)),
+                            Cell::Int(Some(b.booked_ms)),
+                            Cell::Int(Some(b.held_ms)),
+                            Cell::Float(b.utilization),
+                        ]
+                    })
+                    .collect();
+                Ok(vec![Response::Query(query_response(utilization_schema(), rows))])
+            }
             Command::SelectMultiAvailability {
                 resource_ids,
                 start,
@@ -261,19 +297,16 @@ impl DeltaTHandler {
                     stream::iter(rows),
                 ))])
             }
-            Command::UpdateResource { id, name, capacity, buffer_after } => {
-                engine
-                    .update_resource(id, name, capacity, buffer_after)
-                    .await
-                    .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("UPDATE").with_rows(1))])
-            }
-            Command::UpdateRule { id, start, end, blocking } => {
-                engine
-                    .update_rule(id, Span::new(start, end), blocking)
+            Command::SelectRequirementAvailability { requirements, start, end, min_duration } => {
+                let slots = engine
+                    .compute_requirement_availability(&requirements, start, end, min_duration)
                     .await
                     .map_err(engine_err)?;
-                Ok(vec![Response::Execution(Tag::new("UPDATE").with_rows(1))])
+                let rows = slots
+                    .into_iter()
+                    .map(|slot| vec![Cell::Int(Some(slot.start)), Cell::Int(Some(slot.end))])
+                    .collect();
+                Ok(vec![Response::Query(query_response(multi_availability_schema(), rows))])
             }
             Command::SelectResources { parent_id } => {
                 let all = engine.list_resources();
@@ -283,30 +316,148 @@ impl DeltaTHandler {
                     Some(Some(pid)) => all.into_iter().filter(|r| r.parent_id == Some(pid)).collect(),
                 };
 
-                let schema = Arc::new(resources_schema());
-                let rows: Vec<PgWireResult<_>> = filtered
+                table_response(resources_schema(), filtered.iter().map(resource_row).collect())
+            }
+            Command::SelectRules { resource_id } => {
+                let rules = engine.get_rules(resource_id).await.map_err(engine_err)?;
+                rules_response(rules)
+            }
+            Command::SelectRulesById { ids } => rules_response(engine.get_rules_by_id(&ids).await),
+            Command::SelectBookings { resource_id, filter } => {
+                let mut bookings = match filter.status {
+                    Some(status) => engine.get_bookings_by_status(status, Some(resource_id)).await,
+                    None => engine.get_bookings(resource_id).await.map_err(engine_err)?,
+                };
+                bookings.retain(|b| filter.matches(b));
+                bookings_response(bookings)
+            }
+            Command::SelectBookingsByStatus { status, filter } => {
+                let mut bookings = engine.get_bookings_by_status(status, None).await;
+                bookings.retain(|b| filter.matches(b));
+                bookings_response(bookings)
+            }
+            Command::SelectBookingsById { ids, filter } => {
+                let mut bookings = engine.get_bookings_by_id(&ids).await;
+                bookings.retain(|b| filter.matches(b));
+                bookings_response(bookings)
+            }
+            Command::SelectBookingsByOwner { owner, resource_id, filter } => {
+                let mut bookings = if filter.status == Some(BookingStatus::Cancelled) {
+                    let mut cancelled = engine.get_bookings_by_status(BookingStatus::Cancelled, resource_id).await;
+                    cancelled.retain(|b| b.owner.as_deref() == Some(owner.as_str()));
+                    cancelled
+                } else {
+                    engine.get_bookings_by_owner(&owner).await
+                };
+                bookings.retain(|b| resource_id.is_none_or(|rid| b.resource_id == rid) && filter.matches(b));
+                bookings_response(bookings)
+            }
+            Command::SelectHolds { resource_id } => {
+                let holds = engine.get_holds(resource_id).await.map_err(engine_err)?;
+                holds_response(holds)
+            }
+            Command::SelectHoldsById { ids } => holds_response(engine.get_holds_by_id(&ids).await),
+            Command::SelectHoldsByOwner { owner, resource_id } => {
+                let mut holds = engine.get_holds_by_owner(&owner).await;
+                if let Some(rid) = resource_id {
+                    holds.retain(|h| h.resource_id == rid);
+                }
+                holds_response(holds)
+            }
+            Command::InsertCalendar { id, name } => {
+                engine.create_calendar(id, name).await.map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
+            }
+            Command::DeleteCalendar { id } => {
+                engine.delete_calendar(id).await.map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+            }
+            Command::InsertCalendarRule {
+                id,
+                calendar_id,
+                start,
+                end,
+                blocking,
+            } => {
+                engine
+                    .add_calendar_rule(id, calendar_id, Span::new(start, end), blocking)
+                    .await
+                    .map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
+            }
+            Command::UpdateCalendarRule { id, start, end, blocking } => {
+                engine
+                    .update_calendar_rule(id, Span::new(start, end), blocking)
+                    .await
+                    .map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("UPDATE").with_rows(1))])
+            }
+            Command::DeleteCalendarRule { id } => {
+                engine.remove_calendar_rule(id).await.map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+            }
+            Command::AttachCalendar { calendar_id, resource_id } => {
+                engine
+                    .attach_calendar(calendar_id, resource_id)
+                    .await
+                    .map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
+            }
+            Command::DetachCalendar { calendar_id, resource_id } => {
+                engine
+                    .detach_calendar(calendar_id, resource_id)
+                    .await
+                    .map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+            }
+            Command::InsertGroup { id, name, limit, members } => {
+                engine.create_group(id, name, limit, members).await.map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("INSERT").with_rows(1))])
+            }
+            Command::DeleteGroup { id } => {
+                engine.delete_group(id).await.map_err(engine_err)?;
+                Ok(vec![Response::Execution(Tag::new("DELETE").with_rows(1))])
+            }
+            Command::SelectGroups => {
+                let rows = engine
+                    .list_groups()
                     .into_iter()
-                    .map(|r| {
+                    .map(|g| {
+                        let members: Vec<String> = g.members.iter().map(|m| m.to_string()).collect();
+                        vec![
+                            Cell::Text(Some(g.id.to_string())),
+                            Cell::Text(g.name),
+                            Cell::Int(Some(g.limit as i64)),
+                            Cell::Text(Some(serde_json::to_string(&members).unwrap_or_default())),
+                        ]
+                    })
+                    .collect();
+                Ok(vec![Response::Query(query_response(exclusion_groups_schema(), rows))])
+            }
+            Command::SelectCalendars => {
+                let schema = Arc::new(calendars_schema());
+                let rows: Vec<PgWireResult<_>> = engine
+                    .list_calendars()
+                    .await
+                    .into_iter()
+                    .map(|c| {
                         let mut encoder = DataRowEncoder::new(schema.clone());
-                        encoder.encode_field(&r.id.to_string())?;
-                        encoder.encode_field(&r.parent_id.map(|p| p.to_string()))?;
-                        encoder.encode_field(&r.name)?;
-                        encoder.encode_field(&(r.capacity as i64))?;
-                        encoder.encode_field(&r.buffer_after)?;
+                        encoder.encode_field(&c.id.to_string())?;
+                        encoder.encode_field(&c.name)?;
                         Ok(encoder.take_row())
                     })
                     .collect();
                 Ok(vec![Response::Query(QueryResponse::new(schema, stream::iter(rows)))])
             }
-            Command::SelectRules { resource_id } => {
-                let rules = engine.get_rules(resource_id).await.map_err(engine_err)?;
-                let schema = Arc::new(rules_schema());
+            Command::SelectCalendarRules { calendar_id } => {
+                let rules = engine.get_calendar_rules(calendar_id).await.map_err(engine_err)?;
+                let schema = Arc::new(calendar_rules_schema());
                 let rows: Vec<PgWireResult<_>> = rules
                     .into_iter()
                     .map(|r| {
                         let mut encoder = DataRowEncoder::new(schema.clone());
                         encoder.encode_field(&r.id.to_string())?;
-                        encoder.encode_field(&r.resource_id.to_string())?;
+                        encoder.encode_field(&r.calendar_id.to_string())?;
                         encoder.encode_field(&r.start)?;
                         encoder.encode_field(&r.end)?;
                         encoder.encode_field(&r.blocking)?;
@@ -315,35 +466,35 @@ impl DeltaTHandler {
                     .collect();
                 Ok(vec![Response::Query(QueryResponse::new(schema, stream::iter(rows)))])
             }
-            Command::SelectBookings { resource_id } => {
-                let bookings = engine.get_bookings(resource_id).await.map_err(engine_err)?;
-                let schema = Arc::new(bookings_schema());
-                let rows: Vec<PgWireResult<_>> = bookings
-                    .into_iter()
-                    .map(|b| {
-                        let mut encoder = DataRowEncoder::new(schema.clone());
-                        encoder.encode_field(&b.id.to_string())?;
-                        encoder.encode_field(&b.resource_id.to_string())?;
-                        encoder.encode_field(&b.start)?;
-                        encoder.encode_field(&b.end)?;
-                        encoder.encode_field(&b.label)?;
-                        Ok(encoder.take_row())
-                    })
-                    .collect();
-                Ok(vec![Response::Query(QueryResponse::new(schema, stream::iter(rows)))])
-            }
-            Command::SelectHolds { resource_id } => {
-                let holds = engine.get_holds(resource_id).await.map_err(engine_err)?;
-                let schema = Arc::new(holds_schema());
-                let rows: Vec<PgWireResult<_>> = holds
+            Command::SelectCalendarAttachments { calendar_id, resource_id } => {
+                let pairs: Vec<(Ulid, Ulid)> = match (calendar_id, resource_id) {
+                    (Some(cid), Some(rid)) => engine
+                        .get_resource_calendars(rid)
+                        .await
+                        .into_iter()
+                        .filter(|c| *c == cid)
+                        .map(|c| (c, rid))
+                        .collect(),
+                    (Some(cid), None) => engine
+                        .get_calendar_resources(&cid)
+                        .into_iter()
+                        .map(|r| (cid, r))
+                        .collect(),
+                    (None, Some(rid)) => engine
+                        .get_resource_calendars(rid)
+                        .await
+                        .into_iter()
+                        .map(|c| (c, rid))
+                        .collect(),
+                    (None, None) => Vec::new(),
+                };
+                let schema = Arc::new(calendar_attachments_schema());
+                let rows: Vec<PgWireResult<_>> = pairs
                     .into_iter()
-                    .map(|h| {
+                    .map(|(cid, rid)| {
                         let mut encoder = DataRowEncoder::new(schema.clone());
-                        encoder.encode_field(&h.id.to_string())?;
-                        encoder.encode_field(&h.resource_id.to_string())?;
-                        encoder.encode_field(&h.start)?;
-                        encoder.encode_field(&h.end)?;
-                        encoder.encode_field(&h.expires_at)?;
+                        encoder.encode_field(&cid.to_string())?;
+                        encoder.encode_field(&rid.to_string())?;
                         Ok(encoder.take_row())
                     })
                     .collect();
@@ -373,6 +524,192 @@ impl DeltaTHandler {
     }
 }
 
+/// Run an INSERT, UPDATE or DELETE on resources, rules, holds or bookings.
+/// Returns the command tag and the ids of the rows it touched.
+async fn execute_write(engine: &Engine, cmd: Command) -> PgWireResult<(&'static str, Vec<Ulid>)> {
+    match cmd {
+        Command::InsertResource {
+            id,
+            parent_id,
+            name,
+            capacity,
+            buffer_after,
+            settings,
+            on_conflict,
+        } => {
+            let result = if on_conflict == OnConflict::DoUpdate {
+                engine.replace_resource(id, parent_id, name, capacity, buffer_after, settings).await
+            } else {
+                engine
+                    .create_resource_with_settings(id, parent_id, name, capacity, buffer_after, settings)
+                    .await
+            };
+            inserted(id, result, on_conflict)
+        }
+        Command::UpdateResource { id, name, capacity, buffer_after, settings } => {
+            engine
+                .update_resource_with_patch(id, name, capacity, buffer_after, settings)
+                .await
+                .map_err(engine_err)?;
+            Ok(("UPDATE", vec![id]))
+        }
+        Command::DeleteResource { id } => {
+            engine.delete_resource(id).await.map_err(engine_err)?;
+            Ok(("DELETE", vec![id]))
+        }
+        Command::InsertRule {
+            id,
+            resource_id,
+            start,
+            end,
+            blocking,
+            capacity,
+            on_conflict,
+        } => {
+            let span = Span::new(start, end);
+            let result = match (on_conflict, capacity) {
+                (OnConflict::DoUpdate, _) => engine.replace_rule(id, resource_id, span, blocking, capacity).await,
+                (_, Some(capacity)) => engine.add_capacity_rule(id, resource_id, span, capacity).await,
+                (_, None) => engine.add_rule(id, resource_id, span, blocking).await,
+            };
+            inserted(id, result, on_conflict)
+        }
+        Command::UpdateRule { id, start, end, blocking, capacity } => {
+            let span = Span::new(start, end);
+            match capacity {
+                Some(capacity) => engine.update_capacity_rule(id, span, capacity).await,
+                None => engine.update_rule(id, span, blocking).await,
+            }
+            .map_err(engine_err)?;
+            Ok(("UPDATE", vec![id]))
+        }
+        Command::DeleteRule { id } => {
+            engine.remove_rule(id).await.map_err(engine_err)?;
+            Ok(("DELETE", vec![id]))
+        }
+        Command::ReplaceRules { resource_id, start, end, rules } => {
+            let rules = rules
+                .into_iter()
+                .map(|(id, start, end, blocking, capacity)| (id, Span::new(start, end), blocking, capacity))
+                .collect();
+            let ids = engine
+                .replace_rules(resource_id, Span::new(start, end), rules)
+                .await
+                .map_err(engine_err)?;
+            Ok(("INSERT", ids))
+        }
+        Command::InsertHold {
+            id,
+            resource_id,
+            start,
+            end,
+            expires_at,
+            options,
+            on_conflict,
+        } => {
+            let span = Span::new(start, end);
+            let result = if on_conflict == OnConflict::DoUpdate {
+                engine.replace_hold(id, resource_id, span, expires_at, options).await
+            } else {
+                engine.place_hold_with_options(id, resource_id, span, expires_at, options).await
+            };
+            inserted(id, result, on_conflict)
+        }
+        Command::DeleteHold { id } => {
+            engine.release_hold(id).await.map_err(engine_err)?;
+            Ok(("DELETE", vec![id]))
+        }
+        Command::InsertBooking {
+            id,
+            resource_id,
+            start,
+            end,
+            label,
+            options,
+            on_conflict,
+            suggest,
+        } => {
+            let span = Span::new(start, end);
+            let mut result = if on_conflict == OnConflict::DoUpdate {
+                engine.replace_booking(id, resource_id, span, label, options).await
+            } else {
+                engine.confirm_booking_with_options(id, resource_id, span, label, options).await
+            };
+            if suggest > 0
+                && let Err(EngineError::Conflict(_, detail) | EngineError::CapacityExceeded(_, detail)) = &mut result
+            {
+                // Suggestions are best effort: the refusal is what gets reported.
+                detail.alternatives = engine.suggest_alternatives(resource_id, span, suggest).await.unwrap_or_default();
+            }
+            inserted(id, result, on_conflict)
+        }
+        Command::BatchInsertBookings { bookings, on_conflict } => {
+            let batch: Vec<_> = bookings
+                .into_iter()
+                .map(|(id, resource_id, start, end, label, options)| {
+                    (id, resource_id, Span::new(start, end), label, options)
+                })
+                .collect();
+            let ids = engine
+                .batch_upsert_bookings(batch, on_conflict)
+                .await
+                .map_err(engine_err)?;
+            Ok(("INSERT", ids))
+        }
+        Command::DeleteBooking { id } => {
+            engine.cancel_booking(id).await.map_err(engine_err)?;
+            Ok(("DELETE", vec![id]))
+        }
+        Command::DeleteInRange { class, resource_id, descendants, start, end } => {
+            let ids = engine
+                .delete_in_range(class, resource_id, descendants, Span::new(start, end))
+                .await
+                .map_err(engine_err)?;
+            Ok(("DELETE", ids))
+        }
+        Command::UpdateBookingStatus { id, status, reason } => {
+            if status == BookingStatus::Cancelled {
+                engine.cancel_booking_with_reason(id, reason).await.map_err(engine_err)?;
+            } else {
+                engine.set_booking_status(id, status).await.map_err(engine_err)?;
+            }
+            Ok(("UPDATE", vec![id]))
+        }
+        other => unreachable!("{} is not a row write", crate::observability::command_label(&other)),
+    }
+}
+
+/// Run a write and answer with the rows it touched. Rows a DELETE removes
+/// are read before it runs; a deleted booking is read after, as cancelled.
+async fn execute_returning(
+    engine: &Engine,
+    cmd: Command,
+    columns: Option<Vec<String>>,
+) -> PgWireResult<Vec<Response>> {
+    let table = RowTable::of(&cmd);
+    let removed = match &cmd {
+        Command::DeleteResource { id } | Command::DeleteRule { id } | Command::DeleteHold { id } => {
+            Some(table.rows(engine, &[*id]).await)
+        }
+        _ => None,
+    };
+    let (tag, ids) = execute_write(engine, cmd).await?;
+    let rows = match removed {
+        Some(rows) => rows,
+        None => table.rows(engine, &ids).await,
+    };
+    let schema = table.schema();
+    let indices = column_indices(&schema, columns.as_deref()).map_err(undefined_column)?;
+    let schema = indices.iter().map(|&i| schema[i].clone()).collect();
+    let rows = rows
+        .into_iter()
+        .map(|row| indices.iter().map(|&i| row[i].clone()).collect())
+        .collect();
+    let mut response = query_response(schema, rows);
+    response.set_command_tag(tag);
+    Ok(vec![Response::Query(response)])
+}
+
 fn availability_schema() -> Vec<FieldInfo> {
     vec![
         FieldInfo::new(
@@ -401,6 +738,19 @@ fn resources_schema() -> Vec<FieldInfo> {
         FieldInfo::new("name".into(), None, None, Type::VARCHAR, FieldFormat::Text),
         FieldInfo::new("capacity".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("buffer_after".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("inheritance".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("buffer_before".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("min_duration".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("max_duration".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("min_lead_time".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("max_horizon".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("granularity_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("granularity_offset_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("quota_window_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("quota_max_count".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("quota_max_duration_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("components".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("position".into(), None, None, Type::INT8, FieldFormat::Text),
     ]
 }
 
@@ -411,6 +761,7 @@ fn rules_schema() -> Vec<FieldInfo> {
         FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("blocking".into(), None, None, Type::BOOL, FieldFormat::Text),
+        FieldInfo::new("capacity".into(), None, None, Type::INT8, FieldFormat::Text),
     ]
 }
 
@@ -421,6 +772,13 @@ fn bookings_schema() -> Vec<FieldInfo> {
         FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("label".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("buffer_before".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("buffer_after".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("owner".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("metadata".into(), None, None, Type::JSONB, FieldFormat::Text),
+        FieldInfo::new("status".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("cancel_reason".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("cancelled_at".into(), None, None, Type::INT8, FieldFormat::Text),
     ]
 }
 
@@ -431,6 +789,99 @@ fn holds_schema() -> Vec<FieldInfo> {
         FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
         FieldInfo::new("expires_at".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("buffer_before".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("buffer_after".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("owner".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("metadata".into(), None, None, Type::JSONB, FieldFormat::Text),
+    ]
+}
+
+fn calendars_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("name".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+fn calendar_rules_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("calendar_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("blocking".into(), None, None, Type::BOOL, FieldFormat::Text),
+    ]
+}
+
+fn calendar_attachments_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("calendar_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("resource_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+fn exclusion_groups_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("name".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("max_concurrent".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("members".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+/// Blocked segments of a resource; `interval_ids` is a JSON array.
+fn busy_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("cause".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("source_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("interval_ids".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+fn utilization_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("resource_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("open_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("booked_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("held_ms".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("utilization".into(), None, None, Type::FLOAT8, FieldFormat::Text),
+    ]
+}
+
+/// Outcome of an `EXPLAIN` dry run; `detail` is the error's detail field.
+fn explain_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("would_succeed".into(), None, None, Type::BOOL, FieldFormat::Text),
+        FieldInfo::new("error".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("detail".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+fn itinerary_bookings_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("resource_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("start".into(), None, None, Type::INT8, FieldFormat::Text),
+        FieldInfo::new("end".into(), None, None, Type::INT8, FieldFormat::Text),
+    ]
+}
+
+/// Bookings made on resources the engine chose, as `(id, resource_id)`.
+fn chosen_bookings_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("resource_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+    ]
+}
+
+fn resource_clones_schema() -> Vec<FieldInfo> {
+    vec![
+        FieldInfo::new("source_id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
+        FieldInfo::new("id".into(), None, None, Type::VARCHAR, FieldFormat::Text),
     ]
 }
 
@@ -786,12 +1237,44 @@ pub async fn process_connection(
 }
 
 fn schema_for_sql(sql: &str) -> Vec<FieldInfo> {
+    if sql.trim_start().to_uppercase().starts_with("EXPLAIN") {
+        return explain_schema();
+    }
+    if let Some((table, columns)) = sql::returning_clause(sql) {
+        let Some(table) = RowTable::named(&table) else { return vec![] };
+        let schema = table.schema();
+        // Unknown columns are reported when the statement runs.
+        return column_indices(&schema, columns.as_deref())
+            .map(|indices| indices.iter().map(|&i| schema[i].clone()).collect())
+            .unwrap_or_default();
+    }
     let upper = sql.to_uppercase();
+    if upper.contains("RESOURCE_CLONES") {
+        return resource_clones_schema();
+    }
+    if upper.contains("ITINERARY_BOOKINGS") {
+        return itinerary_bookings_schema();
+    }
+    if upper.contains("BLOCK_BOOKINGS") || upper.contains("REQUIREMENT_BOOKINGS") {
+        return chosen_bookings_schema();
+    }
     if !upper.contains("SELECT") {
         return vec![];
     }
-    if upper.contains("AVAILABILITY") {
-        if upper.contains(" IN ") {
+    if upper.contains("UTILIZATION") {
+        utilization_schema()
+    } else if upper.contains("BUSY") {
+        busy_schema()
+    } else if upper.contains("EXCLUSION_GROUPS") {
+        exclusion_groups_schema()
+    } else if upper.contains("CALENDAR_RULES") {
+        calendar_rules_schema()
+    } else if upper.contains("CALENDAR_ATTACHMENTS") {
+        calendar_attachments_schema()
+    } else if upper.contains("CALENDARS") {
+        calendars_schema()
+    } else if upper.contains("AVAILABILITY") {
+        if upper.contains(" IN ") || upper.contains("REQUIREMENTS") {
             multi_availability_schema()
         } else {
             availability_schema()
@@ -809,11 +1292,262 @@ fn schema_for_sql(sql: &str) -> Vec<FieldInfo> {
     }
 }
 
-fn engine_err(e: crate::engine::EngineError) -> PgWireError {
+/// Ids touched by a single-row INSERT. Under `ON CONFLICT DO NOTHING` an
+/// existing id is a skipped row rather than an error.
+fn inserted(
+    id: Ulid,
+    result: Result<(), EngineError>,
+    on_conflict: OnConflict,
+) -> PgWireResult<(&'static str, Vec<Ulid>)> {
+    match result {
+        Ok(()) => Ok(("INSERT", vec![id])),
+        Err(EngineError::AlreadyExists(_)) if on_conflict == OnConflict::DoNothing => Ok(("INSERT", vec![])),
+        Err(e) => Err(engine_err(e)),
+    }
+}
+
+/// Tables whose writes accept `RETURNING`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RowTable {
+    Resources,
+    Rules,
+    Holds,
+    Bookings,
+}
+
+impl RowTable {
+    fn of(cmd: &Command) -> Self {
+        match cmd {
+            Command::InsertResource { .. } | Command::UpdateResource { .. } | Command::DeleteResource { .. } => {
+                RowTable::Resources
+            }
+            Command::InsertRule { .. } | Command::UpdateRule { .. } | Command::DeleteRule { .. } => RowTable::Rules,
+            Command::InsertHold { .. } | Command::DeleteHold { .. } => RowTable::Holds,
+            _ => RowTable::Bookings,
+        }
+    }
+
+    fn named(table: &str) -> Option<Self> {
+        match table {
+            "resources" => Some(RowTable::Resources),
+            "rules" => Some(RowTable::Rules),
+            "holds" => Some(RowTable::Holds),
+            "bookings" => Some(RowTable::Bookings),
+            _ => None,
+        }
+    }
+
+    fn schema(self) -> Vec<FieldInfo> {
+        match self {
+            RowTable::Resources => resources_schema(),
+            RowTable::Rules => rules_schema(),
+            RowTable::Holds => holds_schema(),
+            RowTable::Bookings => bookings_schema(),
+        }
+    }
+
+    async fn rows(self, engine: &Engine, ids: &[Ulid]) -> Vec<Vec<Cell>> {
+        match self {
+            RowTable::Resources => engine.get_resources_by_id(ids).await.iter().map(resource_row).collect(),
+            RowTable::Rules => engine.get_rules_by_id(ids).await.iter().map(rule_row).collect(),
+            RowTable::Holds => engine.get_holds_by_id(ids).await.iter().map(hold_row).collect(),
+            RowTable::Bookings => engine.get_bookings_by_id(ids).await.iter().map(booking_row).collect(),
+        }
+    }
+}
+
+/// Positions of `columns` in `schema`, or every position for `None`.
+/// Errors with the first unknown column.
+fn column_indices(schema: &[FieldInfo], columns: Option<&[String]>) -> Result<Vec<usize>, String> {
+    let Some(columns) = columns else { return Ok((0..schema.len()).collect()) };
+    columns
+        .iter()
+        .map(|c| schema.iter().position(|f| f.name() == c).ok_or_else(|| c.clone()))
+        .collect()
+}
+
+/// A column value, kept unencoded so rows can be narrowed to a `RETURNING`
+/// list before they are sent.
+#[derive(Debug, Clone)]
+enum Cell {
+    Text(Option<String>),
+    Int(Option<i64>),
+    Float(Option<f64>),
+    Bool(bool),
+}
+
+impl Cell {
+    fn encode(&self, encoder: &mut DataRowEncoder) -> PgWireResult<()> {
+        match self {
+            Cell::Text(v) => encoder.encode_field(v),
+            Cell::Int(v) => encoder.encode_field(v),
+            Cell::Float(v) => encoder.encode_field(v),
+            Cell::Bool(v) => encoder.encode_field(v),
+        }
+    }
+}
+
+fn chosen_bookings_response(chosen: Vec<(Ulid, Ulid)>) -> Vec<Response> {
+    let rows = chosen
+        .into_iter()
+        .map(|(id, rid)| vec![Cell::Text(Some(id.to_string())), Cell::Text(Some(rid.to_string()))])
+        .collect();
+    let mut response = query_response(chosen_bookings_schema(), rows);
+    response.set_command_tag("INSERT");
+    vec![Response::Query(response)]
+}
+
+fn query_response(schema: Vec<FieldInfo>, rows: Vec<Vec<Cell>>) -> QueryResponse {
+    let schema = Arc::new(schema);
+    let encoded: Vec<PgWireResult<_>> = rows
+        .into_iter()
+        .map(|row| {
+            let mut encoder = DataRowEncoder::new(schema.clone());
+            for cell in &row {
+                cell.encode(&mut encoder)?;
+            }
+            Ok(encoder.take_row())
+        })
+        .collect();
+    QueryResponse::new(schema, stream::iter(encoded))
+}
+
+fn table_response(schema: Vec<FieldInfo>, rows: Vec<Vec<Cell>>) -> PgWireResult<Vec<Response>> {
+    Ok(vec![Response::Query(query_response(schema, rows))])
+}
+
+fn resource_row(r: &ResourceInfo) -> Vec<Cell> {
+    let quota = r.settings.quota;
+    vec![
+        Cell::Text(Some(r.id.to_string())),
+        Cell::Text(r.parent_id.map(|p| p.to_string())),
+        Cell::Text(r.name.clone()),
+        Cell::Int(Some(r.capacity as i64)),
+        Cell::Int(r.buffer_after),
+        Cell::Text(Some(r.settings.inheritance.as_str().to_string())),
+        Cell::Int(r.settings.buffer_before),
+        Cell::Int(r.settings.policy.min_duration),
+        Cell::Int(r.settings.policy.max_duration),
+        Cell::Int(r.settings.policy.min_lead_time),
+        Cell::Int(r.settings.policy.max_horizon),
+        Cell::Int(r.settings.granularity_ms),
+        Cell::Int(Some(r.settings.granularity_offset_ms)),
+        Cell::Int(quota.map(|q| q.window_ms)),
+        Cell::Int(quota.and_then(|q| q.max_count).map(|c| c as i64)),
+        Cell::Int(quota.and_then(|q| q.max_duration_ms)),
+        Cell::Text(
+            r.settings
+                .is_composite()
+                .then(|| serde_json::to_string(&r.settings.components).expect("ids serialize")),
+        ),
+        Cell::Int(r.settings.position),
+    ]
+}
+
+fn rule_row(r: &RuleInfo) -> Vec<Cell> {
+    vec![
+        Cell::Text(Some(r.id.to_string())),
+        Cell::Text(Some(r.resource_id.to_string())),
+        Cell::Int(Some(r.start)),
+        Cell::Int(Some(r.end)),
+        Cell::Bool(r.blocking),
+        Cell::Int(r.capacity.map(|c| c as i64)),
+    ]
+}
+
+fn booking_row(b: &BookingInfo) -> Vec<Cell> {
+    vec![
+        Cell::Text(Some(b.id.to_string())),
+        Cell::Text(Some(b.resource_id.to_string())),
+        Cell::Int(Some(b.start)),
+        Cell::Int(Some(b.end)),
+        Cell::Text(b.label.clone()),
+        Cell::Int(b.buffer_before),
+        Cell::Int(b.buffer_after),
+        Cell::Text(b.owner.clone()),
+        Cell::Text(b.metadata.clone()),
+        Cell::Text(Some(b.status.as_str().to_string())),
+        Cell::Text(b.cancel_reason.clone()),
+        Cell::Int(b.cancelled_at),
+    ]
+}
+
+fn hold_row(h: &HoldInfo) -> Vec<Cell> {
+    vec![
+        Cell::Text(Some(h.id.to_string())),
+        Cell::Text(Some(h.resource_id.to_string())),
+        Cell::Int(Some(h.start)),
+        Cell::Int(Some(h.end)),
+        Cell::Int(Some(h.expires_at)),
+        Cell::Int(h.buffer_before),
+        Cell::Int(h.buffer_after),
+        Cell::Text(h.owner.clone()),
+        Cell::Text(h.metadata.clone()),
+    ]
+}
+
+fn rules_response(rules: Vec<RuleInfo>) -> PgWireResult<Vec<Response>> {
+    table_response(rules_schema(), rules.iter().map(rule_row).collect())
+}
+
+fn bookings_response(bookings: Vec<BookingInfo>) -> PgWireResult<Vec<Response>> {
+    table_response(bookings_schema(), bookings.iter().map(booking_row).collect())
+}
+
+fn holds_response(holds: Vec<HoldInfo>) -> PgWireResult<Vec<Response>> {
+    table_response(holds_schema(), holds.iter().map(hold_row).collect())
+}
+
+fn engine_err(e: EngineError) -> PgWireError {
+    // invalid_parameter_value for arguments rejected as such.
+    let code = if matches!(e, EngineError::InvalidArgument(_)) { "22023" } else { "P0001" };
+    let mut info = ErrorInfo::new("ERROR".into(), code.into(), e.to_string());
+    if let EngineError::Conflict(_, detail)
+    | EngineError::CapacityExceeded(_, detail)
+    | EngineError::GroupLimitReached(_, detail) = &e
+    {
+        info.detail = Some(conflict_detail_json(detail));
+    }
+    PgWireError::UserError(Box::new(info))
+}
+
+/// The detail field of a conflict error: every allocation in the way, and
+/// the saturated parts of the requested span, and any suggested alternatives.
+fn conflict_detail_json(detail: &ConflictDetail) -> String {
+    let conflicts: Vec<_> = detail
+        .intervals
+        .iter()
+        .map(|i| {
+            serde_json::json!({
+                "id": i.id.to_string(),
+                "kind": i.kind,
+                "start": i.span.start,
+                "end": i.span.end,
+                "buffered": i.buffered,
+            })
+        })
+        .collect();
+    let saturated: Vec<_> = detail
+        .saturated
+        .iter()
+        .map(|s| serde_json::json!({ "start": s.start, "end": s.end }))
+        .collect();
+    let mut json = serde_json::json!({ "conflicts": conflicts, "saturated": saturated });
+    if !detail.alternatives.is_empty() {
+        json["alternatives"] = detail
+            .alternatives
+            .iter()
+            .map(|(rid, s)| serde_json::json!({ "resource_id": rid.to_string(), "start": s.start, "end": s.end }))
+            .collect();
+    }
+    json.to_string()
+}
+
+fn undefined_column(column: String) -> PgWireError {
     PgWireError::UserError(Box::new(ErrorInfo::new(
         "ERROR".into(),
-        "P0001".into(),
-        e.to_string(),
+        "42703".into(),
+        format!("column \"{column}\" does not exist"),
     )))
 }
 
@@ -884,30 +1618,61 @@ mod tests {
     #[test]
     fn schema_for_select_resources() {
         let schema = schema_for_sql("SELECT * FROM resources");
-        assert_eq!(schema.len(), 5);
+        assert_eq!(schema.len(), 18);
         assert_eq!(schema[0].name(), "id");
         assert_eq!(schema[2].name(), "name");
+        assert_eq!(schema[5].name(), "inheritance");
+        assert_eq!(schema[6].name(), "buffer_before");
+        assert_eq!(schema[10].name(), "max_horizon");
+        assert_eq!(schema[11].name(), "granularity_ms");
+        assert_eq!(schema[13].name(), "quota_window_ms");
+        assert_eq!(schema[16].name(), "components");
+        assert_eq!(schema[17].name(), "position");
     }
 
     #[test]
     fn schema_for_select_rules() {
         let schema = schema_for_sql("SELECT * FROM rules WHERE resource_id = $1");
-        assert_eq!(schema.len(), 5);
+        assert_eq!(schema.len(), 6);
         assert_eq!(schema[4].name(), "blocking");
+        assert_eq!(schema[5].name(), "capacity");
     }
 
     #[test]
     fn schema_for_select_bookings() {
         let schema = schema_for_sql("SELECT * FROM bookings WHERE resource_id = $1");
-        assert_eq!(schema.len(), 5);
+        assert_eq!(schema.len(), 12);
         assert_eq!(schema[4].name(), "label");
+        assert_eq!(schema[5].name(), "buffer_before");
+        assert_eq!(schema[6].name(), "buffer_after");
+        assert_eq!(schema[8].name(), "metadata");
+        assert_eq!(schema[8].datatype(), &Type::JSONB);
+        assert_eq!(schema[9].name(), "status");
+        assert_eq!(schema[11].name(), "cancelled_at");
     }
 
     #[test]
     fn schema_for_select_holds() {
         let schema = schema_for_sql("SELECT * FROM holds WHERE resource_id = $1");
-        assert_eq!(schema.len(), 5);
+        assert_eq!(schema.len(), 9);
         assert_eq!(schema[4].name(), "expires_at");
+        assert_eq!(schema[6].name(), "buffer_after");
+        assert_eq!(schema[7].name(), "owner");
+        assert_eq!(schema[8].name(), "metadata");
+    }
+
+    #[test]
+    fn schema_for_select_calendar_tables() {
+        let schema = schema_for_sql("SELECT * FROM calendar_rules WHERE calendar_id = $1");
+        assert_eq!(schema.len(), 5);
+        assert_eq!(schema[1].name(), "calendar_id");
+
+        let schema = schema_for_sql("SELECT * FROM calendar_attachments WHERE resource_id = $1");
+        assert_eq!(schema.len(), 2);
+
+        let schema = schema_for_sql("SELECT * FROM calendars");
+        assert_eq!(schema.len(), 2);
+        assert_eq!(schema[1].name(), "name");
     }
 
     #[test]
@@ -922,6 +1687,19 @@ mod tests {
         assert!(schema.is_empty());
     }
 
+    #[test]
+    fn schema_for_returning() {
+        let schema = schema_for_sql(r#"INSERT INTO bookings (resource_id, start, "end") VALUES ($1, $2, $3) RETURNING id, "end""#);
+        let names: Vec<_> = schema.iter().map(|f| f.name()).collect();
+        assert_eq!(names, ["id", "end"]);
+
+        let schema = schema_for_sql("DELETE FROM holds WHERE id = $1 RETURNING *");
+        assert_eq!(schema.len(), holds_schema().len());
+
+        let schema = schema_for_sql("UPDATE bookings SET status = 'completed' WHERE id = $1 RETURNING status");
+        assert_eq!(schema[0].name(), "status");
+    }
+
     // ── substitute_params ────────────────────────────────────────
 
     fn make_portal(sql: &str, params: Vec<Option<bytes::Bytes>>) -> Portal<String> {
@@ -1134,4 +1912,248 @@ mod tests {
         let sub_cmd = rx.try_recv().unwrap();
         assert!(matches!(sub_cmd, SubscriptionCommand::UnsubscribeAll));
     }
+
+    async fn returned_rows(response: Response) -> (Vec<String>, usize) {
+        let Response::Query(mut query) = response else { panic!("expected rows") };
+        let names = query.row_schema().iter().map(|f| f.name().to_string()).collect();
+        let rows = query.data_rows().count().await;
+        (names, rows)
+    }
+
+    #[tokio::test]
+    async fn execute_returning_rows() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let rid = Ulid::new();
+        engine.create_resource(rid, None, None, 1, None).await.unwrap();
+
+        let sql = format!(
+            r#"INSERT INTO bookings (resource_id, start, "end") VALUES ('{rid}', 1000, 2000), ('{rid}', 3000, 4000) RETURNING id, start"#
+        );
+        let cmd = sql::parse_sql(&sql).unwrap();
+        let mut responses = handler.execute_command(&engine, cmd).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["id", "start"]);
+        assert_eq!(rows, 2);
+        let bookings = engine.get_bookings(rid).await.unwrap();
+        assert_eq!(bookings.len(), 2);
+
+        // Deleted rules are read before they go.
+        let rule = Ulid::new();
+        engine.add_rule(rule, rid, Span::new(0, 10_000), false).await.unwrap();
+        let cmd = sql::parse_sql(&format!("DELETE FROM rules WHERE id = '{rule}' RETURNING *")).unwrap();
+        let mut responses = handler.execute_command(&engine, cmd).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names.len(), rules_schema().len());
+        assert_eq!(rows, 1);
+
+        // A skipped insert returns nothing.
+        let sql = format!(
+            r#"INSERT INTO bookings (id, resource_id, start, "end") VALUES ('{}', '{rid}', 5000, 6000) ON CONFLICT (id) DO NOTHING RETURNING id"#,
+            bookings[0].id
+        );
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        assert_eq!(returned_rows(responses.remove(0)).await.1, 0);
+
+        let sql = format!("UPDATE bookings SET status = 'completed' WHERE id = '{}' RETURNING nope", bookings[0].id);
+        assert!(handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn execute_clone_subtree() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let room = Ulid::new();
+        let desk = Ulid::new();
+        engine.create_resource(room, None, None, 1, None).await.unwrap();
+        engine.create_resource(desk, Some(room), None, 1, None).await.unwrap();
+
+        let sql = format!("INSERT INTO resource_clones (source_id) VALUES ('{room}')");
+        assert_eq!(schema_for_sql(&sql).len(), 2);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["source_id", "id"]);
+        assert_eq!(rows, 2);
+        assert_eq!(engine.list_resources().len(), 4);
+    }
+
+    #[test]
+    fn conflict_error_detail_is_json() {
+        let id = Ulid::new();
+        let detail = ConflictDetail {
+            intervals: vec![crate::engine::ConflictingInterval {
+                id,
+                kind: "hold",
+                span: Span::new(1000, 2000),
+                buffered: true,
+            }],
+            saturated: vec![Span::new(2000, 2500)],
+            alternatives: Vec::new(),
+        };
+        let group = EngineError::GroupLimitReached(Ulid::new(), Box::new(detail.clone()));
+        for e in [EngineError::Conflict(id, Box::new(detail)), group] {
+            let PgWireError::UserError(info) = engine_err(e) else {
+                panic!("expected a user error");
+            };
+            let json: serde_json::Value = serde_json::from_str(info.detail.as_deref().unwrap()).unwrap();
+            assert_eq!(json["conflicts"][0]["id"], id.to_string());
+            assert_eq!(json["conflicts"][0]["kind"], "hold");
+            assert_eq!(json["conflicts"][0]["buffered"], true);
+            assert_eq!(json["saturated"][0]["start"], 2000);
+        }
+        let PgWireError::UserError(info) = engine_err(EngineError::NotFound(id)) else {
+            panic!("expected a user error");
+        };
+        assert!(info.detail.is_none());
+    }
+
+    #[tokio::test]
+    async fn execute_update_resource_keeps_unassigned_settings() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let (parent, rid) = (Ulid::new(), Ulid::new());
+        engine.create_resource(parent, None, None, 1, None).await.unwrap();
+        let sql = format!(
+            "INSERT INTO resources (id, parent_id, inheritance, buffer_before, min_duration, granularity_ms, \
+             quota_window_ms, quota_max_count, position) VALUES ('{rid}', '{parent}', 'union', 600, 1800000, 900000, 604800000, 2, 3)"
+        );
+        handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let before = engine.get_resource(&rid).unwrap().read().await.settings.clone();
+
+        let sql = format!("UPDATE resources SET name = 'Desk 4' WHERE id = '{rid}'");
+        handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let rs = engine.get_resource(&rid).unwrap();
+        assert_eq!(rs.read().await.name.as_deref(), Some("Desk 4"));
+        assert_eq!(rs.read().await.settings, before);
+
+        // Assigned settings change; the rest stay.
+        let sql = format!("UPDATE resources SET max_duration = 3600000, position = NULL WHERE id = '{rid}'");
+        handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let settings = rs.read().await.settings.clone();
+        assert_eq!((settings.policy.min_duration, settings.policy.max_duration), (Some(1_800_000), Some(3_600_000)));
+        assert_eq!(settings.position, None);
+        assert_eq!(settings.inheritance, InheritanceMode::Union);
+        assert_eq!(settings.quota, before.quota);
+    }
+
+    #[test]
+    fn invalid_argument_is_a_parameter_error() {
+        let PgWireError::UserError(info) = engine_err(EngineError::InvalidArgument("min_duration exceeds max_duration")) else {
+            panic!("expected a user error");
+        };
+        assert_eq!(info.code, "22023");
+        let PgWireError::UserError(info) = engine_err(EngineError::LimitExceeded("span too wide")) else {
+            panic!("expected a user error");
+        };
+        assert_eq!(info.code, "P0001");
+    }
+
+    #[tokio::test]
+    async fn execute_explain() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let rid = Ulid::new();
+        let taken = Ulid::new();
+        engine.create_resource(rid, None, None, 1, None).await.unwrap();
+        engine.confirm_booking(taken, rid, Span::new(1000, 2000), None).await.unwrap();
+
+        let book = |start: i64| {
+            format!(
+                "EXPLAIN INSERT INTO bookings (id, resource_id, start, \"end\") VALUES ('{}', '{rid}', {start}, {})",
+                Ulid::new(),
+                start + 1000
+            )
+        };
+        assert_eq!(schema_for_sql(&book(0)).len(), 3);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&book(3000)).unwrap()).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["would_succeed", "error", "detail"]);
+        assert_eq!(rows, 1);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&book(1500)).unwrap()).await.unwrap();
+        assert_eq!(returned_rows(responses.remove(0)).await.1, 1);
+        // Neither booking was made.
+        assert_eq!(engine.get_bookings(rid).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn execute_utilization() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let rid = Ulid::new();
+        engine.create_resource(rid, None, None, 1, None).await.unwrap();
+        engine.add_rule(Ulid::new(), rid, Span::new(0, 4000), false).await.unwrap();
+        engine.confirm_booking(Ulid::new(), rid, Span::new(1000, 2000), None).await.unwrap();
+
+        let sql = format!("SELECT * FROM utilization WHERE resource_id = '{rid}' AND start >= 0 AND \"end\" <= 4000 AND bucket = 2000");
+        assert_eq!(schema_for_sql(&sql).len(), 7);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["resource_id", "start", "end", "open_ms", "booked_ms", "held_ms", "utilization"]);
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn execute_busy() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let rid = Ulid::new();
+        engine.create_resource(rid, None, None, 1, None).await.unwrap();
+        engine.add_rule(Ulid::new(), rid, Span::new(0, 10_000), false).await.unwrap();
+        engine.confirm_booking(Ulid::new(), rid, Span::new(1000, 2000), None).await.unwrap();
+
+        let sql = format!("SELECT * FROM busy WHERE resource_id = '{rid}' AND start >= 0 AND \"end\" <= 12000");
+        assert_eq!(schema_for_sql(&sql).len(), 5);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["start", "end", "cause", "source_id", "interval_ids"]);
+        // The booking, and no open rule past the end of the rule.
+        assert_eq!(rows, 2);
+    }
+
+    #[tokio::test]
+    async fn execute_booking_suggests_alternatives() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let rid = Ulid::new();
+        engine.create_resource(rid, None, None, 1, None).await.unwrap();
+        engine.add_rule(Ulid::new(), rid, Span::new(0, 10_000), false).await.unwrap();
+        engine.confirm_booking(Ulid::new(), rid, Span::new(1000, 2000), None).await.unwrap();
+
+        let sql = format!(
+            "INSERT INTO bookings (id, resource_id, start, \"end\", suggest) VALUES ('{}', '{rid}', 1500, 2500, 2)",
+            Ulid::new()
+        );
+        let Err(PgWireError::UserError(info)) = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await
+        else {
+            panic!("expected a conflict");
+        };
+        let json: serde_json::Value = serde_json::from_str(info.detail.as_deref().unwrap()).unwrap();
+        let alternatives = json["alternatives"].as_array().unwrap();
+        assert_eq!(alternatives.len(), 2);
+        assert_eq!((&alternatives[0]["start"], &alternatives[0]["end"]), (&2000.into(), &3000.into()));
+        assert_eq!(alternatives[1]["start"], 0);
+
+        // Asking for more suggestions than can be given is refused up front.
+        let sql = format!(
+            "INSERT INTO bookings (id, resource_id, start, \"end\", suggest) VALUES ('{}', '{rid}', 1500, 2500, {})",
+            Ulid::new(),
+            crate::limits::MAX_ALTERNATIVES + 1
+        );
+        assert!(matches!(sql::parse_sql(&sql), Err(sql::SqlError::Parse(_))));
+    }
+
+    #[tokio::test]
+    async fn execute_book_block() {
+        let (handler, _rx, engine) = setup_handler_with_subs();
+        let row = Ulid::new();
+        engine.create_resource(row, None, None, 1, None).await.unwrap();
+        engine.add_rule(Ulid::new(), row, Span::new(0, 10_000), false).await.unwrap();
+        for position in 1..=3 {
+            let seat = Ulid::new();
+            let settings = ResourceSettings { position: Some(position), ..Default::default() };
+            engine.create_resource_with_settings(seat, Some(row), None, 1, None, settings).await.unwrap();
+            engine.add_rule(Ulid::new(), seat, Span::new(0, 10_000), false).await.unwrap();
+        }
+
+        let sql = format!("INSERT INTO block_bookings (parent_id, count, start, \"end\") VALUES ('{row}', 2, 1000, 2000)");
+        assert_eq!(schema_for_sql(&sql).len(), 2);
+        let mut responses = handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.unwrap();
+        let (names, rows) = returned_rows(responses.remove(0)).await;
+        assert_eq!(names, ["id", "resource_id"]);
+        assert_eq!(rows, 2);
+        assert!(handler.execute_command(&engine, sql::parse_sql(&sql).unwrap()).await.is_err());
+    }
 }